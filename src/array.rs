@@ -0,0 +1,60 @@
+use ndarray::{Array, ArrayBase, Axis, Data, RemoveAxis};
+
+use crate::Permutation;
+
+pub fn permute_axis<P, A, S, D>(
+    perm: &P,
+    array: &ArrayBase<S, D>,
+    axis: Axis,
+) -> Option<Array<A, D>>
+where
+    P: Permutation,
+    A: Clone,
+    S: Data<Elem = A>,
+    D: RemoveAxis,
+{
+    if axis.index() >= array.ndim() || array.len_of(axis) as u64 != perm.num_points() {
+        return None;
+    }
+
+    let indices = (0..array.len_of(axis))
+        .map(|i| perm.nth_usize(i))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(array.select(axis, &indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array2};
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{RandomPermutation, UniformPermutation};
+
+    #[test]
+    fn test_permute_rows() {
+        let perm = UniformPermutation::try_from(vec![2, 0, 1]).unwrap();
+        let a = array![[0, 1], [2, 3], [4, 5]];
+
+        assert_eq!(
+            perm.permute_axis(&a, Axis(0)),
+            Some(array![[4, 5], [0, 1], [2, 3]])
+        );
+        assert_eq!(perm.permute_axis(&a, Axis(1)), None);
+        assert_eq!(perm.permute_axis(&a, Axis(2)), None);
+    }
+
+    #[test]
+    fn test_permute_columns() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let perm = RandomPermutation::with_rng(60, &mut rng).unwrap();
+        let a = Array2::from_shape_fn((3, 60), |(i, j)| i * 100 + j);
+        let b = perm.permute_axis(&a.view(), Axis(1)).unwrap();
+
+        for ((i, j), &x) in b.indexed_iter() {
+            assert_eq!(x, i * 100 + perm.nth_usize(j).unwrap());
+        }
+    }
+}
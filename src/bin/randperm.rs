@@ -0,0 +1,146 @@
+//! A command line interface to the library. Permutations are generated with
+//! `RandomPermutation::hybrid_with_rng`, or a `PhiloxPermutation` if `N` is too large for a table
+//! and not supported by the CRT construction, and a `Xoshiro256StarStar` RNG seeded with
+//! `seed_from_u64`, so the same `N` and seed always give the same permutation.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256StarStar;
+use randperm_crt::{
+    external::{ExternalShuffle, LineIndex},
+    InvertiblePermutation, Permutation, PhiloxPermutation, RandomPermutation,
+    DEFAULT_UNIFORM_THRESHOLD,
+};
+
+const USAGE: &str = "\
+Usage:
+    randperm gen N [--seed S]        Print the images of 0, 1, ..., N - 1, one per line
+    randperm nth N I [--seed S]      Print the image of I
+    randperm invert N I [--seed S]   Print the preimage of I
+    randperm shuffle FILE [--seed S] Print the lines of FILE (or stdin if FILE is -) shuffled
+
+Lines are shuffled without loading them all into memory, so stdin is first copied to a temporary
+file. If no seed is given, a random one is used and printed to stderr.";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (args, seed) = match args {
+        [rest @ .., flag, seed] if flag == "--seed" => (rest, parse(seed)?),
+        _ => {
+            let seed = rand::random();
+            eprintln!("seed: {seed}");
+            (args, seed)
+        }
+    };
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    match args {
+        [command, n] if command == "gen" => {
+            for a in permutation(parse(n)?, seed)?.iter() {
+                writeln!(out, "{a}").map_err(|e| e.to_string())?;
+            }
+        }
+        [command, n, i] if command == "nth" => {
+            let a = permutation(parse(n)?, seed)?.nth(parse(i)?);
+            writeln!(out, "{}", a.ok_or("index out of range")?).map_err(|e| e.to_string())?;
+        }
+        [command, n, i] if command == "invert" => {
+            let a = permutation(parse(n)?, seed)?.preimage(parse(i)?);
+            writeln!(out, "{}", a.ok_or("index out of range")?).map_err(|e| e.to_string())?;
+        }
+        [command, file] if command == "shuffle" && file == "-" => {
+            // Stdin can only be read once, so it is copied to a temporary file and shuffled from
+            // there, which keeps the memory usage independent of the input size.
+            let path = std::env::temp_dir().join(format!("randperm-stdin-{}", std::process::id()));
+            let result = File::create(&path)
+                .and_then(|mut file| io::copy(&mut io::stdin().lock(), &mut file))
+                .map_err(|e| format!("{}: {e}", path.display()))
+                .and_then(|_| shuffle_file(&path, seed, &mut out));
+            let _ = fs::remove_file(&path);
+            result?;
+        }
+        [command, file] if command == "shuffle" => shuffle_file(Path::new(file), seed, &mut out)?,
+        _ => return Err("invalid arguments".to_string()),
+    }
+
+    out.flush().map_err(|e| e.to_string())
+}
+
+// Shuffles the lines of a file without loading it into memory.
+fn shuffle_file<W: Write>(path: &Path, seed: u64, out: W) -> Result<(), String> {
+    let open = || File::open(path).map_err(|e| format!("{}: {e}", path.display()));
+    let index = LineIndex::new(open()?).map_err(|e| format!("{}: {e}", path.display()))?;
+    if !index.is_empty() {
+        let perm = permutation(index.len(), seed)?;
+        ExternalShuffle::new()
+            .shuffle_lines(&perm, &index, open()?, out)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn parse(s: &str) -> Result<u64, String> {
+    s.parse().map_err(|_| format!("invalid number: {s}"))
+}
+
+fn permutation(n: u64, seed: u64) -> Result<RandomPermutation, String> {
+    let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+    RandomPermutation::hybrid_with_rng(n, DEFAULT_UNIFORM_THRESHOLD, &mut rng)
+        .or_else(|| PhiloxPermutation::with_rng(n, &mut rng).map(Into::into))
+        .ok_or_else(|| format!("unsupported number of points: {n}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation() {
+        assert!(permutation(0, 0).is_err());
+
+        // Too large for a table, and 1048583 is prime, so this falls back to `PhiloxPermutation`.
+        for n in [1009, 1 << 20, 1048583] {
+            let perm = permutation(n, 0).unwrap();
+            assert_eq!(perm.num_points(), n);
+            assert_eq!(perm.preimage(perm.nth(n - 1).unwrap()), Some(n - 1));
+        }
+    }
+
+    #[test]
+    fn test_shuffle_prime_line_count() {
+        let n = 1048583;
+        let path = std::env::temp_dir().join(format!("randperm-cli-test-{}", std::process::id()));
+        let text = (0..n).map(|i| format!("{i}\n")).collect::<String>();
+        fs::write(&path, &text).unwrap();
+
+        let mut out = Vec::new();
+        let result = shuffle_file(&path, 0, &mut out);
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<u64>>();
+        assert_ne!(lines, (0..n).collect::<Vec<_>>());
+        lines.sort_unstable();
+        assert!(lines.into_iter().eq(0..n));
+    }
+}
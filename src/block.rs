@@ -0,0 +1,194 @@
+use rand::Rng;
+
+use crate::{Inversed, InvertiblePermutation, Permutation, PermutationExt, RandomPermutation};
+
+/// A two-level permutation of `0..b * s`, for data stored in `b` blocks of `s` points each.
+///
+/// The blocks are permuted by `outer`, and the points within block `i` of the input are permuted
+/// by `inner[i]` before being moved to block `outer(i)`, so point `i * s + j` maps to
+/// `outer(i) * s + inner[i](j)`. Iterating in order therefore visits all the points of one block
+/// before moving on to the next, with the blocks in a random order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockPermutation<P = RandomPermutation, Q = P> {
+    outer: P,
+    inner: Vec<Q>,
+    block_size: u64,
+    num_points: u64,
+}
+
+impl BlockPermutation {
+    /// Generates independent permutations of the blocks and of each block. Returns `None` if
+    /// `num_blocks` or `block_size` isn't supported by `RandomPermutation::new`, or if the total
+    /// number of points doesn't fit in a `u64`.
+    pub fn with_rng<R: Rng + ?Sized>(
+        num_blocks: u64,
+        block_size: u64,
+        rng: &mut R,
+    ) -> Option<Self> {
+        num_blocks.checked_mul(block_size)?;
+
+        let outer = RandomPermutation::with_rng(num_blocks, rng)?;
+        let inner = (0..num_blocks)
+            .map(|_| RandomPermutation::with_rng(block_size, rng))
+            .collect::<Option<_>>()?;
+        Self::new(outer, inner)
+    }
+}
+
+impl<P: Permutation, Q: Permutation> BlockPermutation<P, Q> {
+    /// Returns `None` if there isn't one inner permutation for each block, if the inner
+    /// permutations have different numbers of points, or if the total number of points doesn't
+    /// fit in a `u64`.
+    #[must_use]
+    pub fn new(outer: P, inner: Vec<Q>) -> Option<Self> {
+        let block_size = inner.first()?.num_points();
+        if inner.len() as u64 != outer.num_points()
+            || inner.iter().any(|q| q.num_points() != block_size)
+        {
+            return None;
+        }
+
+        Some(Self {
+            num_points: outer.num_points().checked_mul(block_size)?,
+            outer,
+            inner,
+            block_size,
+        })
+    }
+
+    #[must_use]
+    pub fn num_blocks(&self) -> u64 {
+        self.outer.num_points()
+    }
+
+    #[must_use]
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> (P, Vec<Q>) {
+        (self.outer, self.inner)
+    }
+}
+
+impl<P: Permutation, Q: Permutation> Permutation for BlockPermutation<P, Q> {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points {
+            return None;
+        }
+
+        let (block, offset) = (n / self.block_size, n % self.block_size);
+        let inner = &self.inner[block as usize];
+        Some(self.outer.nth(block)? * self.block_size + inner.nth(offset)?)
+    }
+}
+
+impl<P: InvertiblePermutation, Q: InvertiblePermutation> InvertiblePermutation
+    for BlockPermutation<P, Q>
+{
+    type Inverse<'a>
+        = Inversed<&'a Self>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        if x >= self.num_points {
+            return None;
+        }
+
+        let block = self.outer.preimage(x / self.block_size)?;
+        let offset = self.inner[block as usize].preimage(x % self.block_size)?;
+        Some(block * self.block_size + offset)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{PhiloxPermutation, UniformPermutation};
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for (b, s) in [(1, 1), (1, 12), (12, 1), (8, 45), (30, 7)] {
+            let p = BlockPermutation::with_rng(b, s, &mut rng).unwrap();
+            assert_eq!(p.num_points(), b * s);
+            assert_eq!(p.nth(b * s), None);
+
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort();
+            assert!(values.into_iter().eq(0..b * s));
+
+            for n in 0..b * s {
+                assert_eq!(p.preimage(p.nth(n).unwrap()), Some(n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_blocks_stay_together() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = BlockPermutation::with_rng(6, 10, &mut rng).unwrap();
+
+        for chunk in p.iter().collect::<Vec<_>>().chunks(10) {
+            let mut chunk = chunk.to_vec();
+            chunk.sort();
+            assert_eq!(chunk[0] % 10, 0);
+            assert!(chunk.iter().copied().eq(chunk[0]..chunk[0] + 10));
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let outer = UniformPermutation::try_from(vec![1, 0]).unwrap();
+        let inner = [vec![0, 2, 1], vec![2, 1, 0]]
+            .map(|v| UniformPermutation::try_from(v).unwrap())
+            .to_vec();
+        let p = BlockPermutation::new(outer.clone(), inner.clone()).unwrap();
+        assert!(p.iter().eq([3, 5, 4, 2, 1, 0]));
+        assert_eq!((p.num_blocks(), p.block_size()), (2, 3));
+
+        assert_eq!(
+            BlockPermutation::new(outer.clone(), inner[..1].to_vec()),
+            None
+        );
+        assert_eq!(
+            BlockPermutation::new(outer, Vec::<UniformPermutation>::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mixed_types() {
+        let outer = PhiloxPermutation::with_key(1000, 0).unwrap();
+        let inner = (0..1000)
+            .map(|k| PhiloxPermutation::with_key(257, k).unwrap())
+            .collect();
+        let p = BlockPermutation::new(outer, inner).unwrap();
+
+        for n in (0..257000).step_by(997) {
+            assert_eq!(p.preimage(p.nth(n).unwrap()), Some(n));
+        }
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        assert_eq!(BlockPermutation::with_rng(257, 2, &mut rng), None);
+        assert_eq!(BlockPermutation::with_rng(2, 0, &mut rng), None);
+        assert_eq!(BlockPermutation::with_rng(1 << 40, 1 << 40, &mut rng), None);
+    }
+}
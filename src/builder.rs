@@ -0,0 +1,362 @@
+use std::fmt;
+
+use rand::{rngs::ThreadRng, Rng};
+
+use crate::{
+    FactoredInteger, PrimeSet, RandomPermutation, UniformPermutation, DEFAULT_IMPLICIT_THRESHOLD,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    ZeroPoints,
+    ZeroRounds,
+    /// `n` has a prime factor that isn't allowed by the smoothness bound or prime set.
+    NotSmooth,
+    /// The tables would take `required` bytes, which is more than the memory budget.
+    MemoryBudgetExceeded {
+        required: u64,
+        budget: u64,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroPoints => write!(f, "number of points is zero"),
+            Self::ZeroRounds => write!(f, "number of rounds is zero"),
+            Self::NotSmooth => write!(f, "number of points has a prime factor that is too large"),
+            Self::MemoryBudgetExceeded { required, budget } => write!(
+                f,
+                "tables need {required} bytes, but the memory budget is {budget} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Collects the options for generating a `RandomPermutation`. See `RandomPermutation::builder`.
+///
+/// There is no option for the storage width of the tables: every table entry is a `u64`, since
+/// the tables are exposed as `&[u64]` (see `Component::table`) and stored that way by `to_bytes`
+/// and `write_mappable`.
+#[derive(Debug, Clone)]
+pub struct RandomPermutationBuilder<R = ThreadRng> {
+    num_points: u64,
+    rng: R,
+    rounds: usize,
+    uniform_threshold: u64,
+    primes: PrimeSet,
+    implicit_threshold: u64,
+    memory_budget: Option<u64>,
+}
+
+impl RandomPermutationBuilder {
+    /// A builder for a single CRT round with the thread-local RNG, no uniform fallback, and no
+    /// memory budget, i.e. the same as `RandomPermutation::new`.
+    #[must_use]
+    pub fn new(n: u64) -> Self {
+        Self {
+            num_points: n,
+            rng: rand::thread_rng(),
+            rounds: 1,
+            uniform_threshold: 0,
+            primes: PrimeSet::default(),
+            implicit_threshold: DEFAULT_IMPLICIT_THRESHOLD,
+            memory_budget: None,
+        }
+    }
+}
+
+impl<R: Rng> RandomPermutationBuilder<R> {
+    /// Uses `rng` instead of the thread-local RNG. Pass `&mut rng` to keep using it afterwards.
+    #[must_use]
+    pub fn rng<S: Rng>(self, rng: S) -> RandomPermutationBuilder<S> {
+        RandomPermutationBuilder {
+            num_points: self.num_points,
+            rng,
+            rounds: self.rounds,
+            uniform_threshold: self.uniform_threshold,
+            primes: self.primes,
+            implicit_threshold: self.implicit_threshold,
+            memory_budget: self.memory_budget,
+        }
+    }
+
+    /// See `RandomPermutation::with_rng_rounds`.
+    #[must_use]
+    pub fn rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Generates a single uniformly random table if `n < threshold`, like
+    /// `RandomPermutation::hybrid_with_rng`. Below the threshold, `rounds` and the smoothness bound
+    /// are ignored.
+    #[must_use]
+    pub fn uniform_threshold(mut self, threshold: u64) -> Self {
+        self.uniform_threshold = threshold;
+        self
+    }
+
+    /// Only allows `n` whose prime factors are all at most `bound`. The default is 255. Smaller
+    /// bounds limit the size of the tables, and larger bounds accept more `n`, up to
+    /// `PrimeSet::MAX_BOUND`. See `PrimeSet::up_to`.
+    #[must_use]
+    pub fn smoothness_bound(mut self, bound: u64) -> Self {
+        self.primes = PrimeSet::up_to(bound);
+        self
+    }
+
+    /// Only allows `n` whose prime factors are all in `primes`. This replaces the smoothness
+    /// bound.
+    #[must_use]
+    pub fn primes(mut self, primes: PrimeSet) -> Self {
+        self.primes = primes;
+        self
+    }
+
+    /// Represents prime power components with more than `len` points by a table-free bijection
+    /// (like `PhiloxPermutation`) instead of a table. These take no memory, but are slower to
+    /// evaluate. The default is `DEFAULT_IMPLICIT_THRESHOLD`.
+    #[must_use]
+    pub fn implicit_threshold(mut self, len: u64) -> Self {
+        self.implicit_threshold = len;
+        self
+    }
+
+    /// Fails instead of generating tables that take more than `bytes` bytes in total, at 8 bytes
+    /// per table entry.
+    #[must_use]
+    pub fn memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    pub fn build(mut self) -> Result<RandomPermutation, BuildError> {
+        let factors = self.factors()?;
+
+        let required = self.bytes_needed(factors.as_ref());
+        if let Some(budget) = self.memory_budget.filter(|&budget| required > budget) {
+            return Err(BuildError::MemoryBudgetExceeded { required, budget });
+        }
+
+        let n = self.num_points;
+        Ok(match factors {
+            None => UniformPermutation::with_rng(n, &mut self.rng).into(),
+            Some(factors) => RandomPermutation::from_factors(
+                n,
+                &factors.factors,
+                self.rounds,
+                self.implicit_threshold,
+                &mut self.rng,
+            ),
+        })
+    }
+
+    /// Returns the number of bytes that the tables would take, without generating them. This is
+    /// what `memory_budget` is compared against, so it can be used to choose a budget or to
+    /// report the size up front.
+    pub fn required_bytes(&self) -> Result<u64, BuildError> {
+        Ok(self.bytes_needed(self.factors()?.as_ref()))
+    }
+
+    // Returns `None` if a single uniform table will be generated, and the factors of `n` to
+    // generate CRT rounds from otherwise.
+    fn factors(&self) -> Result<Option<FactoredInteger>, BuildError> {
+        let n = self.num_points;
+        if n == 0 {
+            return Err(BuildError::ZeroPoints);
+        }
+
+        if n < self.uniform_threshold {
+            return Ok(None);
+        }
+
+        if self.rounds == 0 {
+            return Err(BuildError::ZeroRounds);
+        }
+
+        FactoredInteger::with_primes(n, &self.primes)
+            .map(Some)
+            .ok_or(BuildError::NotSmooth)
+    }
+
+    fn bytes_needed(&self, factors: Option<&FactoredInteger>) -> u64 {
+        let entries = match factors {
+            None => self.num_points,
+            Some(factors) => factors
+                .factors
+                .iter()
+                .map(|&(p, k)| (p as u64).pow(k as u32))
+                .filter(|&pk| pk <= self.implicit_threshold)
+                .sum::<u64>()
+                .saturating_mul(self.rounds as u64),
+        };
+
+        entries.saturating_mul(size_of::<u64>() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{InvertiblePermutation, Mode, Permutation};
+
+    #[test]
+    fn test_matches_constructors() {
+        let rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        let p = RandomPermutation::builder(3600)
+            .rng(rng.clone())
+            .build()
+            .unwrap();
+        assert_eq!(
+            p,
+            RandomPermutation::with_rng(3600, &mut rng.clone()).unwrap()
+        );
+
+        let p = RandomPermutation::builder(3600)
+            .rng(rng.clone())
+            .rounds(3)
+            .build()
+            .unwrap();
+        let q = RandomPermutation::with_rng_rounds(3600, 3, &mut rng.clone()).unwrap();
+        assert_eq!(p, q);
+
+        let p = RandomPermutation::builder(1009)
+            .rng(rng.clone())
+            .uniform_threshold(2000)
+            .build()
+            .unwrap();
+        let q = RandomPermutation::hybrid_with_rng(1009, 2000, &mut rng.clone()).unwrap();
+        assert_eq!(p, q);
+        assert_eq!(p.mode(), Mode::Uniform);
+    }
+
+    #[test]
+    fn test_errors() {
+        let rng = Xoshiro256StarStar::seed_from_u64(1);
+        let builder = |n| RandomPermutation::builder(n).rng(rng.clone());
+
+        assert_eq!(builder(0).build(), Err(BuildError::ZeroPoints));
+        assert_eq!(builder(360).rounds(0).build(), Err(BuildError::ZeroRounds));
+        assert_eq!(builder(1009).build(), Err(BuildError::NotSmooth));
+        assert_eq!(
+            builder(2 * 3 * 7).smoothness_bound(5).build(),
+            Err(BuildError::NotSmooth)
+        );
+        assert!(builder(2 * 3 * 7).smoothness_bound(7).build().is_ok());
+
+        // 3600 = 16 * 9 * 25, so each round has 50 table entries.
+        assert_eq!(
+            builder(3600).rounds(2).memory_budget(799).build(),
+            Err(BuildError::MemoryBudgetExceeded {
+                required: 800,
+                budget: 799
+            })
+        );
+        assert!(builder(3600).rounds(2).memory_budget(800).build().is_ok());
+        assert!(builder(100)
+            .uniform_threshold(1000)
+            .memory_budget(799)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_required_bytes() {
+        let builder = RandomPermutation::builder;
+
+        assert_eq!(builder(3600).required_bytes(), Ok(400));
+        assert_eq!(builder(3600).rounds(3).required_bytes(), Ok(1200));
+        assert_eq!(
+            builder(3600).uniform_threshold(4000).required_bytes(),
+            Ok(28800)
+        );
+        assert_eq!(builder(3 << 40).required_bytes(), Ok(24));
+        assert_eq!(
+            builder(3 << 40)
+                .implicit_threshold(u64::MAX)
+                .required_bytes(),
+            Ok((8 << 40) + 24)
+        );
+        assert_eq!(builder(0).required_bytes(), Err(BuildError::ZeroPoints));
+        assert_eq!(builder(1009).required_bytes(), Err(BuildError::NotSmooth));
+
+        // A large prime power fails quickly, without trying to allocate anything.
+        assert_eq!(
+            builder(1 << 40)
+                .implicit_threshold(u64::MAX)
+                .memory_budget(1 << 30)
+                .build(),
+            Err(BuildError::MemoryBudgetExceeded {
+                required: 8 << 40,
+                budget: 1 << 30
+            })
+        );
+    }
+
+    #[test]
+    fn test_implicit_threshold() {
+        let rng = Xoshiro256StarStar::seed_from_u64(3);
+        let builder = |n| RandomPermutation::builder(n).rng(rng.clone());
+
+        // Without a threshold, this would need a table with 2^40 entries.
+        let n = 3 << 40;
+        let p = builder(n).memory_budget(1 << 10).build().unwrap();
+        assert!(p.verify());
+        for i in (0..n).step_by(1 << 30).chain([n - 1]) {
+            assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+        }
+
+        // Implicit components also work below the default threshold.
+        let p = builder(3600).implicit_threshold(10).build().unwrap();
+        assert!(p.verify());
+        let mut values = p.iter().collect::<Vec<_>>();
+        values.sort();
+        assert!(values.into_iter().eq(0..3600));
+
+        // Tables that fit below the default threshold are unchanged.
+        assert_eq!(
+            builder(3600).build(),
+            builder(3600).implicit_threshold(3600).build()
+        );
+    }
+
+    #[test]
+    fn test_primes() {
+        let rng = Xoshiro256StarStar::seed_from_u64(2);
+        let builder = |n| RandomPermutation::builder(n).rng(rng.clone());
+
+        let p = builder(1009 * 12).smoothness_bound(1009).build().unwrap();
+        assert!(p.verify());
+        assert_eq!(
+            builder(1009 * 12).smoothness_bound(1008).build(),
+            Err(BuildError::NotSmooth)
+        );
+
+        let primes = PrimeSet::from_primes([2, 65537]).unwrap();
+        let p = builder(4 * 65537).primes(primes.clone()).build().unwrap();
+        assert!(p.verify());
+        assert_eq!(
+            builder(3 * 65537).primes(primes).build(),
+            Err(BuildError::NotSmooth)
+        );
+
+        // The default is the same as `RandomPermutation::new`.
+        assert_eq!(
+            builder(3600).primes(PrimeSet::default()).build(),
+            builder(3600).build()
+        );
+    }
+
+    #[test]
+    fn test_thread_rng() {
+        let p = RandomPermutation::builder(360).build().unwrap();
+        assert_eq!(p.num_points(), 360);
+    }
+}
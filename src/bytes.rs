@@ -0,0 +1,368 @@
+use std::{fmt, io};
+
+use crate::{
+    round::{ImplicitPerm, Round, SubPerm, SubPerms},
+    Permutation, PhiloxPermutation, RandomPermutation,
+};
+
+const MAGIC: &[u8; 4] = b"RPCR";
+
+pub(crate) const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FromBytesError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    InvalidVarint,
+    TrailingBytes,
+    InvalidTables,
+    ChecksumMismatch,
+}
+
+impl fmt::Display for FromBytesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "invalid magic header"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            Self::UnexpectedEnd => write!(f, "unexpected end of input"),
+            Self::InvalidVarint => write!(f, "invalid varint"),
+            Self::TrailingBytes => write!(f, "trailing bytes after permutation"),
+            Self::InvalidTables => write!(f, "tables do not describe a permutation"),
+            Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for FromBytesError {}
+
+impl From<FromBytesError> for io::Error {
+    fn from(err: FromBytesError) -> Self {
+        Self::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+fn entry_width(modulus: u64) -> usize {
+    match modulus - 1 {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x10000..=0xffff_ffff => 4,
+        _ => 8,
+    }
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+/// Writes a round, using `write_table` to write the entries of each table after its modulus.
+pub(crate) fn write_round(out: &mut Vec<u8>, round: &Round, write_table: fn(&mut Vec<u8>, &[u64])) {
+    out.push(round.inverted as u8);
+    write_varint(out, round.sub_perms.len() as u64);
+
+    for perm in round.sub_perms.iter() {
+        match perm {
+            SubPerm::Table(table) => {
+                write_varint(out, table.len() as u64);
+                write_table(out, table);
+            }
+            SubPerm::Implicit(perm) => {
+                // A modulus of 0 can't be a table, so it marks an implicit component.
+                write_varint(out, 0);
+                write_varint(out, perm.perm.num_points());
+                out.extend_from_slice(&perm.perm.key().to_le_bytes());
+                out.push(perm.inverted as u8);
+            }
+        }
+    }
+}
+
+fn write_inline_table(out: &mut Vec<u8>, table: &[u64]) {
+    let width = entry_width(table.len() as u64);
+    for &a in table {
+        out.extend_from_slice(&a.to_le_bytes()[..width]);
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+/// Reads the entries of a table with modulus `pk` into `sub_perms`.
+pub(crate) type ReadTable = fn(&mut Reader<'_>, &mut SubPerms, u64) -> Result<(), FromBytesError>;
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], FromBytesError> {
+        if len > self.bytes.len() {
+            return Err(FromBytesError::UnexpectedEnd);
+        }
+
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u64, FromBytesError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            let bits = (byte & 0x7f) as u64;
+            if bits << shift >> shift != bits {
+                return Err(FromBytesError::InvalidVarint);
+            }
+
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(FromBytesError::InvalidVarint)
+    }
+
+    pub(crate) fn flag(&mut self) -> Result<bool, FromBytesError> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(FromBytesError::InvalidTables),
+        }
+    }
+
+    /// Reads a round, appending its components to `sub_perms`.
+    pub(crate) fn round(
+        &mut self,
+        mut sub_perms: SubPerms,
+        read_table: ReadTable,
+    ) -> Result<Round, FromBytesError> {
+        let inverted = self.flag()?;
+        let num_sub_perms = self.varint()?;
+
+        for _ in 0..num_sub_perms {
+            let pk = self.varint()?;
+            if pk == 0 {
+                // An implicit component: the modulus, the key, and whether it's inverted.
+                let pk = self.varint()?;
+                let key = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                let perm =
+                    PhiloxPermutation::with_key(pk, key).ok_or(FromBytesError::InvalidTables)?;
+                let inverted = self.flag()?;
+                sub_perms.push_implicit(ImplicitPerm { perm, inverted });
+                continue;
+            }
+
+            read_table(self, &mut sub_perms, pk)?;
+        }
+
+        let mut round = Round::new(sub_perms).ok_or(FromBytesError::InvalidTables)?;
+        round.inverted = inverted;
+        Ok(round)
+    }
+
+    fn read_inline_table(
+        &mut self,
+        sub_perms: &mut SubPerms,
+        pk: u64,
+    ) -> Result<(), FromBytesError> {
+        let width = entry_width(pk);
+        let len = usize::try_from(pk)
+            .ok()
+            .and_then(|pk| pk.checked_mul(width))
+            .ok_or(FromBytesError::UnexpectedEnd)?;
+        let table = self.take(len)?;
+
+        let perm = table
+            .chunks_exact(width)
+            .map(|chunk| {
+                let mut buf = [0; 8];
+                buf[..width].copy_from_slice(chunk);
+                u64::from_le_bytes(buf)
+            })
+            .collect::<Vec<_>>();
+        sub_perms.push(SubPerm::Table(&perm));
+        Ok(())
+    }
+}
+
+impl RandomPermutation {
+    /// Encodes the permutation in a compact binary format that can be read by `from_bytes`.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        write_varint(&mut out, self.num_points);
+        write_varint(&mut out, self.rounds.len() as u64);
+
+        for round in &self.rounds {
+            write_round(&mut out, round, write_inline_table);
+        }
+
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FromBytesError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(4).map_err(|_| FromBytesError::InvalidMagic)? != MAGIC {
+            return Err(FromBytesError::InvalidMagic);
+        }
+
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(FromBytesError::UnsupportedVersion(version));
+        }
+
+        let num_points = reader.varint()?;
+        let num_rounds = reader.varint()?;
+
+        let mut rounds = Vec::new();
+        for _ in 0..num_rounds {
+            rounds.push(reader.round(SubPerms::new(), |reader, sub_perms, pk| {
+                reader.read_inline_table(sub_perms, pk)
+            })?);
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(FromBytesError::TrailingBytes);
+        }
+
+        let perm = Self { num_points, rounds };
+
+        if perm.verify() {
+            Ok(perm)
+        } else {
+            Err(FromBytesError::InvalidTables)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 300, 65536, 3113510400, (1..=20).product()] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            assert_eq!(RandomPermutation::from_bytes(&p.to_bytes()), Ok(p));
+        }
+
+        let p = RandomPermutation::with_rng_rounds(3628800, 3, &mut rng).unwrap();
+        assert_eq!(RandomPermutation::from_bytes(&p.to_bytes()), Ok(p));
+    }
+
+    #[test]
+    fn test_implicit() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::builder(3 << 40)
+            .rng(&mut rng)
+            .build()
+            .unwrap();
+
+        // Header, num_points, round count, round flag, component count, the 3-entry table, then
+        // the marker, modulus, key and flag of the implicit component, in either order.
+        let bytes = p.to_bytes();
+        assert_eq!(bytes.len(), 4 + 1 + 6 + 1 + 1 + 1 + 1 + 3 + 1 + 6 + 8 + 1);
+        assert_eq!(RandomPermutation::from_bytes(&bytes), Ok(p));
+    }
+
+    #[test]
+    fn test_inverted() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::builder(3 << 40)
+            .rng(&mut rng)
+            .rounds(2)
+            .build()
+            .unwrap()
+            .into_inverted();
+        assert_eq!(RandomPermutation::from_bytes(&p.to_bytes()), Ok(p));
+
+        let p = RandomPermutation::with_rng(3600, &mut rng)
+            .unwrap()
+            .into_inverted();
+        let mut bytes = p.to_bytes();
+        assert_eq!(RandomPermutation::from_bytes(&bytes), Ok(p));
+
+        bytes[8] = 2;
+        assert_eq!(
+            RandomPermutation::from_bytes(&bytes),
+            Err(FromBytesError::InvalidTables)
+        );
+    }
+
+    #[test]
+    fn test_narrow_entries() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(256 * 243, &mut rng).unwrap();
+
+        // Header, num_points, round count, round flag, component count, then two 2-byte moduli,
+        // each followed by 1-byte entries.
+        assert_eq!(
+            p.to_bytes().len(),
+            4 + 1 + 3 + 1 + 1 + 1 + 2 + 256 + 2 + 243
+        );
+    }
+
+    #[test]
+    fn test_varint() {
+        for n in [0, 1, 127, 128, 300, u64::MAX / 3, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, n);
+            assert_eq!(Reader { bytes: &out }.varint(), Ok(n));
+        }
+
+        let overflow = [0xff; 9].into_iter().chain([0x02]).collect::<Vec<_>>();
+        assert_eq!(
+            Reader { bytes: &overflow }.varint(),
+            Err(FromBytesError::InvalidVarint)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let bytes = RandomPermutation::with_rng(360, &mut rng)
+            .unwrap()
+            .to_bytes();
+
+        let from_bytes = RandomPermutation::from_bytes;
+
+        assert_eq!(from_bytes(b"RP"), Err(FromBytesError::InvalidMagic));
+        assert_eq!(from_bytes(b"XXXX\x01"), Err(FromBytesError::InvalidMagic));
+        for version in [0, 2, 5] {
+            assert_eq!(
+                from_bytes(&[b'R', b'P', b'C', b'R', version]),
+                Err(FromBytesError::UnsupportedVersion(version))
+            );
+        }
+        assert_eq!(
+            from_bytes(&bytes[..bytes.len() - 1]),
+            Err(FromBytesError::UnexpectedEnd)
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(from_bytes(&trailing), Err(FromBytesError::TrailingBytes));
+
+        let mut duplicate = bytes.clone();
+        let last = duplicate.len() - 1;
+        duplicate[last] = duplicate[last - 1];
+        assert_eq!(from_bytes(&duplicate), Err(FromBytesError::InvalidTables));
+
+        let mut wrong_size = bytes.clone();
+        wrong_size[5] += 1;
+        assert_eq!(from_bytes(&wrong_size), Err(FromBytesError::InvalidTables));
+
+        let huge = b"RPCR\x01\x00\x01\x00\x01\xff\xff\xff\xff\xff\xff\xff\xff\x7f";
+        assert_eq!(from_bytes(huge), Err(FromBytesError::UnexpectedEnd));
+    }
+}
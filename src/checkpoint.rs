@@ -0,0 +1,95 @@
+use rand::{Rng, SeedableRng};
+
+use crate::{Permutation, PermutationIter, RandomPermutation};
+
+/// The state of an iteration over a seeded `RandomPermutation`, which can be stored and used to
+/// resume the iteration later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    num_points: u64,
+    seed: u64,
+    index: u64,
+}
+
+impl Checkpoint {
+    /// Records the position of `iter`, which must be iterating over a permutation constructed with
+    /// an RNG seeded by `R::seed_from_u64(seed)`.
+    #[must_use]
+    pub fn new<P: Permutation>(seed: u64, iter: &PermutationIter<'_, P>) -> Self {
+        Self {
+            num_points: iter.perm.num_points(),
+            seed,
+            index: iter.idx,
+        }
+    }
+
+    #[must_use]
+    pub fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    #[must_use]
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Reconstructs the permutation that was being iterated over.
+    #[must_use]
+    pub fn permutation<R: Rng + SeedableRng>(&self) -> Option<RandomPermutation> {
+        RandomPermutation::with_rng(self.num_points, &mut R::seed_from_u64(self.seed))
+    }
+
+    /// Returns an iterator over `perm` starting at the recorded position, or `None` if `perm` has
+    /// the wrong number of points.
+    #[must_use]
+    pub fn resume<'a, P: Permutation>(&self, perm: &'a P) -> Option<PermutationIter<'a, P>> {
+        if perm.num_points() == self.num_points {
+            Some(PermutationIter {
+                perm,
+                idx: self.index,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_resume() {
+        let p = RandomPermutation::with_rng(3628800, &mut Xoshiro256StarStar::seed_from_u64(42))
+            .unwrap();
+        let mut iter = p.iter();
+        iter.by_ref().take(12345).for_each(drop);
+
+        let checkpoint = Checkpoint::new(42, &iter);
+        assert_eq!(checkpoint.index(), 12345);
+
+        let restored = checkpoint.permutation::<Xoshiro256StarStar>().unwrap();
+        assert_eq!(restored, p);
+
+        let resumed = checkpoint.resume(&restored).unwrap();
+        assert!(resumed.take(1000).eq(iter.take(1000)));
+    }
+
+    #[test]
+    fn test_resume_wrong_size() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(100, &mut rng).unwrap();
+        let q = RandomPermutation::with_rng(120, &mut rng).unwrap();
+
+        let checkpoint = Checkpoint::new(0, &p.iter());
+        assert!(checkpoint.resume(&q).is_none());
+    }
+}
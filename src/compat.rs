@@ -0,0 +1,109 @@
+use crate::{FromVecError, Permutation, RandomPermutation, SmallPermutation, UniformPermutation};
+
+// `permutation::Permutation::oneline(v)` maps `i` to `v[i]`, which is the same convention as the
+// tables here.
+
+fn oneline<P: Permutation>(perm: &P) -> permutation::Permutation {
+    permutation::Permutation::oneline(
+        (0..perm.num_points() as usize)
+            .map(|i| perm.nth_usize(i).unwrap())
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn table(perm: permutation::Permutation) -> Vec<u64> {
+    // Applying a permutation to an index takes O(n) time unless it is normalized.
+    let perm = perm.normalize(false);
+    (0..perm.len()).map(|i| perm.apply_idx(i) as u64).collect()
+}
+
+impl From<UniformPermutation> for permutation::Permutation {
+    fn from(perm: UniformPermutation) -> Self {
+        oneline(&perm)
+    }
+}
+
+/// # Panics
+///
+/// Panics if `perm` isn't valid, which `permutation` only checks in debug builds.
+impl From<permutation::Permutation> for UniformPermutation {
+    fn from(perm: permutation::Permutation) -> Self {
+        Self::try_from(table(perm)).unwrap()
+    }
+}
+
+impl<const N: usize> From<SmallPermutation<N>> for permutation::Permutation {
+    fn from(perm: SmallPermutation<N>) -> Self {
+        oneline(&perm)
+    }
+}
+
+/// Fails with `FromVecError::WrongLength` if the permutation doesn't have exactly `N` points.
+impl<const N: usize> TryFrom<permutation::Permutation> for SmallPermutation<N> {
+    type Error = FromVecError;
+
+    fn try_from(perm: permutation::Permutation) -> Result<Self, Self::Error> {
+        Self::try_from(table(perm))
+    }
+}
+
+/// Stores the permutation as a single explicit table, like `From<UniformPermutation>`.
+impl From<permutation::Permutation> for RandomPermutation {
+    fn from(perm: permutation::Permutation) -> Self {
+        UniformPermutation::from(perm).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_uniform() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = UniformPermutation::with_rng(1009, &mut rng);
+        let q = permutation::Permutation::from(p.clone());
+
+        assert_eq!(q.len(), 1009);
+        assert!(p
+            .pairs()
+            .all(|(i, a)| q.apply_idx(i as usize) == a as usize));
+        assert_eq!(UniformPermutation::from(q), p);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let q = permutation::Permutation::oneline([0, 2, 3, 1]).inverse();
+        let p = UniformPermutation::from(q);
+        assert_eq!(p.as_slice(), &[0, 3, 1, 2]);
+    }
+
+    #[test]
+    fn test_small() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = SmallPermutation::<20>::with_rng(&mut rng);
+        let q = permutation::Permutation::from(p);
+
+        assert_eq!(SmallPermutation::<20>::try_from(q.clone()), Ok(p));
+        assert_eq!(
+            SmallPermutation::<10>::try_from(q),
+            Err(FromVecError::WrongLength {
+                expected: 10,
+                found: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_random() {
+        let q = permutation::Permutation::oneline([2, 0, 1]);
+        let p = RandomPermutation::from(q);
+        assert!(p.iter().eq([2, 0, 1]));
+
+        let p = RandomPermutation::from(permutation::Permutation::one(0));
+        assert_eq!(p.num_points(), 0);
+    }
+}
@@ -1,40 +1,247 @@
+use std::fmt;
+#[cfg(feature = "simd")]
+use std::simd::{cmp::SimdPartialOrd, Select, Simd};
+
+use crate::{divisor::Divisor, modmath};
+
+/// Returns the unique `x` in `0..product(moduli)` with `x % moduli[i] == remainders[i]` for all `i`,
+/// or `None` if the moduli aren't pairwise coprime, their product doesn't fit in a `u64`, or the
+/// slices have different lengths.
+#[must_use]
 pub fn chinese_remainder(remainders: &[u64], moduli: &[u64]) -> Option<u64> {
     if remainders.len() != moduli.len() {
         return None;
     }
 
-    let product_of_moduli = moduli.iter().try_fold(1u64, |a, &b| a.checked_mul(b))? as i128;
-    let mut result = 0;
+    let product_of_moduli = moduli.iter().try_fold(1u64, |a, &b| a.checked_mul(b))?;
+    if product_of_moduli == 0 {
+        return None;
+    }
+
+    // Each term `partial_product * (remainder * inverse % modulus)` is less than the product of
+    // the moduli, so reducing after each term keeps the sum below `2 * u64::MAX`.
+    moduli
+        .iter()
+        .zip(remainders)
+        .try_fold(0u64, |result, (&modulus, &remainder)| {
+            let partial_product = product_of_moduli / modulus;
+            let inverse = modmath::mod_inverse(partial_product % modulus, modulus)?;
+            let digit = modmath::mul_mod(remainder % modulus, inverse, modulus)?;
+            let term = partial_product as u128 * digit as u128;
+            Some(((result as u128 + term) % product_of_moduli as u128) as u64)
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrtError {
+    LengthMismatch,
+    ZeroModulus,
+    /// The congruences have no common solution.
+    Inconsistent,
+    /// The least common multiple of the moduli doesn't fit in a `u64`.
+    Overflow,
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LengthMismatch => write!(f, "different numbers of remainders and moduli"),
+            Self::ZeroModulus => write!(f, "modulus is zero"),
+            Self::Inconsistent => write!(f, "congruences have no common solution"),
+            Self::Overflow => write!(f, "least common multiple of moduli overflows u64"),
+        }
+    }
+}
+
+impl std::error::Error for CrtError {}
 
-    for (&remainder, &modulus) in remainders.iter().zip(moduli) {
-        let (remainder, modulus) = (remainder as i128, modulus as i128);
-        let partial_product = product_of_moduli / modulus;
-        let inverse = mod_inverse(partial_product, modulus)?;
-        result += remainder * partial_product * inverse;
+/// Solves `x % moduli[i] == remainders[i] % moduli[i]` for all `i`, where the moduli don't need to
+/// be pairwise coprime.
+///
+/// Returns `(x, lcm)`, where `lcm` is the least common multiple of the moduli and `x` is the unique
+/// solution in `0..lcm`.
+pub fn chinese_remainder_general(
+    remainders: &[u64],
+    moduli: &[u64],
+) -> Result<(u64, u64), CrtError> {
+    if remainders.len() != moduli.len() {
+        return Err(CrtError::LengthMismatch);
     }
 
-    Some((result % product_of_moduli) as u64)
+    remainders
+        .iter()
+        .zip(moduli)
+        .try_fold((0, 1), |(a1, m1), (&a2, &m2)| {
+            if m2 == 0 {
+                return Err(CrtError::ZeroModulus);
+            }
+
+            // Write `x = a1 + m1 * t` and solve `m1 * t == a2 - a1 (mod m2)` for `t`.
+            let g = gcd(m1, m2);
+            let m2_divisor = Divisor::new(m2);
+            let diff = modmath::sub_mod(m2_divisor.rem(a2), m2_divisor.rem(a1), m2);
+            if !diff.is_multiple_of(g) {
+                return Err(CrtError::Inconsistent);
+            }
+
+            let lcm = (m1 / g).checked_mul(m2).ok_or(CrtError::Overflow)?;
+            let reduced = Divisor::new(m2 / g);
+            let inverse = modmath::mod_inverse(m1 / g, m2 / g).unwrap();
+            let t = modmath::mul_mod_divisor(reduced.rem(diff / g), inverse, &reduced);
+
+            Ok((a1 + m1 * t, lcm))
+        })
 }
 
-fn mod_inverse(a: i128, m: i128) -> Option<i128> {
-    let mut mn = (m, a);
-    let mut xy = (0, 1);
+/// Same as `chinese_remainder`, but computed with Garner's algorithm, whose intermediate results
+/// are all less than the product of the moduli. Reductions use a precomputed reciprocal (a `u128`
+/// multiply) instead of a division.
+#[must_use]
+pub fn garner(remainders: &[u64], moduli: &[u64]) -> Option<u64> {
+    CrtBasis::new(moduli)?.combine(remainders)
+}
+
+/// Precomputed data for solving systems of congruences with a fixed set of pairwise coprime moduli.
+///
+/// The solution is built up using Garner's algorithm, one modulus at a time, as
+/// `x = r_0 + m_0 * (t_1 + m_1 * (t_2 + ...))`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrtBasis {
+    divisors: Vec<Divisor>,
+    // `prefixes[i]` is the product of the first `i` moduli, and `inverses[i]` is its inverse
+    // modulo `moduli[i]`.
+    prefixes: Vec<u64>,
+    inverses: Vec<u64>,
+    product: u64,
+}
+
+impl CrtBasis {
+    /// Returns `None` if the moduli aren't pairwise coprime, any of them are zero, or their product
+    /// doesn't fit in a `u64`.
+    #[must_use]
+    pub fn new(moduli: &[u64]) -> Option<Self> {
+        if moduli.contains(&0) {
+            return None;
+        }
+
+        let divisors = moduli.iter().map(|&m| Divisor::new(m)).collect::<Vec<_>>();
+
+        let mut product = 1u64;
+        let mut prefixes = Vec::with_capacity(moduli.len());
+        let mut inverses = Vec::with_capacity(moduli.len());
+        for d in &divisors {
+            prefixes.push(product);
+            inverses.push(modmath::mod_inverse(product, d.get())?);
+            product = product.checked_mul(d.get())?;
+        }
 
-    while mn.1 != 0 {
-        let quotient = mn.0 / mn.1;
-        mn = (mn.1, mn.0 - quotient * mn.1);
-        xy = (xy.1, xy.0 - quotient * xy.1);
+        Some(Self {
+            divisors,
+            prefixes,
+            inverses,
+            product,
+        })
     }
 
-    if mn.0 > 1 {
-        None
-    } else {
-        Some((xy.0 + m) % m)
+    #[must_use]
+    pub fn moduli(&self) -> Vec<u64> {
+        self.divisors.iter().map(Divisor::get).collect()
+    }
+
+    #[must_use]
+    pub fn product(&self) -> u64 {
+        self.product
+    }
+
+    /// Returns the unique `x` in `0..self.product()` with `x % moduli[i] == remainders[i] %
+    /// moduli[i]` for all `i`, or `None` if the number of remainders is wrong.
+    #[must_use]
+    pub fn combine(&self, remainders: &[u64]) -> Option<u64> {
+        if remainders.len() == self.divisors.len() {
+            Some(
+                self.combine_iter(
+                    remainders
+                        .iter()
+                        .zip(&self.divisors)
+                        .map(|(&r, d)| d.rem(r)),
+                ),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Returns the remainders of `x` modulo each of the moduli.
+    #[must_use]
+    pub fn decompose(&self, x: u64) -> Vec<u64> {
+        self.divisors.iter().map(|d| d.rem(x)).collect()
+    }
+
+    pub(crate) fn divisors(&self) -> &[Divisor] {
+        &self.divisors
+    }
+
+    /// Like `combine`, but the remainders must already be reduced.
+    pub(crate) fn combine_iter<I: IntoIterator<Item = u64>>(&self, remainders: I) -> u64 {
+        let steps = self.divisors.iter().zip(&self.prefixes).zip(&self.inverses);
+
+        // `x` is always less than the prefix product, so `x + t * prefix` is less than the next
+        // prefix product, which is at most `self.product`.
+        remainders
+            .into_iter()
+            .zip(steps)
+            .fold(0, |x, (r, ((d, &prefix), &inverse))| {
+                let t =
+                    modmath::mul_mod_divisor(modmath::sub_mod(r, d.rem(x), d.get()), inverse, d);
+                x + t * prefix
+            })
+    }
+
+    /// Like `combine_iter`, but for `L` sets of remainders at once.
+    #[cfg(feature = "simd")]
+    pub(crate) fn combine_simd<const L: usize, I>(&self, remainders: I) -> Simd<u64, L>
+    where
+        I: IntoIterator<Item = Simd<u64, L>>,
+    {
+        let steps = self.divisors.iter().zip(&self.prefixes).zip(&self.inverses);
+
+        remainders.into_iter().zip(steps).fold(
+            Simd::splat(0),
+            |x, (r, ((d, &prefix), &inverse))| {
+                let m = Simd::splat(d.get());
+                let (_, xr) = d.div_rem_simd(x);
+                let diff = r.simd_ge(xr).select(r - xr, r + (m - xr));
+
+                // The product only fits in a `u64` if the modulus does in a `u32`.
+                let t = if d.get() <= u64::from(u32::MAX) {
+                    d.div_rem_simd(diff * Simd::splat(inverse)).1
+                } else {
+                    Simd::from_array(
+                        diff.to_array()
+                            .map(|a| modmath::mul_mod_divisor(a, inverse, d)),
+                    )
+                };
+
+                x + t * Simd::splat(prefix)
+            },
+        )
     }
 }
 
+#[must_use]
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
     use super::*;
 
     #[test]
@@ -44,10 +251,177 @@ mod tests {
     }
 
     #[test]
-    fn test_mod_inverse() {
-        assert_eq!(mod_inverse(3, 7), Some(5));
-        assert_eq!(mod_inverse(4, 7), Some(2));
-        assert_eq!(mod_inverse(2, 5), Some(3));
-        assert_eq!(mod_inverse(3, 6), None);
+    fn test_chinese_remainder_overflow() {
+        // The product is close to `u64::MAX`, and so is each term before it is reduced.
+        let moduli = [4294967291, 4294967279];
+        let product = moduli[0] * moduli[1];
+        for x in [0, 1, product / 2, product - 2, product - 1] {
+            assert_eq!(chinese_remainder(&moduli.map(|m| x % m), &moduli), Some(x));
+        }
+
+        // Many moduli, with a product just below `u64::MAX`.
+        let moduli = [2048, 27, 25, 343, 14641, 13, 6859, 23];
+        let product = moduli.iter().product::<u64>();
+        for x in [0, product / 3, product - 1] {
+            assert_eq!(chinese_remainder(&moduli.map(|m| x % m), &moduli), Some(x));
+        }
+
+        let moduli = [(1 << 63) - 25, 2];
+        let x = (1 << 63) + 12345;
+        assert_eq!(chinese_remainder(&moduli.map(|m| x % m), &moduli), Some(x));
+    }
+
+    #[test]
+    fn test_chinese_remainder_invalid() {
+        assert_eq!(chinese_remainder(&[1, 2], &[4, 6]), None);
+        assert_eq!(chinese_remainder(&[1, 2], &[0, 3]), None);
+        assert_eq!(chinese_remainder(&[1], &[3, 5]), None);
+        assert_eq!(chinese_remainder(&[1, 1], &[1 << 40, 1 << 30]), None);
+        assert_eq!(chinese_remainder(&[], &[]), Some(0));
+    }
+
+    #[test]
+    fn test_crt_basis() {
+        let basis = CrtBasis::new(&[3, 5, 7]).unwrap();
+        assert_eq!(basis.product(), 105);
+        assert_eq!(basis.moduli(), [3, 5, 7]);
+        assert_eq!(basis.combine(&[2, 3, 2]), Some(23));
+        assert_eq!(basis.combine(&[5, 8, 9]), Some(23));
+        assert_eq!(basis.combine(&[2, 3]), None);
+        assert_eq!(basis.decompose(23), [2, 3, 2]);
+
+        for x in 0..105 {
+            assert_eq!(basis.combine(&basis.decompose(x)), Some(x));
+        }
+    }
+
+    #[test]
+    fn test_crt_basis_large() {
+        let moduli = [2048, 27, 25, 343, 14641, 13, 6859, 23];
+        let basis = CrtBasis::new(&moduli).unwrap();
+        assert_eq!(basis.product(), 14237396402848819200);
+
+        for x in [0, 1, 1234567890123456789, 14237396402848819199] {
+            let remainders = basis.decompose(x);
+            assert_eq!(basis.combine(&remainders), Some(x));
+            assert_eq!(chinese_remainder(&remainders, &moduli), Some(x));
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn test_combine_simd() {
+        use std::simd::u64x4;
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // Includes moduli above `u32::MAX`, which take the scalar path.
+        for moduli in [
+            &[2048, 27, 25, 343][..],
+            &[1 << 40, 3u64.pow(15)],
+            &[3u64.pow(40)],
+        ] {
+            let basis = CrtBasis::new(moduli).unwrap();
+            let xs = [(); 4].map(|()| rng.gen_range(0..basis.product()));
+
+            let remainders = moduli
+                .iter()
+                .map(|&m| u64x4::from_array(xs.map(|x| x % m)))
+                .collect::<Vec<_>>();
+            assert_eq!(basis.combine_simd(remainders), u64x4::from_array(xs));
+        }
+    }
+
+    #[test]
+    fn test_crt_basis_invalid() {
+        assert_eq!(CrtBasis::new(&[4, 6]), None);
+        assert_eq!(CrtBasis::new(&[0, 3]), None);
+        assert_eq!(CrtBasis::new(&[1 << 40, 3u64.pow(30)]), None);
+
+        let empty = CrtBasis::new(&[]).unwrap();
+        assert_eq!(empty.product(), 1);
+        assert_eq!(empty.combine(&[]), Some(0));
+    }
+
+    #[test]
+    fn test_garner() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let moduli = [2048, 27, 25, 343, 14641, 13, 6859, 23];
+
+        for _ in 0..1000 {
+            let remainders = moduli.map(|m| rng.gen_range(0..m));
+            assert_eq!(
+                garner(&remainders, &moduli),
+                chinese_remainder(&remainders, &moduli)
+            );
+        }
+
+        assert_eq!(garner(&[2, 3, 2], &[3, 5, 7]), Some(23));
+        assert_eq!(garner(&[2, 3], &[4, 6]), None);
+    }
+
+    #[test]
+    fn test_garner_large_moduli() {
+        // Two primes whose product is close to `u64::MAX`.
+        let moduli = [4294967291, 4294967279];
+        let product = moduli[0] * moduli[1];
+
+        for x in [0, 1, product / 2, product - 1] {
+            let remainders = moduli.map(|m| x % m);
+            assert_eq!(garner(&remainders, &moduli), Some(x));
+        }
+
+        let moduli = [(1 << 63) - 25, 2];
+        let x = (1 << 63) + 12345;
+        assert_eq!(garner(&moduli.map(|m| x % m), &moduli), Some(x));
+    }
+
+    #[test]
+    fn test_chinese_remainder_general() {
+        assert_eq!(
+            chinese_remainder_general(&[2, 3, 2], &[3, 5, 7]),
+            Ok((23, 105))
+        );
+        assert_eq!(chinese_remainder_general(&[3, 5], &[4, 6]), Ok((11, 12)));
+        assert_eq!(
+            chinese_remainder_general(&[1, 1, 1], &[4, 6, 10]),
+            Ok((1, 60))
+        );
+        assert_eq!(chinese_remainder_general(&[7, 7], &[10, 10]), Ok((7, 10)));
+        assert_eq!(chinese_remainder_general(&[], &[]), Ok((0, 1)));
+
+        assert_eq!(
+            chinese_remainder_general(&[1, 2], &[4, 6]),
+            Err(CrtError::Inconsistent)
+        );
+        assert_eq!(
+            chinese_remainder_general(&[1], &[4, 6]),
+            Err(CrtError::LengthMismatch)
+        );
+        assert_eq!(
+            chinese_remainder_general(&[1, 1], &[4, 0]),
+            Err(CrtError::ZeroModulus)
+        );
+        assert_eq!(
+            chinese_remainder_general(&[0, 0], &[1 << 40, 3u64.pow(30)]),
+            Err(CrtError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_chinese_remainder_general_exhaustive() {
+        let moduli = [12, 18, 8];
+        for x in 0..72 {
+            let remainders = moduli.map(|m| x % m);
+            assert_eq!(chinese_remainder_general(&remainders, &moduli), Ok((x, 72)));
+        }
+    }
+
+    #[test]
+    fn test_gcd() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(7, 9), 1);
+        assert_eq!(gcd(0, 5), 5);
+        assert_eq!(gcd(5, 0), 5);
     }
 }
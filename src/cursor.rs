@@ -0,0 +1,99 @@
+use crate::Permutation;
+
+/// A position in the permuted order that can move in both directions.
+///
+/// The cursor sits between two values, like a text cursor. `next` returns the value after the
+/// cursor and moves forward, `prev` returns the value before the cursor and moves back, so calling
+/// `next` then `prev` returns the same value twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermutationCursor<'a, P: Permutation> {
+    perm: &'a P,
+    idx: u64,
+}
+
+impl<'a, P: Permutation> PermutationCursor<'a, P> {
+    /// Returns a cursor at position 0.
+    #[must_use]
+    pub fn new(perm: &'a P) -> Self {
+        Self { perm, idx: 0 }
+    }
+
+    /// Moves the cursor to position `i`, clamped to `0..=n`.
+    pub fn seek(&mut self, i: u64) {
+        self.idx = i.min(self.perm.num_points());
+    }
+
+    /// Returns the number of values before the cursor.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.idx
+    }
+
+    /// Returns the value before the cursor and moves the cursor back, or `None` at position 0.
+    pub fn prev(&mut self) -> Option<u64> {
+        self.idx = self.idx.checked_sub(1)?;
+        self.perm.nth(self.idx)
+    }
+}
+
+impl<P: Permutation> Iterator for PermutationCursor<'_, P> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let a = self.perm.nth(self.idx)?;
+        self.idx += 1;
+        Some(a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::RandomPermutation;
+
+    #[test]
+    fn test_cursor() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let mut cursor = PermutationCursor::new(&p);
+
+        assert_eq!(cursor.prev(), None);
+        assert_eq!(cursor.position(), 0);
+
+        let a = cursor.next();
+        assert_eq!(a, p.nth(0));
+        assert_eq!(cursor.position(), 1);
+        assert_eq!(cursor.prev(), a);
+        assert_eq!(cursor.position(), 0);
+
+        cursor.seek(100);
+        assert_eq!(cursor.next(), p.nth(100));
+        assert_eq!(cursor.next(), p.nth(101));
+        assert_eq!(cursor.prev(), p.nth(101));
+        assert_eq!(cursor.prev(), p.nth(100));
+        assert_eq!(cursor.prev(), p.nth(99));
+
+        cursor.seek(1000);
+        assert_eq!(cursor.position(), 360);
+        assert_eq!(cursor.next(), None);
+        assert_eq!(cursor.position(), 360);
+        assert_eq!(cursor.prev(), p.nth(359));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let mut cursor = PermutationCursor::new(&p);
+
+        let forward = cursor.by_ref().collect::<Vec<_>>();
+        let mut backward = std::iter::from_fn(|| cursor.prev()).collect::<Vec<_>>();
+        backward.reverse();
+
+        assert_eq!(forward, backward);
+        assert!(forward.into_iter().eq(p.iter()));
+    }
+}
@@ -0,0 +1,137 @@
+use rand::Rng;
+
+use crate::{round::Round, CycleStats, Permutation, RandomPermutation, DEFAULT_UNIFORM_THRESHOLD};
+
+/// A random permutation of `0..n` consisting of a single `n`-cycle.
+///
+/// This is `π ∘ s ∘ π⁻¹`, where `s` is the shift `i ↦ i + 1 mod n` and `π` is a random
+/// permutation, so the cycle visits `π(0), π(1), ..., π(n - 1)` in that order. `π` is generated
+/// like `RandomPermutation::new_hybrid`, so the cycle is uniformly distributed over all `n`-cycles
+/// when `n < DEFAULT_UNIFORM_THRESHOLD`, and uses the CRT construction otherwise. Components too
+/// large to tabulate are table-free bijections, as in `RandomPermutation`, so `n` can be huge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclicPermutation {
+    num_points: u64,
+    round: Round,
+    // The inverse of `round`, which takes as much memory as `round`.
+    inverse: Round,
+}
+
+impl CyclicPermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    /// Returns `None` if `n` isn't supported by `RandomPermutation::hybrid_with_rng`.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        let mut perm = RandomPermutation::hybrid_with_rng(n, DEFAULT_UNIFORM_THRESHOLD, rng)?;
+        let round = std::mem::take(&mut perm.rounds).into_iter().next()?;
+        let mut inverse = round.clone();
+        inverse.invert();
+
+        Some(Self {
+            num_points: n,
+            round,
+            inverse,
+        })
+    }
+}
+
+impl Permutation for CyclicPermutation {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points {
+            return None;
+        }
+
+        let i = self.inverse.nth(n) + 1;
+        Some(self.round.nth(if i == self.num_points { 0 } else { i }))
+    }
+
+    fn cycle_stats(&self) -> CycleStats {
+        CycleStats::single_cycle(self.num_points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    fn cycle_len(p: &CyclicPermutation) -> u64 {
+        let mut x = p.nth(0).unwrap();
+        let mut len = 1;
+        while x != 0 {
+            x = p.nth(x).unwrap();
+            len += 1;
+        }
+        len
+    }
+
+    #[test]
+    fn test_single_cycle() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 3, 1009, 3628800] {
+            let p = CyclicPermutation::with_rng(n, &mut rng).unwrap();
+            assert_eq!(cycle_len(&p), n);
+            assert_eq!(p.nth(n), None);
+        }
+
+        assert_eq!(CyclicPermutation::with_rng(0, &mut rng), None);
+    }
+
+    #[test]
+    fn test_crt() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let n = 2 * DEFAULT_UNIFORM_THRESHOLD * 3;
+        let p = CyclicPermutation::with_rng(n, &mut rng).unwrap();
+
+        // Check part of the cycle, since following all of it is slow.
+        let mut x = 0;
+        let mut seen = HashSet::new();
+        for _ in 0..10000 {
+            assert!(seen.insert(x));
+            x = p.nth(x).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_implicit() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let n = 3 << 40;
+        let p = CyclicPermutation::with_rng(n, &mut rng).unwrap();
+        assert!(p.round.sub_perms.iter().any(|perm| perm.table().is_none()));
+
+        let mut x = 0;
+        let mut seen = HashSet::new();
+        for _ in 0..10000 {
+            assert!(seen.insert(x));
+            x = p.nth(x).unwrap();
+            assert!(x < n);
+        }
+        assert_eq!(p.nth(n), None);
+    }
+
+    #[test]
+    fn test_all_cycles() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+
+        // There are (4 - 1)! = 6 cycles of length 4.
+        let cycles = (0..1000)
+            .map(|_| {
+                let p = CyclicPermutation::with_rng(4, &mut rng).unwrap();
+                p.iter().collect::<Vec<_>>()
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(cycles.len(), 6);
+    }
+}
@@ -0,0 +1,412 @@
+use std::fmt;
+
+use crate::{
+    crt,
+    round::{ImplicitPerm, Round, SubPerm},
+    uniform, FromVecError, PhiloxPermutation, RandomPermutation,
+};
+
+/// The reason a list of tables can't be the components of a `RandomPermutation`. See
+/// `RandomPermutation::from_sub_perms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FromSubPermsError {
+    /// There were no tables.
+    Empty,
+    /// The length of table `index` is `len`, which isn't a prime power, and there is more than one
+    /// table (or `len` is zero).
+    NotPrimePower { index: usize, len: usize },
+    /// The lengths of tables `first` and `second` aren't coprime.
+    NotCoprime { first: usize, second: usize },
+    /// The product of the table lengths doesn't fit in a `u64`.
+    TooLarge,
+    /// Table `index` isn't a permutation.
+    InvalidTable { index: usize, error: FromVecError },
+}
+
+impl fmt::Display for FromSubPermsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no tables"),
+            Self::NotPrimePower { index, len } => write!(
+                f,
+                "table {index} has length {len}, which isn't a prime power"
+            ),
+            Self::NotCoprime { first, second } => {
+                write!(
+                    f,
+                    "tables {first} and {second} have lengths with a common factor"
+                )
+            }
+            Self::TooLarge => write!(f, "product of the table lengths is too large"),
+            Self::InvalidTable { index, error } => write!(f, "table {index}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for FromSubPermsError {}
+
+/// A read-only view of one round of a `RandomPermutation`. See `RandomPermutation::decompose`.
+///
+/// Evaluating the round splits the index into mixed-radix digits with the moduli as the bases
+/// (least significant first), maps each digit through its component, and combines the results with
+/// the CRT. An inverted round does this the other way around: it splits the index into residues,
+/// maps each residue through its component, and combines the results as mixed-radix digits.
+#[derive(Debug, Clone, Copy)]
+pub struct RoundView<'a> {
+    round: &'a Round,
+}
+
+impl<'a> RoundView<'a> {
+    /// Returns the moduli of the components, in the order that was chosen for this round. These are
+    /// pairwise coprime prime powers, except in a round with a single component, whose modulus is
+    /// the number of points and can be anything (e.g. a hybrid permutation's uniform table, or a
+    /// `PhiloxPermutation` converted with `From`).
+    #[must_use]
+    pub fn moduli(&self) -> Vec<u64> {
+        self.round.basis.moduli()
+    }
+
+    #[must_use]
+    pub fn is_inverted(&self) -> bool {
+        self.round.inverted
+    }
+
+    #[must_use]
+    pub fn components(&self) -> impl ExactSizeIterator<Item = Component<'a>> + Clone {
+        self.round
+            .sub_perms
+            .iter()
+            .map(|sub_perm| Component { sub_perm })
+    }
+}
+
+/// A read-only view of one component of a round, which permutes `0..m` for its modulus `m`. See
+/// `RoundView::moduli`.
+#[derive(Debug, Clone, Copy)]
+pub struct Component<'a> {
+    sub_perm: SubPerm<'a>,
+}
+
+impl<'a> Component<'a> {
+    /// Returns the number of points that the component permutes.
+    #[must_use]
+    pub fn modulus(&self) -> u64 {
+        self.sub_perm.len()
+    }
+
+    /// Returns the image of `r` under the component, or `None` if `r >= self.modulus()`.
+    #[must_use]
+    pub fn get(&self, r: u64) -> Option<u64> {
+        (r < self.modulus()).then(|| self.sub_perm.get(r))
+    }
+
+    /// Returns the table of the component, or `None` if it is too large to tabulate and is
+    /// represented by a `PhiloxPermutation` instead.
+    #[must_use]
+    pub fn table(&self) -> Option<&'a [u64]> {
+        self.sub_perm.table()
+    }
+
+    /// Returns the `PhiloxPermutation` of a component that is too large to tabulate, and whether
+    /// the component is its inverse.
+    #[must_use]
+    pub fn implicit(&self) -> Option<(PhiloxPermutation, bool)> {
+        match self.sub_perm {
+            SubPerm::Table(_) => None,
+            SubPerm::Implicit(&ImplicitPerm { perm, inverted }) => Some((perm, inverted)),
+        }
+    }
+}
+
+impl RandomPermutation {
+    /// Returns views of the rounds of the permutation, in the order they are applied.
+    #[must_use]
+    pub fn decompose(&self) -> Vec<RoundView<'_>> {
+        self.rounds
+            .iter()
+            .map(|round| RoundView { round })
+            .collect()
+    }
+
+    /// Builds a single-round permutation from its component tables. Each table must be a
+    /// permutation of `0..p^k` for some prime `p`, and the lengths must be pairwise coprime, except
+    /// that a single table can have any nonzero length. The permutation has as many points as the
+    /// product of the lengths.
+    ///
+    /// The order of the tables is the order of the factors, i.e. the lengths are the moduli of the
+    /// round in the given order, and table `i` gives mixed-radix digit `i` (least significant
+    /// first), as described on `RoundView`. Reordering the tables gives a different permutation.
+    ///
+    /// This is the inverse of `decompose` for single-round permutations without implicit
+    /// components, including hybrid permutations with a uniform table, so it can be used to
+    /// generate the tables some other way (e.g. with stratified shuffles) and still evaluate them
+    /// with the CRT construction.
+    pub fn from_sub_perms(tables: Vec<Vec<u64>>) -> Result<Self, FromSubPermsError> {
+        if tables.is_empty() {
+            return Err(FromSubPermsError::Empty);
+        }
+
+        for (index, table) in tables.iter().enumerate() {
+            let len = table.len();
+            if len == 0 || (tables.len() > 1 && !is_prime_power(len as u64)) {
+                return Err(FromSubPermsError::NotPrimePower { index, len });
+            }
+            uniform::validate(table)
+                .map_err(|error| FromSubPermsError::InvalidTable { index, error })?;
+        }
+
+        for (second, b) in tables.iter().enumerate() {
+            for (first, a) in tables[..second].iter().enumerate() {
+                if crt::gcd(a.len() as u64, b.len() as u64) != 1 {
+                    return Err(FromSubPermsError::NotCoprime { first, second });
+                }
+            }
+        }
+
+        let num_points = tables
+            .iter()
+            .try_fold(1u64, |n, table| n.checked_mul(table.len() as u64))
+            .ok_or(FromSubPermsError::TooLarge)?;
+
+        Ok(Self {
+            num_points,
+            rounds: vec![Round::new(tables).unwrap()],
+        })
+    }
+}
+
+// Whether `n` is `p^k` for a prime `p` and `k >= 0`, by trial division. Table lengths are small
+// enough for this to be cheap.
+fn is_prime_power(mut n: u64) -> bool {
+    if n <= 1 {
+        return n == 1;
+    }
+
+    let p = (2..)
+        .take_while(|&d| d * d <= n)
+        .find(|&d| n.is_multiple_of(d))
+        .unwrap_or(n);
+    while n.is_multiple_of(p) {
+        n /= p;
+    }
+    n == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{crt::CrtBasis, InvertiblePermutation, Permutation, UniformPermutation};
+
+    // Evaluates a round from its view alone, following the description on `RoundView`.
+    fn eval(view: &RoundView<'_>, mut n: u64) -> u64 {
+        let moduli = view.moduli();
+        let basis = CrtBasis::new(&moduli).unwrap();
+        let components = view.components().collect::<Vec<_>>();
+
+        if view.is_inverted() {
+            let residues = basis.decompose(n);
+            components
+                .iter()
+                .zip(residues)
+                .rev()
+                .fold(0, |idx, (c, r)| idx * c.modulus() + c.get(r).unwrap())
+        } else {
+            let remainders = components
+                .iter()
+                .map(|c| {
+                    let r = n % c.modulus();
+                    n /= c.modulus();
+                    c.get(r).unwrap()
+                })
+                .collect::<Vec<_>>();
+            basis.combine(&remainders).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_is_prime_power() {
+        let powers = (0..100).filter(|&n| is_prime_power(n)).collect::<Vec<_>>();
+        assert_eq!(
+            powers,
+            [
+                1, 2, 3, 4, 5, 7, 8, 9, 11, 13, 16, 17, 19, 23, 25, 27, 29, 31, 32, 37, 41, 43, 47,
+                49, 53, 59, 61, 64, 67, 71, 73, 79, 81, 83, 89, 97
+            ]
+        );
+        assert!(is_prime_power(65537 * 65537));
+        assert!(!is_prime_power(65537 * 65539));
+    }
+
+    #[test]
+    fn test_decompose() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng_rounds(2 * 3 * 3 * 5 * 7 * 7, 2, &mut rng).unwrap();
+        let rounds = p.decompose();
+        assert_eq!(rounds.len(), 2);
+
+        for round in &rounds {
+            let mut moduli = round.moduli();
+            moduli.sort_unstable();
+            assert_eq!(moduli, [2, 5, 9, 49]);
+
+            for c in round.components() {
+                let mut table = c.table().unwrap().to_vec();
+                table.sort_unstable();
+                assert!(table.into_iter().eq(0..c.modulus()));
+                assert_eq!(c.implicit(), None);
+                assert_eq!(c.get(c.modulus()), None);
+            }
+        }
+
+        for i in 0..p.num_points() {
+            let j = rounds.iter().fold(i, |i, round| eval(round, i));
+            assert_eq!(Some(j), p.nth(i));
+        }
+    }
+
+    #[test]
+    fn test_decompose_inverted_and_implicit() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::builder(3 * 25 * 64)
+            .implicit_threshold(30)
+            .rng(&mut rng)
+            .build()
+            .unwrap()
+            .into_inverted();
+        let rounds = p.decompose();
+
+        assert!(rounds.iter().all(|round| round.is_inverted()));
+        let implicit = rounds[0]
+            .components()
+            .filter_map(|c| c.implicit())
+            .collect::<Vec<_>>();
+        assert_eq!(implicit.len(), 1);
+        assert_eq!(implicit[0].0.num_points(), 64);
+        assert!(implicit[0].1);
+
+        for i in 0..p.num_points() {
+            let j = rounds.iter().fold(i, |i, round| eval(round, i));
+            assert_eq!(Some(j), p.nth(i));
+        }
+    }
+
+    #[test]
+    fn test_decompose_single_component() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::hybrid_with_rng(1000, 1 << 20, &mut rng).unwrap();
+        let rounds = p.decompose();
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].moduli(), [1000]);
+        assert_eq!(
+            rounds[0]
+                .components()
+                .next()
+                .unwrap()
+                .table()
+                .unwrap()
+                .len(),
+            1000
+        );
+
+        let philox = PhiloxPermutation::with_rng(1000, &mut rng).unwrap();
+        let p = RandomPermutation::from(philox);
+        let rounds = p.decompose();
+        assert_eq!(rounds[0].moduli(), [1000]);
+        let c = rounds[0].components().next().unwrap();
+        assert_eq!(c.implicit(), Some((philox, false)));
+        for i in 0..1000 {
+            assert_eq!(c.get(i), p.nth(i));
+        }
+    }
+
+    #[test]
+    fn test_from_sub_perms() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng(8 * 9 * 5 * 7, &mut rng).unwrap();
+        let tables = p.decompose()[0]
+            .components()
+            .map(|c| c.table().unwrap().to_vec())
+            .collect::<Vec<_>>();
+
+        let q = RandomPermutation::from_sub_perms(tables).unwrap();
+        assert_eq!(q, p);
+
+        let q =
+            RandomPermutation::from_sub_perms(vec![vec![1, 2, 0], vec![0, 1], vec![0]]).unwrap();
+        assert_eq!(q.num_points(), 6);
+        assert!(q.verify());
+        assert_eq!(q.decompose()[0].moduli(), [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_from_sub_perms_any_prime_power() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+
+        // A hybrid permutation has a single uniform table of any length.
+        let p = RandomPermutation::hybrid_with_rng(1000, 1 << 20, &mut rng).unwrap();
+        let table = p.decompose()[0]
+            .components()
+            .next()
+            .unwrap()
+            .table()
+            .unwrap();
+        let q = RandomPermutation::from_sub_perms(vec![table.to_vec()]).unwrap();
+        assert_eq!(q, p);
+
+        let tables = [257, 4, 263 * 263]
+            .map(|n| UniformPermutation::with_rng(n, &mut rng).into_vec())
+            .to_vec();
+        let q = RandomPermutation::from_sub_perms(tables.clone()).unwrap();
+        assert_eq!(q.num_points(), 257 * 4 * 263 * 263);
+        assert_eq!(q.decompose()[0].moduli(), [257, 4, 263 * 263]);
+        for (c, table) in q.decompose()[0].components().zip(&tables) {
+            assert_eq!(c.table(), Some(&table[..]));
+        }
+        for i in [0, 1, 12345, q.num_points() - 1] {
+            assert_eq!(q.preimage(q.nth(i).unwrap()), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_from_sub_perms_invalid() {
+        use FromSubPermsError::*;
+
+        assert_eq!(RandomPermutation::from_sub_perms(vec![]), Err(Empty));
+        assert_eq!(
+            RandomPermutation::from_sub_perms(vec![vec![0, 1], (0..6).collect()]),
+            Err(NotPrimePower { index: 1, len: 6 })
+        );
+        assert_eq!(
+            RandomPermutation::from_sub_perms(vec![vec![]]),
+            Err(NotPrimePower { index: 0, len: 0 })
+        );
+        assert_eq!(
+            RandomPermutation::from_sub_perms(vec![(0..1000).collect(), vec![0, 1, 2]]),
+            Err(NotPrimePower {
+                index: 0,
+                len: 1000
+            })
+        );
+        assert_eq!(
+            RandomPermutation::from_sub_perms(vec![vec![0, 1], vec![0, 1, 2], (0..4).collect()]),
+            Err(NotCoprime {
+                first: 0,
+                second: 2
+            })
+        );
+        assert_eq!(
+            RandomPermutation::from_sub_perms(vec![vec![0, 1], vec![0, 0, 2]]),
+            Err(InvalidTable {
+                index: 1,
+                error: FromVecError::Duplicate {
+                    value: 0,
+                    first: 0,
+                    second: 1
+                }
+            })
+        );
+    }
+}
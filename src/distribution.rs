@@ -0,0 +1,75 @@
+use rand::{distributions::Distribution, Rng};
+
+use crate::{FactoredInteger, RandomPermutation};
+
+/// The distribution of `RandomPermutation::with_rng_rounds(n, rounds, rng)`, so permutations can
+/// be generated with `rng.sample` and by generic code that takes a `Distribution`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RandPerm {
+    num_points: u64,
+    rounds: usize,
+}
+
+impl RandPerm {
+    /// Returns `None` if `RandomPermutation` doesn't support `n`.
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rounds(n, 1)
+    }
+
+    /// Returns `None` if `RandomPermutation` doesn't support `n`, or if `rounds == 0`.
+    #[must_use]
+    pub fn with_rounds(n: u64, rounds: usize) -> Option<Self> {
+        if rounds == 0 {
+            return None;
+        }
+
+        FactoredInteger::new(n)?;
+        Some(Self {
+            num_points: n,
+            rounds,
+        })
+    }
+
+    #[must_use]
+    pub fn num_points(&self) -> u64 {
+        self.num_points
+    }
+}
+
+impl Distribution<RandomPermutation> for RandPerm {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> RandomPermutation {
+        RandomPermutation::with_rng_rounds(self.num_points, self.rounds, rng).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::Permutation;
+
+    #[test]
+    fn test_sample() {
+        let mut rng1 = Xoshiro256StarStar::seed_from_u64(0);
+        let mut rng2 = rng1.clone();
+
+        let p: RandomPermutation = rng1.sample(RandPerm::new(3600).unwrap());
+        assert_eq!(p, RandomPermutation::with_rng(3600, &mut rng2).unwrap());
+
+        let dist = RandPerm::with_rounds(360, 3).unwrap();
+        let perms = dist.sample_iter(&mut rng1).take(5).collect::<Vec<_>>();
+        assert!(perms
+            .iter()
+            .all(|p| p.num_points() == 360 && p.num_rounds() == 3));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(RandPerm::new(0), None);
+        assert_eq!(RandPerm::new(1009), None);
+        assert_eq!(RandPerm::with_rounds(360, 0), None);
+    }
+}
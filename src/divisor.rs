@@ -0,0 +1,131 @@
+#[cfg(feature = "simd")]
+use std::simd::Simd;
+
+/// A divisor with a precomputed multiply-shift reciprocal, so that division and remainder don't
+/// need a hardware division instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Divisor {
+    divisor: u64,
+    magic: u64,
+    shift: u32,
+}
+
+impl Divisor {
+    pub fn new(divisor: u64) -> Self {
+        assert_ne!(divisor, 0, "division by zero");
+
+        // Round-up method from "Division by Invariant Integers using Multiplication" (Granlund and
+        // Montgomery), with a 65-bit multiplier whose top bit is implicit.
+        let log2 = u64::BITS - (divisor - 1).leading_zeros();
+        let magic = if divisor == 1 {
+            0
+        } else {
+            ((1u128 << 64) * ((1u128 << log2) - divisor as u128) / divisor as u128 + 1) as u64
+        };
+
+        Self {
+            divisor,
+            magic,
+            shift: log2.saturating_sub(1),
+        }
+    }
+
+    pub fn get(&self) -> u64 {
+        self.divisor
+    }
+
+    pub fn div(&self, n: u64) -> u64 {
+        if self.divisor == 1 {
+            return n;
+        }
+
+        let t = ((self.magic as u128 * n as u128) >> 64) as u64;
+        (t + ((n - t) >> 1)) >> self.shift
+    }
+
+    pub fn div_rem(&self, n: u64) -> (u64, u64) {
+        let q = self.div(n);
+        (q, n - q * self.divisor)
+    }
+
+    pub fn rem(&self, n: u64) -> u64 {
+        self.div_rem(n).1
+    }
+
+    #[cfg(feature = "simd")]
+    pub fn div_simd<const L: usize>(&self, n: Simd<u64, L>) -> Simd<u64, L> {
+        if self.divisor == 1 {
+            return n;
+        }
+
+        // There is no 64x64 -> 128 bit vector multiply, so compute the high half from 32-bit limbs.
+        let mask = Simd::splat(u64::from(u32::MAX));
+        let (n0, n1) = (n & mask, n >> 32);
+        let (m0, m1) = (
+            Simd::splat(self.magic & u64::from(u32::MAX)),
+            Simd::splat(self.magic >> 32),
+        );
+
+        let (p00, p01, p10, p11) = (n0 * m0, n0 * m1, n1 * m0, n1 * m1);
+        let mid = (p00 >> 32) + (p01 & mask) + (p10 & mask);
+        let t = p11 + (p01 >> 32) + (p10 >> 32) + (mid >> 32);
+
+        (t + ((n - t) >> 1)) >> Simd::splat(u64::from(self.shift))
+    }
+
+    #[cfg(feature = "simd")]
+    pub fn div_rem_simd<const L: usize>(&self, n: Simd<u64, L>) -> (Simd<u64, L>, Simd<u64, L>) {
+        let q = self.div_simd(n);
+        (q, n - q * Simd::splat(self.divisor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "simd")]
+    use std::simd::u64x4;
+
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_div_rem() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        let divisors = (1..1000)
+            .chain([
+                1 << 32,
+                (1 << 63) - 1,
+                1 << 63,
+                (1 << 63) + 1,
+                u64::MAX - 1,
+                u64::MAX,
+            ])
+            .chain((0..1000).map(|i| match i % 2 {
+                0 => rng.gen(),
+                _ => rng.gen::<u32>() as u64,
+            }))
+            .collect::<Vec<u64>>();
+
+        for d in divisors {
+            let divisor = Divisor::new(d);
+            let numerators = [0, 1, d - 1, d, d.wrapping_add(1), u64::MAX - 1, u64::MAX]
+                .into_iter()
+                .chain((0..100).map(|_| rng.gen()))
+                .collect::<Vec<_>>();
+
+            for &n in &numerators {
+                assert_eq!(divisor.div_rem(n), (n / d, n % d), "{n} / {d}");
+            }
+
+            #[cfg(feature = "simd")]
+            for chunk in numerators.chunks_exact(4) {
+                let n = u64x4::from_slice(chunk);
+                let d = u64x4::splat(d);
+                assert_eq!(divisor.div_rem_simd(n), (n / d, n % d), "{n:?} / {d:?}");
+            }
+        }
+    }
+}
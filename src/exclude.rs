@@ -0,0 +1,237 @@
+use crate::{Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+/// A permutation of the points of `0..n` that aren't in an exclusion set, derived from a
+/// permutation of `0..n`.
+///
+/// The image of an allowed point `x` is found by applying the base permutation `σ` repeatedly
+/// until it reaches an allowed point (cycle walking), i.e. the next allowed point on the cycle of
+/// `σ` through `x`. This is a bijection on the allowed points, and the preimage walks the cycle
+/// in the other direction. Each walk takes about `n / (n - k)` steps on average, where `k` is the
+/// number of excluded points, so this is only efficient when most points are allowed.
+///
+/// As a `Permutation`, this permutes the ranks `0..n - k` of the allowed points. Use `value` to
+/// convert a rank to the corresponding allowed point of `0..n`, or `iter_values` and `get` to work
+/// with the points directly.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExcludingPermutation<P> {
+    base: P,
+    // Sorted, with no duplicates.
+    excluded: Vec<u64>,
+}
+
+impl<P: Permutation> ExcludingPermutation<P> {
+    /// Excludes the points in `excluded` from `base`, ignoring duplicates. Returns `None` if an
+    /// excluded point is out of range, or if every point is excluded.
+    #[must_use]
+    pub fn new<I: IntoIterator<Item = u64>>(base: P, excluded: I) -> Option<Self> {
+        let mut excluded = excluded.into_iter().collect::<Vec<_>>();
+        excluded.sort_unstable();
+        excluded.dedup();
+
+        let n = base.num_points();
+        if excluded.last().is_some_and(|&x| x >= n) || excluded.len() as u64 == n {
+            return None;
+        }
+
+        Some(Self { base, excluded })
+    }
+
+    #[must_use]
+    pub fn is_excluded(&self, x: u64) -> bool {
+        self.excluded.binary_search(&x).is_ok()
+    }
+
+    /// Returns the excluded points, in increasing order.
+    #[must_use]
+    pub fn excluded(&self) -> &[u64] {
+        &self.excluded
+    }
+
+    /// Returns the allowed point with the given rank, i.e. the `rank`th smallest allowed point, or
+    /// `None` if `rank >= num_points`.
+    #[must_use]
+    pub fn value(&self, rank: u64) -> Option<u64> {
+        if rank >= self.num_points() {
+            return None;
+        }
+
+        // The number of allowed points below `excluded[i]` is `excluded[i] - i`, which is
+        // nondecreasing in `i`, so binary search for the number of excluded points below the
+        // answer.
+        let (mut below, mut end) = (0, self.excluded.len());
+        while below < end {
+            let mid = below + (end - below) / 2;
+            if self.excluded[mid] - mid as u64 <= rank {
+                below = mid + 1;
+            } else {
+                end = mid;
+            }
+        }
+        Some(rank + below as u64)
+    }
+
+    /// Returns the rank of the allowed point `x`, or `None` if `x` is excluded or out of range.
+    #[must_use]
+    pub fn rank(&self, x: u64) -> Option<u64> {
+        if x >= self.base.num_points() {
+            return None;
+        }
+
+        match self.excluded.binary_search(&x) {
+            Ok(_) => None,
+            Err(below) => Some(x - below as u64),
+        }
+    }
+
+    /// Returns the image of the allowed point `x`, or `None` if `x` is excluded or out of range.
+    #[must_use]
+    pub fn get(&self, x: u64) -> Option<u64> {
+        if self.is_excluded(x) {
+            return None;
+        }
+
+        let mut y = self.base.nth(x)?;
+        while self.is_excluded(y) {
+            y = self.base.nth(y)?;
+        }
+        Some(y)
+    }
+
+    /// Returns an iterator over the images of the allowed points, in increasing order of the
+    /// allowed points. This visits every allowed point exactly once.
+    pub fn iter_values(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.base.num_points())
+            .filter(|&x| !self.is_excluded(x))
+            .map(|x| self.get(x).unwrap())
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> (P, Vec<u64>) {
+        (self.base, self.excluded)
+    }
+}
+
+impl<P: InvertiblePermutation> ExcludingPermutation<P> {
+    /// Returns the allowed point whose image is `y`, or `None` if `y` is excluded or out of range.
+    #[must_use]
+    pub fn get_preimage(&self, y: u64) -> Option<u64> {
+        if self.is_excluded(y) {
+            return None;
+        }
+
+        let mut x = self.base.preimage(y)?;
+        while self.is_excluded(x) {
+            x = self.base.preimage(x)?;
+        }
+        Some(x)
+    }
+}
+
+impl<P: Permutation> Permutation for ExcludingPermutation<P> {
+    fn num_points(&self) -> u64 {
+        self.base.num_points() - self.excluded.len() as u64
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.rank(self.get(self.value(n)?)?)
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for ExcludingPermutation<P> {
+    type Inverse<'a>
+        = Inversed<&'a Self>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.rank(self.get_preimage(self.value(x)?)?)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{PhiloxPermutation, RandomPermutation};
+
+    #[test]
+    fn test_rank() {
+        let p = ExcludingPermutation::new(RandomPermutation::new(10).unwrap(), [7, 0, 3, 4, 3])
+            .unwrap();
+        assert_eq!(p.excluded(), [0, 3, 4, 7]);
+        assert_eq!(p.num_points(), 6);
+
+        let allowed = [1, 2, 5, 6, 8, 9];
+        for (rank, &x) in allowed.iter().enumerate() {
+            assert_eq!(p.value(rank as u64), Some(x));
+            assert_eq!(p.rank(x), Some(rank as u64));
+        }
+        assert_eq!(p.value(6), None);
+        assert_eq!(p.rank(0), None);
+        assert_eq!(p.rank(10), None);
+    }
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let base = RandomPermutation::with_rng(1000, &mut rng).unwrap();
+        let excluded = (0..300).map(|i| i * 3 + 1).collect::<Vec<_>>();
+        let p = ExcludingPermutation::new(base, excluded.iter().copied()).unwrap();
+        assert_eq!(p.num_points(), 700);
+
+        let mut values = p.iter().collect::<Vec<_>>();
+        values.sort();
+        assert!(values.into_iter().eq(0..700));
+
+        let mut values = p.iter_values().collect::<Vec<_>>();
+        assert!(values.iter().all(|x| !excluded.contains(x)));
+        values.sort();
+        assert!(values
+            .iter()
+            .copied()
+            .eq((0..1000).filter(|x| !excluded.contains(x))));
+
+        for i in 0..700 {
+            assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+        }
+        for x in 0..1000 {
+            match p.get(x) {
+                Some(y) => assert_eq!(p.get_preimage(y), Some(x)),
+                None => assert!(excluded.contains(&x)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_philox() {
+        let base = PhiloxPermutation::with_key(1 << 20, 3).unwrap();
+        let p = ExcludingPermutation::new(base, (0..1 << 20).step_by(5)).unwrap();
+        for i in (0..p.num_points()).step_by(997) {
+            let x = p.value(i).unwrap();
+            let y = p.get(x).unwrap();
+            assert!(!y.is_multiple_of(5));
+            assert_eq!(p.get_preimage(y), Some(x));
+        }
+    }
+
+    #[test]
+    fn test_no_exclusions() {
+        let base = PhiloxPermutation::with_key(100, 3).unwrap();
+        let p = ExcludingPermutation::new(base, []).unwrap();
+        assert!(p.eq_mapping(&base));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let base = PhiloxPermutation::with_key(10, 3).unwrap();
+        assert_eq!(ExcludingPermutation::new(base, [10]), None);
+        assert_eq!(ExcludingPermutation::new(base, 0..10), None);
+        assert!(ExcludingPermutation::new(base, 1..10).is_some());
+    }
+}
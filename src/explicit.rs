@@ -0,0 +1,81 @@
+//! Serializes a `RandomPermutation` as its explicit list of images `[σ(0), σ(1), ..., σ(n - 1)]`,
+//! for files that need to be human-readable. Use it with `#[serde(with = "randperm_crt::explicit")]`.
+//!
+//! This takes O(n) space, so it is only suitable for small permutations. Deserializing checks that
+//! the list is a permutation, and stores it as a single explicit table.
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+use crate::{Permutation, RandomPermutation, UniformPermutation};
+
+pub fn serialize<S: Serializer>(
+    perm: &RandomPermutation,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(perm.iter())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<RandomPermutation, D::Error> {
+    let mapping = Vec::<u64>::deserialize(deserializer)?;
+    let perm = UniformPermutation::try_from(mapping).map_err(de::Error::custom)?;
+    Ok(perm.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::SmallPermutation;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        #[serde(with = "crate::explicit")]
+        perm: RandomPermutation,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let perm = RandomPermutation::with_rng(12, &mut rng).unwrap();
+        let json = serde_json::to_string(&Config { perm: perm.clone() }).unwrap();
+
+        let values = perm.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(json, format!(r#"{{"perm":[{}]}}"#, values.join(",")));
+
+        let config = serde_json::from_str::<Config>(&json).unwrap();
+        assert!(config.perm.eq_mapping(&perm));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = serde_json::from_str::<Config>(r#"{"perm":[0,2,2]}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("value 2 appears at indices 1 and 2"));
+        assert!(serde_json::from_str::<Config>(r#"{"perm":[0,3,1]}"#).is_err());
+    }
+
+    #[test]
+    fn test_explicit_types() {
+        let p = UniformPermutation::try_from(vec![2, 0, 1]).unwrap();
+        assert_eq!(serde_json::to_string(&p).unwrap(), "[2,0,1]");
+        assert_eq!(
+            serde_json::from_str::<UniformPermutation>("[2,0,1]").unwrap(),
+            p
+        );
+        assert!(serde_json::from_str::<UniformPermutation>("[2,0,0]").is_err());
+
+        let p = SmallPermutation::<3>::try_from(vec![1, 2, 0]).unwrap();
+        assert_eq!(serde_json::to_string(&p).unwrap(), "[1,2,0]");
+        assert_eq!(
+            serde_json::from_str::<SmallPermutation<3>>("[1,2,0]").unwrap(),
+            p
+        );
+        assert!(serde_json::from_str::<SmallPermutation<3>>("[1,0]").is_err());
+    }
+}
@@ -0,0 +1,643 @@
+use std::{borrow::Cow, ops::Range};
+
+use crate::{Inverse, Permutation, RandomPermutation, Shape, Shaped};
+
+/// A permutation whose inverse can be evaluated without materializing it.
+pub trait InvertiblePermutation: Permutation {
+    /// The type of `inverse`. Types without a cheaper representation use `Inversed<&Self>`, which
+    /// evaluates `preimage`.
+    type Inverse<'a>: Permutation
+    where
+        Self: 'a;
+
+    /// Returns the `i` with `nth(i) == Some(x)`, or `None` if `x >= num_points`.
+    fn preimage(&self, x: u64) -> Option<u64>;
+
+    /// Returns the inverse permutation, whose `nth` is `preimage`.
+    fn inverse(&self) -> Self::Inverse<'_>;
+}
+
+impl InvertiblePermutation for RandomPermutation {
+    type Inverse<'a> = Inverse<'a>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.inverse().nth(x)
+    }
+
+    fn inverse(&self) -> Inverse<'_> {
+        Self::inverse(self)
+    }
+}
+
+impl<'a> InvertiblePermutation for Inverse<'a> {
+    type Inverse<'b>
+        = &'a RandomPermutation
+    where
+        Self: 'b;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.perm.nth(x)
+    }
+
+    fn inverse(&self) -> &'a RandomPermutation {
+        self.perm
+    }
+}
+
+/// Chainable combinators for building permutations out of other permutations. Each combinator
+/// takes ownership of `self` (pass a reference to keep using the original) and evaluates lazily.
+pub trait PermutationExt: Permutation {
+    /// Returns the permutation that applies `self` and then `other`, i.e. `i ↦ other(self(i))`, or
+    /// `None` if they have different numbers of points.
+    fn then<Q: Permutation>(self, other: Q) -> Option<Then<Self, Q>> {
+        Then::new(self, other)
+    }
+
+    /// Returns the inverse permutation.
+    fn inversed(self) -> Inversed<Self>
+    where
+        Self: InvertiblePermutation,
+    {
+        Inversed { perm: self }
+    }
+
+    /// Returns the permutation `i ↦ (self(i) + k) mod n`.
+    fn offset(self, k: u64) -> Offset<Self> {
+        Offset::new(self, k)
+    }
+
+    /// Returns the permutation `i ↦ n - 1 - self(i)`.
+    fn complement(self) -> Complement<Self> {
+        Complement { perm: self }
+    }
+
+    /// Treats `0..n` as the points of a grid, numbered in row-major order. Returns `None` if
+    /// `shape` doesn't have `n` points.
+    fn with_shape(self, shape: Shape) -> Option<Shaped<Self>> {
+        Shaped::new(self, shape)
+    }
+
+    /// Returns an iterator over `self(i)` for each `i` in `range`, clamped to `0..n`.
+    fn restricted_iter(&self, range: Range<u64>) -> RestrictedIter<'_, Self> {
+        let n = self.num_points();
+        RestrictedIter {
+            perm: self,
+            range: range.start.min(n)..range.end.min(n),
+        }
+    }
+
+    /// Splits the values of the permutation into `k` disjoint iterators, where shard `j` yields
+    /// `self(i)` for `i = j, j + k, j + 2k, ...`. Together they cover every value exactly once, so
+    /// each can be consumed by a different thread without any coordination.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k == 0`.
+    fn shards(&self, k: usize) -> Vec<Shard<'_, Self>> {
+        assert!(k != 0, "number of shards is zero");
+
+        let n = self.num_points();
+        (0..k as u64)
+            .map(|j| Shard {
+                perm: self,
+                start: j.min(n),
+                end: n,
+                step: k as u64,
+            })
+            .collect()
+    }
+}
+
+impl<P: Permutation> PermutationExt for P {}
+
+impl<P: Permutation> Permutation for &P {
+    fn num_points(&self) -> u64 {
+        (**self).num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        (**self).nth(n)
+    }
+}
+
+impl<P: Permutation + Clone> Permutation for Cow<'_, P> {
+    fn num_points(&self) -> u64 {
+        (**self).num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        (**self).nth(n)
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for &P {
+    type Inverse<'a>
+        = P::Inverse<'a>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        (**self).preimage(x)
+    }
+
+    fn inverse(&self) -> P::Inverse<'_> {
+        (**self).inverse()
+    }
+}
+
+/// `first` followed by `second`, evaluated lazily. Unlike `Composition`, the two stages can have
+/// different types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Then<P, Q> {
+    first: P,
+    second: Q,
+}
+
+impl<P: Permutation, Q: Permutation> Then<P, Q> {
+    /// Returns `None` if `first` and `second` have different numbers of points.
+    #[must_use]
+    pub fn new(first: P, second: Q) -> Option<Self> {
+        if first.num_points() != second.num_points() {
+            return None;
+        }
+
+        Some(Self { first, second })
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> (P, Q) {
+        (self.first, self.second)
+    }
+}
+
+impl<P: Permutation, Q: Permutation> Permutation for Then<P, Q> {
+    fn num_points(&self) -> u64 {
+        self.first.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.second.nth(self.first.nth(n)?)
+    }
+}
+
+impl<P: InvertiblePermutation, Q: InvertiblePermutation> InvertiblePermutation for Then<P, Q> {
+    type Inverse<'a>
+        = Then<Q::Inverse<'a>, P::Inverse<'a>>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.first.preimage(self.second.preimage(x)?)
+    }
+
+    fn inverse(&self) -> Self::Inverse<'_> {
+        Then {
+            first: self.second.inverse(),
+            second: self.first.inverse(),
+        }
+    }
+}
+
+/// The inverse of a permutation. See `PermutationExt::inversed`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inversed<P> {
+    perm: P,
+}
+
+impl<P: InvertiblePermutation> Permutation for Inversed<P> {
+    fn num_points(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.perm.preimage(n)
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for Inversed<P> {
+    type Inverse<'a>
+        = &'a P
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.perm.nth(x)
+    }
+
+    fn inverse(&self) -> &P {
+        &self.perm
+    }
+}
+
+/// A permutation followed by the rotation `x ↦ (x + k) mod n`, so the same shuffle can be reused
+/// with a different phase without regenerating it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offset<P> {
+    perm: P,
+    offset: u64,
+}
+
+impl<P: Permutation> Offset<P> {
+    #[must_use]
+    pub fn new(perm: P, k: u64) -> Self {
+        let n = perm.num_points();
+        Self {
+            offset: if n == 0 { 0 } else { k % n },
+            perm,
+        }
+    }
+
+    /// Returns `k mod n`.
+    #[must_use]
+    pub fn shift(&self) -> u64 {
+        self.offset
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> P {
+        self.perm
+    }
+}
+
+impl<P: Permutation> Permutation for Offset<P> {
+    fn num_points(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        let a = self.perm.nth(n)?;
+        let n = self.num_points();
+        Some(if a >= n - self.offset {
+            a - (n - self.offset)
+        } else {
+            a + self.offset
+        })
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for Offset<P> {
+    type Inverse<'a>
+        = Inversed<&'a Self>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        if x >= self.num_points() {
+            return None;
+        }
+
+        let a = if x >= self.offset {
+            x - self.offset
+        } else {
+            x + (self.num_points() - self.offset)
+        };
+        self.perm.preimage(a)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+/// A permutation followed by the reflection `x ↦ n - 1 - x`, so each value is paired with its
+/// mirror image in `0..n`. This is useful for antithetic sampling. See `PermutationExt::complement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Complement<P> {
+    perm: P,
+}
+
+impl<P: Permutation> Complement<P> {
+    #[must_use]
+    pub fn into_inner(self) -> P {
+        self.perm
+    }
+}
+
+impl<P: Permutation> Permutation for Complement<P> {
+    fn num_points(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        Some(self.num_points() - 1 - self.perm.nth(n)?)
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for Complement<P> {
+    type Inverse<'a>
+        = Inversed<&'a Self>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.perm
+            .preimage(self.num_points().checked_sub(x)?.checked_sub(1)?)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+/// An iterator over part of a permutation. See `PermutationExt::restricted_iter`.
+#[derive(Debug, Clone)]
+pub struct RestrictedIter<'a, P> {
+    perm: &'a P,
+    range: Range<u64>,
+}
+
+impl<P: Permutation> Iterator for RestrictedIter<'_, P> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.perm.nth(self.range.next()?)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.perm.nth(self.range.nth(n)?)
+    }
+}
+
+impl<P: Permutation> DoubleEndedIterator for RestrictedIter<'_, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.perm.nth(self.range.next_back()?)
+    }
+}
+
+/// One of the disjoint iterators returned by `PermutationExt::shards`.
+#[derive(Debug, Clone)]
+pub struct Shard<'a, P> {
+    perm: &'a P,
+    // The remaining positions are `start, start + step, ...`, up to but not including `end`.
+    start: u64,
+    end: u64,
+    step: u64,
+}
+
+impl<P> Shard<'_, P> {
+    fn remaining(&self) -> u64 {
+        if self.start < self.end {
+            (self.end - self.start - 1) / self.step + 1
+        } else {
+            0
+        }
+    }
+}
+
+impl<P: Permutation> Iterator for Shard<'_, P> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (
+            usize::try_from(remaining).unwrap_or(usize::MAX),
+            usize::try_from(remaining).ok(),
+        )
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n as u64 >= self.remaining() {
+            self.start = self.end;
+            return None;
+        }
+
+        let i = self.start + n as u64 * self.step;
+        self.start = i.saturating_add(self.step).min(self.end);
+        self.perm.nth(i)
+    }
+}
+
+impl<P: Permutation> DoubleEndedIterator for Shard<'_, P> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let i = self.start + self.remaining().checked_sub(1)? * self.step;
+        self.end = i;
+        self.perm.nth(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::UniformPermutation;
+
+    #[test]
+    fn test_then() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let q = UniformPermutation::with_rng(360, &mut rng);
+        let pq = (&p).then(&q).unwrap();
+
+        assert_eq!(pq.num_points(), 360);
+        assert!(p.pairs().all(|(i, a)| pq.nth(i) == q.nth(a)));
+        assert_eq!(pq.nth(360), None);
+
+        let r = UniformPermutation::with_rng(361, &mut rng);
+        assert!(p.then(r).is_none());
+    }
+
+    #[test]
+    fn test_then_new() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+        let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+        let q = RandomPermutation::with_rng_rounds(3600, 2, &mut rng).unwrap();
+        let pq = Then::new(&p, &q).unwrap();
+
+        assert!(pq.pairs().all(|(i, a)| pq.preimage(a) == Some(i)));
+        assert_eq!(pq.preimage(3600), None);
+
+        let (first, second) = pq.into_inner();
+        assert_eq!((first, second), (&p, &q));
+        assert_eq!(
+            Then::new(&p, UniformPermutation::with_rng(10, &mut rng)),
+            None
+        );
+        assert_eq!(
+            Then::new(
+                UniformPermutation::with_rng(0, &mut rng),
+                UniformPermutation::new(0)
+            )
+            .map(|pq| pq.num_points()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_inversed() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+        let inv = (&p).inversed();
+
+        assert!(p.pairs().all(|(i, a)| inv.nth(a) == Some(i)));
+        assert!(inv.inversed().eq_mapping(&p));
+    }
+
+    fn check_inverse<P: InvertiblePermutation>(p: &P) {
+        let inv = InvertiblePermutation::inverse(p);
+        assert_eq!(inv.num_points(), p.num_points());
+        assert!((0..p.num_points()).all(|x| inv.nth(x) == p.preimage(x)));
+        assert_eq!(inv.nth(p.num_points()), None);
+    }
+
+    #[test]
+    fn test_inverse() {
+        use crate::{Composition, MortonPermutation, PhiloxPermutation, SmallPermutation};
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let q = UniformPermutation::with_rng(360, &mut rng);
+        check_inverse(&p);
+        check_inverse(&p.inverse());
+        check_inverse(&q);
+        check_inverse(&(&p).then(&q).unwrap());
+        check_inverse(&(&q).inversed());
+        check_inverse(&(&p).offset(7).complement());
+        check_inverse(&MortonPermutation::new(2, 3).unwrap());
+        check_inverse(&PhiloxPermutation::with_rng(360, &mut rng).unwrap());
+        check_inverse(&SmallPermutation::<6>::with_rng(&mut rng));
+
+        let c = Composition::new(vec![p.clone(), p.clone()]).unwrap();
+        check_inverse(&c);
+    }
+
+    #[test]
+    fn test_offset() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+        for k in [0, 1, 359, 360, 1000, u64::MAX] {
+            let q = (&p).offset(k);
+            assert!(p
+                .pairs()
+                .all(|(i, a)| q.nth(i) == Some((a + k % 360) % 360)));
+        }
+    }
+
+    #[test]
+    fn test_offset_inverse() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(6);
+        let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+
+        for k in [0, 1, 3599, 3600, 12345] {
+            let q = Offset::new(&p, k);
+            assert_eq!(q.shift(), k % 3600);
+            assert!(q.pairs().all(|(i, a)| q.preimage(a) == Some(i)));
+            assert_eq!(q.preimage(3600), None);
+            assert!(q
+                .inversed()
+                .then(Offset::new(&p, k))
+                .unwrap()
+                .iter()
+                .eq(0..3600));
+        }
+
+        let q = Offset::new(UniformPermutation::new(0), 5);
+        assert_eq!(q.shift(), 0);
+        assert_eq!(q.into_inner().num_points(), 0);
+    }
+
+    #[test]
+    fn test_complement() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(7);
+        let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+        let q = (&p).complement();
+
+        assert!(p.pairs().all(|(i, a)| q.nth(i) == Some(3599 - a)));
+        assert!(q.pairs().all(|(i, a)| q.preimage(a) == Some(i)));
+        assert_eq!(q.nth(3600), None);
+        assert_eq!(q.preimage(3600), None);
+        assert!(q.complement().eq_mapping(&p));
+    }
+
+    #[test]
+    fn test_restricted_iter() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+        assert!(p
+            .restricted_iter(10..20)
+            .eq((10..20).map(|i| p.nth(i).unwrap())));
+        assert!(p
+            .restricted_iter(300..1000)
+            .rev()
+            .eq((300..360).rev().map(|i| p.nth(i).unwrap())));
+        assert_eq!(p.restricted_iter(400..500).next(), None);
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let q = (&p).then((&p).inversed()).unwrap().offset(5);
+
+        assert!(q.iter().eq((0..360).map(|i| (i + 5) % 360)));
+    }
+
+    #[test]
+    fn test_shards() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+        for k in [1, 2, 7, 360, 1000] {
+            let shards = p.shards(k);
+            assert_eq!(shards.len(), k);
+
+            let mut values = shards.into_iter().flatten().collect::<Vec<_>>();
+            values.sort();
+            assert!(values.into_iter().eq(0..360));
+        }
+
+        let shards = p.shards(7);
+        assert!(shards[3]
+            .clone()
+            .eq((3..360).step_by(7).map(|i| p.nth(i).unwrap())));
+        assert_eq!(shards[3].size_hint(), (51, Some(51)));
+        assert!(shards[3].clone().rev().eq((3..360u32)
+            .step_by(7)
+            .rev()
+            .map(u64::from)
+            .map(|i| p.nth(i).unwrap())));
+        let mut shard = shards[3].clone();
+        assert_eq!(shard.nth(2), p.nth(17));
+        assert_eq!(shard.next_back(), p.nth(353));
+        assert_eq!(shard.size_hint(), (47, Some(47)));
+        assert_eq!(shard.nth(100), None);
+        assert_eq!(shard.next(), None);
+        assert!(p.shards(1000)[500].clone().next().is_none());
+    }
+
+    #[test]
+    fn test_shards_threads() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+
+        let mut values = std::thread::scope(|s| {
+            p.shards(4)
+                .into_iter()
+                .map(|shard| s.spawn(move || shard.collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        values.sort();
+        assert!(values.into_iter().eq(0..3600));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_shards_zero() {
+        let p = UniformPermutation::try_from(vec![0]).unwrap();
+        let _ = p.shards(0);
+    }
+}
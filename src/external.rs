@@ -0,0 +1,532 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{InvertiblePermutation, Permutation};
+
+static SHUFFLE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Shuffles fixed-size records that don't fit in memory.
+///
+/// Record `i` of the input is written to position `perm.nth(i)` of the output. The first pass
+/// partitions the records into buckets by the high part of their destination index, the second
+/// pass loads one bucket at a time and writes it out in order.
+///
+/// At most `max_open_files` buckets are written at once. If more are needed to fit each bucket in
+/// the memory budget, the buckets are partitioned again in further passes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalShuffle {
+    temp_dir: PathBuf,
+    memory_budget: usize,
+    max_open_files: usize,
+}
+
+impl Default for ExternalShuffle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExternalShuffle {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            temp_dir: std::env::temp_dir(),
+            memory_budget: 256 * 1024 * 1024,
+            max_open_files: 64,
+        }
+    }
+
+    #[must_use]
+    pub fn temp_dir<T: Into<PathBuf>>(mut self, temp_dir: T) -> Self {
+        self.temp_dir = temp_dir.into();
+        self
+    }
+
+    #[must_use]
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// Sets the number of temporary files that `shuffle` writes to at once. Each one also has an
+    /// 8 KiB write buffer. Values below 2 are treated as 2.
+    #[must_use]
+    pub fn max_open_files(mut self, n: usize) -> Self {
+        self.max_open_files = n.max(2);
+        self
+    }
+
+    pub fn shuffle<P: Permutation, R: Read, W: Write>(
+        &self,
+        perm: &P,
+        record_len: usize,
+        input: R,
+        mut output: W,
+    ) -> io::Result<()> {
+        if record_len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record length must be nonzero",
+            ));
+        }
+
+        let num_points = perm.num_points();
+        let mut input = BufReader::new(input);
+        let mut i = 0;
+        let mut next = |record: &mut [u8]| {
+            if i == num_points {
+                return Ok(None);
+            }
+            input.read_exact(record)?;
+            i += 1;
+            Ok(Some(perm.nth(i - 1).unwrap()))
+        };
+
+        self.write_range(0..num_points, record_len, &mut next, &mut output)?;
+        output.flush()
+    }
+
+    // Writes the records whose destinations are in `range` to `output`, in order of destination.
+    // `next` reads the next record into its argument and returns its destination, or returns
+    // `None` if there are no more records.
+    //
+    // If the records don't fit in the memory budget, they are partitioned into at most
+    // `max_open_files` buckets by destination, and each bucket is written out in turn, splitting it
+    // again if it's still too large.
+    fn write_range<W: Write>(
+        &self,
+        range: Range<u64>,
+        record_len: usize,
+        next: &mut dyn FnMut(&mut [u8]) -> io::Result<Option<u64>>,
+        output: &mut W,
+    ) -> io::Result<()> {
+        let len = range.end - range.start;
+        let budget = self.memory_budget.max(record_len) as u64;
+        let num_buckets = len.saturating_mul(record_len as u64).div_ceil(budget);
+
+        let mut record = vec![0; record_len];
+        if num_buckets <= 1 {
+            let mut buffer = vec![0; len as usize * record_len];
+            while let Some(dest) = next(&mut record)? {
+                let offset = (dest - range.start) as usize * record_len;
+                buffer[offset..offset + record_len].copy_from_slice(&record);
+            }
+            return output.write_all(&buffer);
+        }
+
+        let num_buckets = num_buckets.min(self.max_open_files as u64);
+        let bucket_len = len.div_ceil(num_buckets);
+
+        // Only files that were created here are added, so that dropping `buckets` never removes
+        // anything else.
+        let mut buckets = Buckets { paths: Vec::new() };
+        let mut writers = Vec::new();
+        for b in 0..num_buckets {
+            let (path, file) = self.create_bucket(b)?;
+            buckets.paths.push(path);
+            writers.push(BufWriter::new(file));
+        }
+
+        while let Some(dest) = next(&mut record)? {
+            let writer = &mut writers[((dest - range.start) / bucket_len) as usize];
+            writer.write_all(&dest.to_le_bytes())?;
+            writer.write_all(&record)?;
+        }
+
+        for writer in &mut writers {
+            writer.flush()?;
+        }
+        drop(writers);
+
+        for (b, path) in buckets.paths.iter().enumerate() {
+            let start = range.start + b as u64 * bucket_len;
+            let end = (start + bucket_len).min(range.end);
+
+            let mut reader = BufReader::new(File::open(path)?);
+            let mut next = |record: &mut [u8]| read_entry(&mut reader, record);
+            self.write_range(start..end, record_len, &mut next, output)?;
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    // Creates a new temporary file for bucket `b`. The file must not already exist, so that an
+    // existing file or symlink with the same name is never truncated or followed. If there is one,
+    // this tries again with the next id.
+    fn create_bucket(&self, b: u64) -> io::Result<(PathBuf, File)> {
+        loop {
+            let id = SHUFFLE_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = self
+                .temp_dir
+                .join(format!("randperm-{}-{id}-{b}.tmp", std::process::id()));
+            match File::options().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Writes the lines of `input` in permuted order. As in `shuffle`, line `i` of the input is
+    /// written to position `perm.nth(i)` of the output, so line `j` of the output is line
+    /// `perm.preimage(j)` of the input. Every output line ends with a newline, even if the last
+    /// input line didn't.
+    ///
+    /// The output is produced in windows of lines whose total length fits in the memory budget
+    /// (or a single line, if it doesn't fit). The lines in each window are read in the order they
+    /// appear in the input, so nearby lines are read without seeking.
+    pub fn shuffle_lines<P: InvertiblePermutation, R: Read + Seek, W: Write>(
+        &self,
+        perm: &P,
+        index: &LineIndex,
+        input: R,
+        mut output: W,
+    ) -> io::Result<()> {
+        let num_points = perm.num_points();
+        if num_points != index.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "permutation has {num_points} points, but the input has {} lines",
+                    index.len()
+                ),
+            ));
+        }
+
+        let mut input = BufReader::new(input);
+        input.rewind()?;
+        let mut pos = 0;
+
+        let mut sources = Vec::new();
+        let mut ranges = Vec::new();
+        let mut buffer = Vec::new();
+        let mut i = 0;
+        while i < num_points {
+            // `(line, slot)` pairs, where `slot` is the position of the line in this window.
+            sources.clear();
+            let mut bytes = 0;
+            while i < num_points {
+                let line = perm.preimage(i).unwrap();
+                let range = index.range(line);
+                let len = range.end - range.start;
+                if !sources.is_empty() && bytes + len > self.memory_budget as u64 {
+                    break;
+                }
+
+                sources.push((line, sources.len()));
+                bytes += len;
+                i += 1;
+            }
+            sources.sort_unstable();
+
+            buffer.clear();
+            ranges.clear();
+            ranges.resize(sources.len(), 0..0);
+            for &(line, slot) in &sources {
+                let range = index.range(line);
+                input.seek_relative(range.start as i64 - pos as i64)?;
+
+                let start = buffer.len();
+                buffer.resize(start + (range.end - range.start) as usize, 0);
+                input.read_exact(&mut buffer[start..])?;
+                ranges[slot] = start..buffer.len();
+                pos = range.end;
+            }
+
+            for range in &ranges {
+                let line = &buffer[range.clone()];
+                output.write_all(line.strip_suffix(b"\n").unwrap_or(line))?;
+                output.write_all(b"\n")?;
+            }
+        }
+
+        output.flush()
+    }
+}
+
+/// The byte offsets of the lines of an input, for use with `ExternalShuffle::shuffle_lines`.
+///
+/// This stores 8 bytes per line, but none of the line contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    // The start of each line, followed by the end of the input.
+    offsets: Vec<u64>,
+}
+
+impl LineIndex {
+    /// Reads `input` to the end, recording where each line starts. Lines are terminated by `\n`,
+    /// and a final line without a terminator still counts as a line.
+    pub fn new<R: Read>(input: R) -> io::Result<Self> {
+        let mut input = BufReader::new(input);
+        let mut offsets = vec![0];
+        let mut pos = 0;
+
+        loop {
+            let buf = input.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+
+            let newlines = buf.iter().enumerate().filter(|&(_, &b)| b == b'\n');
+            offsets.extend(newlines.map(|(i, _)| pos + i as u64 + 1));
+
+            let len = buf.len();
+            pos += len as u64;
+            input.consume(len);
+        }
+
+        if offsets.last() != Some(&pos) {
+            offsets.push(pos);
+        }
+
+        Ok(Self { offsets })
+    }
+
+    /// Returns the number of lines.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.offsets.len() as u64 - 1
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the byte range of line `i`, including its terminator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i >= self.len()`.
+    #[must_use]
+    pub fn range(&self, i: u64) -> Range<u64> {
+        let i = i as usize;
+        self.offsets[i]..self.offsets[i + 1]
+    }
+}
+
+// Reads a destination and a record written by `write_range`, or returns `None` at the end of the
+// file.
+fn read_entry<R: Read>(reader: &mut R, record: &mut [u8]) -> io::Result<Option<u64>> {
+    let mut dest = [0; 8];
+    match reader.read_exact(&mut dest) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    reader.read_exact(record)?;
+    Ok(Some(u64::from_le_bytes(dest)))
+}
+
+struct Buckets {
+    paths: Vec<PathBuf>,
+}
+
+impl Drop for Buckets {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::RandomPermutation;
+
+    fn records(n: u32) -> Vec<u8> {
+        (0..n).flat_map(u32::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(1000, &mut rng).unwrap();
+
+        let mut output = Vec::new();
+        ExternalShuffle::new()
+            .memory_budget(256)
+            .shuffle(&p, 4, records(1000).as_slice(), &mut output)
+            .unwrap();
+
+        for i in 0..1000 {
+            let j = p.nth(i).unwrap() as usize;
+            let record = u32::from_le_bytes(output[4 * j..4 * j + 4].try_into().unwrap());
+            assert_eq!(record as u64, i);
+        }
+    }
+
+    #[test]
+    fn test_shuffle_multi_pass() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+        let p = RandomPermutation::with_rng(1000, &mut rng).unwrap();
+
+        // 4000 bytes in buckets of at most 40 bytes needs 100 buckets, so with 3 open files at a
+        // time, the records are partitioned in several passes.
+        let temp_dir = std::env::temp_dir().join(format!("randperm-test-{}", std::process::id()));
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut output = Vec::new();
+        ExternalShuffle::new()
+            .temp_dir(&temp_dir)
+            .memory_budget(40)
+            .max_open_files(3)
+            .shuffle(&p, 4, records(1000).as_slice(), &mut output)
+            .unwrap();
+
+        for i in 0..1000 {
+            let j = p.nth(i).unwrap() as usize;
+            let record = u32::from_le_bytes(output[4 * j..4 * j + 4].try_into().unwrap());
+            assert_eq!(record as u64, i);
+        }
+
+        // The temporary files are removed.
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+        fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_shuffle_existing_temp_files() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(6);
+        let p = RandomPermutation::with_rng(1000, &mut rng).unwrap();
+
+        let temp_dir =
+            std::env::temp_dir().join(format!("randperm-test-existing-{}", std::process::id()));
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Take the names that the next few buckets would get, which mustn't be overwritten.
+        let next = SHUFFLE_COUNTER.load(Ordering::Relaxed);
+        let existing = (next..next + 20)
+            .flat_map(|id| (0..4).map(move |b| (id, b)))
+            .map(|(id, b)| temp_dir.join(format!("randperm-{}-{id}-{b}.tmp", std::process::id())))
+            .collect::<Vec<_>>();
+        for path in &existing {
+            fs::write(path, "keep").unwrap();
+        }
+
+        let mut output = Vec::new();
+        ExternalShuffle::new()
+            .temp_dir(&temp_dir)
+            .memory_budget(400)
+            .max_open_files(4)
+            .shuffle(&p, 4, records(1000).as_slice(), &mut output)
+            .unwrap();
+
+        for i in 0..1000 {
+            let j = p.nth(i).unwrap() as usize;
+            let record = u32::from_le_bytes(output[4 * j..4 * j + 4].try_into().unwrap());
+            assert_eq!(record as u64, i);
+        }
+
+        for path in &existing {
+            assert_eq!(fs::read_to_string(path).unwrap(), "keep");
+        }
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), existing.len());
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_shuffle_single_bucket() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+        let mut small_budget = Vec::new();
+        ExternalShuffle::new()
+            .memory_budget(100)
+            .shuffle(&p, 4, records(360).as_slice(), &mut small_budget)
+            .unwrap();
+
+        let mut large_budget = Vec::new();
+        ExternalShuffle::new()
+            .shuffle(&p, 4, records(360).as_slice(), &mut large_budget)
+            .unwrap();
+
+        assert_eq!(small_budget, large_budget);
+    }
+
+    fn lines(perm: &RandomPermutation, text: &str, budget: usize) -> String {
+        let index = LineIndex::new(text.as_bytes()).unwrap();
+        let mut output = Vec::new();
+        ExternalShuffle::new()
+            .memory_budget(budget)
+            .shuffle_lines(perm, &index, io::Cursor::new(text), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_line_index() {
+        let index = LineIndex::new("ab\n\ncde".as_bytes()).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.range(0), 0..3);
+        assert_eq!(index.range(1), 3..4);
+        assert_eq!(index.range(2), 4..7);
+
+        assert_eq!(LineIndex::new("ab\n".as_bytes()).unwrap().len(), 1);
+        assert!(LineIndex::new("".as_bytes()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shuffle_lines() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let text = (0..360).map(|i| format!("line {i}\n")).collect::<String>();
+
+        let expected = (0..360)
+            .map(|j| format!("line {}\n", p.preimage(j).unwrap()))
+            .collect::<String>();
+        for budget in [0, 50, 1000, 1 << 20] {
+            assert_eq!(lines(&p, &text, budget), expected);
+        }
+
+        // The last line gets a terminator.
+        assert_eq!(lines(&p, text.trim_end(), 100), expected);
+    }
+
+    #[test]
+    fn test_shuffle_lines_matches_shuffle() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let p = RandomPermutation::with_rng(1000, &mut rng).unwrap();
+
+        // Lines of the same length are records, so both methods put them in the same order.
+        let text = (0..1000).map(|i| format!("{i:07}\n")).collect::<String>();
+        let mut records = Vec::new();
+        ExternalShuffle::new()
+            .memory_budget(400)
+            .shuffle(&p, 8, text.as_bytes(), &mut records)
+            .unwrap();
+
+        assert_eq!(lines(&p, &text, 400).into_bytes(), records);
+    }
+
+    #[test]
+    fn test_shuffle_lines_wrong_length() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let p = RandomPermutation::with_rng(10, &mut rng).unwrap();
+        let index = LineIndex::new("a\nb\n".as_bytes()).unwrap();
+
+        let result =
+            ExternalShuffle::new().shuffle_lines(&p, &index, io::Cursor::new("a\nb\n"), Vec::new());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_shuffle_short_input() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng(100, &mut rng).unwrap();
+
+        let result = ExternalShuffle::new().shuffle(&p, 4, records(99).as_slice(), Vec::new());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
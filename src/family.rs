@@ -0,0 +1,133 @@
+use rand::Rng;
+
+use crate::{rng::SplitMix64, PhiloxPermutation, RandomPermutation, WorkerIter};
+
+/// A master key from which independent, reproducible permutations can be derived for any domain
+/// size and stream id, so that many differently sized permutations can be managed with one seed.
+///
+/// Deriving a permutation only mixes the key, `n` and the stream id into a new 64-bit key, so the
+/// family itself never stores anything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PermutationFamily {
+    key: u64,
+}
+
+impl PermutationFamily {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::with_key(rng.next_u64())
+    }
+
+    #[must_use]
+    pub fn with_key(key: u64) -> Self {
+        Self { key }
+    }
+
+    #[must_use]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// The key of the permutation of `0..n` for `stream_id`.
+    #[must_use]
+    pub fn derive_key(&self, n: u64, stream_id: u64) -> u64 {
+        let h = SplitMix64::mix(self.key ^ 0x9E37_79B9_7F4A_7C15);
+        let h = SplitMix64::mix(h ^ n);
+        SplitMix64::mix(h ^ stream_id)
+    }
+
+    /// The permutation of `0..n` for `stream_id`. This needs no tables, so it works for any `n`
+    /// and is cheap to create. Returns `None` if `n == 0`.
+    #[must_use]
+    pub fn get(&self, n: u64, stream_id: u64) -> Option<PhiloxPermutation> {
+        PhiloxPermutation::with_key(n, self.derive_key(n, stream_id))
+    }
+
+    /// Like `get`, but generates a `RandomPermutation`, which is faster to evaluate but only
+    /// supports the same `n` as `RandomPermutation::new`.
+    #[must_use]
+    pub fn random_permutation(&self, n: u64, stream_id: u64) -> Option<RandomPermutation> {
+        RandomPermutation::with_rng(n, &mut SplitMix64(self.derive_key(n, stream_id)))
+    }
+
+    /// Returns worker `rank`'s portion of the order of `0..n` for `epoch`, where there are
+    /// `world_size` workers sharing this family's key. The order for each epoch is `get(n, epoch)`.
+    /// Returns `None` if `n == 0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rank >= world_size`.
+    #[must_use]
+    pub fn iter_for_worker(
+        &self,
+        n: u64,
+        world_size: u64,
+        rank: u64,
+        epoch: u64,
+    ) -> Option<WorkerIter> {
+        WorkerIter::new(self, n, world_size, rank, epoch)
+    }
+}
+
+impl Default for PermutationFamily {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::Permutation;
+
+    #[test]
+    fn test_reproducible() {
+        let a = PermutationFamily::with_key(42);
+        let b = PermutationFamily::with_key(42);
+
+        for (n, stream_id) in [(1, 0), (1000, 0), (1000, 1), (1009, 7)] {
+            assert_eq!(a.get(n, stream_id), b.get(n, stream_id));
+            assert!(a
+                .get(n, stream_id)
+                .unwrap()
+                .iter()
+                .eq(b.get(n, stream_id).unwrap().iter()));
+        }
+
+        let p = a.random_permutation(360, 3).unwrap();
+        let q = b.random_permutation(360, 3).unwrap();
+        assert!(p.iter().eq(q.iter()));
+    }
+
+    #[test]
+    fn test_independent() {
+        let family = PermutationFamily::with_key(0);
+        let keys = (0..100)
+            .flat_map(|n| (0..100).map(move |stream_id| (n, stream_id)))
+            .map(|(n, stream_id)| family.derive_key(n, stream_id))
+            .collect::<HashSet<_>>();
+        assert_eq!(keys.len(), 100 * 100);
+
+        let p = family.get(1000, 0).unwrap();
+        let q = family.get(1000, 1).unwrap();
+        assert!(!p.iter().eq(q.iter()));
+
+        let other = PermutationFamily::with_key(1);
+        assert!(!p.iter().eq(other.get(1000, 0).unwrap().iter()));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let family = PermutationFamily::with_key(0);
+
+        assert_eq!(family.get(0, 0), None);
+        assert!(family.get(257, 0).is_some());
+        assert!(family.random_permutation(257, 0).is_none());
+    }
+}
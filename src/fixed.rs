@@ -0,0 +1,154 @@
+use rand::Rng;
+
+use crate::{divisor::Divisor, modmath, rng, FactoredInteger, Permutation};
+
+// A `u64` has at most 15 distinct prime factors.
+const MAX_FACTORS: usize = 15;
+
+/// A CRT permutation of `0..n` whose tables are stored inline in a fixed-capacity array, so it can
+/// be created and evaluated without heap allocation.
+///
+/// The tables for the prime power factors of `n` take up `CAP` entries in total, so `CAP` must be
+/// at least the sum of the prime power factors of `n`. Given the same RNG, this generates the same
+/// permutation as `RandomPermutation::with_rng`.
+///
+/// Creating and evaluating the permutation doesn't allocate, but the crate itself requires `std`,
+/// so this can't be used on targets without an allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FixedPermutation<const CAP: usize> {
+    num_points: u64,
+    num_factors: usize,
+    divisors: [Divisor; MAX_FACTORS],
+    // `idempotents[i]` is `1 mod m_i` and `0 mod m_j` for `j != i`.
+    idempotents: [u64; MAX_FACTORS],
+    tables: [u64; CAP],
+}
+
+impl<const CAP: usize> FixedPermutation<CAP> {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    /// Returns `None` if `n` isn't supported, or if its tables don't fit in `CAP` entries.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        let mut factors = FactoredInteger::new(n)?.factors;
+        let moduli = factors.iter().map(|&(p, k)| (p as u64).pow(k as u32));
+        if moduli.sum::<u64>() > CAP as u64 {
+            return None;
+        }
+
+        let mut perm = Self {
+            num_points: n,
+            num_factors: factors.len(),
+            divisors: [Divisor::new(1); MAX_FACTORS],
+            idempotents: [0; MAX_FACTORS],
+            tables: [0; CAP],
+        };
+
+        // This consumes the RNG in the same way as `Round::with_rng`.
+        rng::shuffle(rng, &mut factors);
+
+        let mut offset = 0;
+        for (i, &(p, k)) in factors.iter().enumerate() {
+            let m = (p as u64).pow(k as u32);
+            let cofactor = n / m;
+            let inverse = modmath::mod_inverse(cofactor, m)?;
+            perm.divisors[i] = Divisor::new(m);
+            perm.idempotents[i] = modmath::mul_mod(cofactor, inverse, n)?;
+
+            let table = &mut perm.tables[offset..offset + m as usize];
+            for (j, a) in table.iter_mut().enumerate() {
+                *a = j as u64;
+            }
+            rng::shuffle(rng, table);
+            offset += m as usize;
+        }
+
+        Some(perm)
+    }
+}
+
+impl<const CAP: usize> Permutation for FixedPermutation<CAP> {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, mut n: u64) -> Option<u64> {
+        let num_points = self.num_points;
+        if n >= num_points {
+            return None;
+        }
+
+        let components = self.divisors.iter().zip(&self.idempotents);
+        let mut offset = 0;
+        let mut x = 0;
+        for (pk, &e) in components.take(self.num_factors) {
+            let (q, r) = pk.div_rem(n);
+            n = q;
+
+            let t = modmath::mul_mod(self.tables[offset + r as usize], e, num_points)?;
+            x = if x >= num_points - t {
+                x - (num_points - t)
+            } else {
+                x + t
+            };
+            offset += pk.get() as usize;
+        }
+
+        Some(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::RandomPermutation;
+
+    #[test]
+    fn test_matches_random_permutation() {
+        for n in [
+            1,
+            2,
+            360,
+            1024,
+            2 * 3 * 5 * 7 * 11 * 13,
+            3 * 5 * 7 * 11 * 13 * 17 * 19 * 23,
+        ] {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(n);
+            let p = FixedPermutation::<1024>::with_rng(n, &mut rng).unwrap();
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(n);
+            let q = RandomPermutation::with_rng(n, &mut rng).unwrap();
+
+            assert_eq!(p.num_points(), n);
+            let samples = (0..1000).map(|i| i * (n / 1000).max(1)).filter(|&i| i < n);
+            assert!(samples.into_iter().all(|i| p.nth(i) == q.nth(i)));
+            assert_eq!(p.nth(n), None);
+        }
+    }
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = FixedPermutation::<64>::with_rng(3600, &mut rng).unwrap();
+
+        let mut values = p.iter().collect::<Vec<_>>();
+        values.sort();
+        assert!(values.into_iter().eq(0..3600));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        // 3600 = 16 * 9 * 25, so the tables need 50 entries.
+        assert!(FixedPermutation::<50>::with_rng(3600, &mut rng).is_some());
+        assert_eq!(FixedPermutation::<49>::with_rng(3600, &mut rng), None);
+        assert_eq!(FixedPermutation::<64>::with_rng(0, &mut rng), None);
+        assert_eq!(FixedPermutation::<64>::with_rng(1009 * 2, &mut rng), None);
+    }
+}
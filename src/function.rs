@@ -0,0 +1,123 @@
+use std::fmt;
+
+use crate::Permutation;
+
+/// Domains up to this size are checked to be bijections by `FnPermutation::new` in debug builds.
+const DEBUG_VALIDATION_LIMIT: u64 = 1 << 16;
+
+/// A permutation of `0..n` given by a closure, e.g. a custom mapping to be composed with the other
+/// permutations in this crate.
+///
+/// The closure must map `0..n` bijectively onto `0..n`. This isn't checked in release builds, and
+/// `nth` returns `None` for any position where the closure returns a value out of range.
+#[derive(Clone, Copy)]
+pub struct FnPermutation<F> {
+    num_points: u64,
+    f: F,
+}
+
+impl<F: Fn(u64) -> u64> FnPermutation<F> {
+    /// In debug builds, this panics if `n` is at most 2^16 and `f` isn't a bijection on `0..n`.
+    #[must_use]
+    pub fn new(n: u64, f: F) -> Self {
+        let perm = Self { num_points: n, f };
+        debug_assert!(
+            n > DEBUG_VALIDATION_LIMIT || perm.is_bijection(),
+            "closure is not a bijection on 0..{n}"
+        );
+        perm
+    }
+
+    /// Checks that the closure maps `0..n` bijectively onto `0..n`.
+    ///
+    /// This evaluates the closure at every point and uses n bits of memory, so it is only
+    /// suitable for small `n`.
+    #[must_use]
+    pub fn is_bijection(&self) -> bool {
+        let mut seen = vec![0u64; self.num_points.div_ceil(64) as usize];
+        (0..self.num_points).all(|i| {
+            let a = (self.f)(i);
+            if a >= self.num_points {
+                return false;
+            }
+
+            let (word, bit) = ((a / 64) as usize, 1 << (a % 64));
+            let new = seen[word] & bit == 0;
+            seen[word] |= bit;
+            new
+        })
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> F {
+        self.f
+    }
+}
+
+impl<F: Fn(u64) -> u64> Permutation for FnPermutation<F> {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points {
+            return None;
+        }
+
+        Some((self.f)(n)).filter(|&a| a < self.num_points)
+    }
+}
+
+impl<F> fmt::Debug for FnPermutation<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnPermutation")
+            .field("num_points", &self.num_points)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Composition, PhiloxPermutation};
+
+    #[test]
+    fn test_nth() {
+        let p = FnPermutation::new(10, |i| (i * 3 + 1) % 10);
+        assert!(p.iter().eq([1, 4, 7, 0, 3, 6, 9, 2, 5, 8]));
+        assert_eq!(p.nth(10), None);
+        assert!(p.is_bijection());
+
+        let p = FnPermutation::new(0, |i| i);
+        assert_eq!(p.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_compose() {
+        let q = PhiloxPermutation::with_key(1000, 5).unwrap();
+        let f: Box<dyn Fn(u64) -> u64> = Box::new(|i| q.nth(i).unwrap());
+        let g: Box<dyn Fn(u64) -> u64> = Box::new(|i| 999 - i);
+        let perms = [FnPermutation::new(1000, f), FnPermutation::new(1000, g)];
+        let c = Composition::new(perms).unwrap();
+        assert!(c.iter().eq(q.iter().map(|a| 999 - a)));
+    }
+
+    #[test]
+    fn test_not_bijection() {
+        // Too large to be checked by `new`.
+        let p = FnPermutation::new(1 << 20, |i| i / 2);
+        assert!(!p.is_bijection());
+
+        let p = FnPermutation::new(1 << 20, |i| i + 1);
+        assert!(!p.is_bijection());
+        assert_eq!(p.nth(0), Some(1));
+        assert_eq!(p.nth((1 << 20) - 1), None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_debug_validation() {
+        let _ = FnPermutation::new(10, |i| i / 2);
+    }
+}
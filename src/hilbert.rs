@@ -0,0 +1,144 @@
+use crate::{Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+/// Maps the row-major index of a point in a `2^bits × 2^bits` grid to its distance along the
+/// Hilbert curve, which starts at `(0, 0)` and ends at `(0, 2^bits - 1)` (as `(row, column)`).
+///
+/// Consecutive points along the curve are always adjacent in the grid, so this preserves locality
+/// better than `MortonPermutation`, at the cost of a slightly more expensive evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HilbertPermutation {
+    bits: u32,
+}
+
+impl HilbertPermutation {
+    /// Returns `None` if the grid has `2^64` or more points, i.e. if `bits >= 32`.
+    #[must_use]
+    pub fn new(bits: u32) -> Option<Self> {
+        (bits < 32).then_some(Self { bits })
+    }
+
+    /// Returns the number of points along each axis.
+    #[must_use]
+    pub fn side(&self) -> u64 {
+        1 << self.bits
+    }
+
+    // Reflects and transposes the quadrant of side `s` so that the curve in it starts at the
+    // origin and ends at the bottom left.
+    fn rotate(s: u64, x: &mut u64, y: &mut u64, rx: u64, ry: u64) {
+        if ry == 0 {
+            if rx == 1 {
+                *x = s - 1 - *x;
+                *y = s - 1 - *y;
+            }
+            std::mem::swap(x, y);
+        }
+    }
+
+    fn distance(&self, mut x: u64, mut y: u64) -> u64 {
+        let mut d = 0;
+        let mut s = self.side() >> 1;
+        while s > 0 {
+            let rx = u64::from(x & s != 0);
+            let ry = u64::from(y & s != 0);
+            d += s * s * ((3 * rx) ^ ry);
+            Self::rotate(self.side(), &mut x, &mut y, rx, ry);
+            s >>= 1;
+        }
+        d
+    }
+
+    fn point(&self, mut d: u64) -> (u64, u64) {
+        let (mut x, mut y) = (0, 0);
+        let mut s = 1;
+        while s < self.side() {
+            let rx = 1 & (d >> 1);
+            let ry = 1 & (d ^ rx);
+            Self::rotate(s, &mut x, &mut y, rx, ry);
+            x += s * rx;
+            y += s * ry;
+            d >>= 2;
+            s <<= 1;
+        }
+        (x, y)
+    }
+}
+
+impl Permutation for HilbertPermutation {
+    fn num_points(&self) -> u64 {
+        1 << (2 * self.bits)
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        (n < self.num_points()).then(|| self.distance(n & (self.side() - 1), n >> self.bits))
+    }
+}
+
+impl InvertiblePermutation for HilbertPermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        (x < self.num_points()).then(|| {
+            let (x, y) = self.point(x);
+            y << self.bits | x
+        })
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(HilbertPermutation::new(32), None);
+        assert_eq!(HilbertPermutation::new(31).unwrap().num_points(), 1 << 62);
+        assert_eq!(HilbertPermutation::new(0).unwrap().num_points(), 1);
+    }
+
+    #[test]
+    fn test_2x2() {
+        // Row-major order (0, 0), (0, 1), (1, 0), (1, 1), visited as (0, 0), (1, 0), (1, 1), (0, 1).
+        let p = HilbertPermutation::new(1).unwrap();
+        assert_eq!(p.iter().collect::<Vec<_>>(), [0, 3, 1, 2]);
+        assert_eq!(p.nth(4), None);
+    }
+
+    #[test]
+    fn test_adjacent() {
+        for bits in 0..6 {
+            let p = HilbertPermutation::new(bits).unwrap();
+            let n = p.num_points();
+
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort_unstable();
+            assert!(values.into_iter().eq(0..n));
+
+            let points = (0..n)
+                .map(|d| {
+                    let i = p.preimage(d).unwrap();
+                    assert_eq!(p.nth(i), Some(d));
+                    (i >> bits, i & (p.side() - 1))
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(points.first(), Some(&(0, 0)));
+            assert_eq!(points.last(), Some(&(0, p.side() - 1)));
+            for w in points.windows(2) {
+                assert_eq!(w[0].0.abs_diff(w[1].0) + w[0].1.abs_diff(w[1].1), 1);
+            }
+            assert_eq!(p.preimage(n), None);
+        }
+    }
+
+    #[test]
+    fn test_large() {
+        let p = HilbertPermutation::new(31).unwrap();
+        for i in [0, 1, 12345678987654321, (1 << 62) - 1] {
+            assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+        }
+    }
+}
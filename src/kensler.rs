@@ -0,0 +1,123 @@
+use rand::Rng;
+
+use crate::Permutation;
+
+/// The hash-based permutation from Andrew Kensler's "Correlated Multi-Jittered Sampling" (Pixar
+/// technical memo 13-01), for `n` up to `u32::MAX`.
+///
+/// This needs no tables and is fast to evaluate, but with only a 32-bit seed and a fixed hash
+/// function, the permutations it generates are far from uniform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KenslerPermutation {
+    num_points: u32,
+    seed: u32,
+}
+
+impl KenslerPermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::with_seed(n, rng.next_u32())
+    }
+
+    /// Returns `None` if `n == 0` or `n > u32::MAX`.
+    #[must_use]
+    pub fn with_seed(n: u64, seed: u32) -> Option<Self> {
+        let num_points = u32::try_from(n).ok().filter(|&n| n != 0)?;
+        Some(Self { num_points, seed })
+    }
+
+    #[must_use]
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    // The `permute(i, l, p)` function from the paper, where `0 < l` and `i < l` (otherwise it can
+    // loop forever).
+    //
+    // The final step `(i + p) % l` is computed without overflowing, so this differs from the
+    // original (which is then not always a bijection) when `i + p >= 2^32`.
+    fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+        let mut w = l - 1;
+        w |= w >> 1;
+        w |= w >> 2;
+        w |= w >> 4;
+        w |= w >> 8;
+        w |= w >> 16;
+
+        loop {
+            i ^= p;
+            i = i.wrapping_mul(0xe170893d);
+            i ^= p >> 16;
+            i ^= (i & w) >> 4;
+            i ^= p >> 8;
+            i = i.wrapping_mul(0x0929eb3f);
+            i ^= p >> 23;
+            i ^= (i & w) >> 1;
+            i = i.wrapping_mul(1 | p >> 27);
+            i = i.wrapping_mul(0x6935fa69);
+            i ^= (i & w) >> 11;
+            i = i.wrapping_mul(0x74dcb303);
+            i ^= (i & w) >> 2;
+            i = i.wrapping_mul(0x9e501cc3);
+            i ^= (i & w) >> 2;
+            i = i.wrapping_mul(0xc860a3df);
+            i &= w;
+            i ^= i >> 5;
+
+            if i < l {
+                break;
+            }
+        }
+
+        ((i as u64 + p as u64) % l as u64) as u32
+    }
+}
+
+impl Permutation for KenslerPermutation {
+    fn num_points(&self) -> u64 {
+        self.num_points as u64
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        let n = u32::try_from(n).ok().filter(|&n| n < self.num_points)?;
+        Some(Self::permute(n, self.num_points, self.seed) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 3, 16, 17, 1000, 1009, 65536] {
+            for seed in [0, rng.next_u32(), u32::MAX] {
+                let p = KenslerPermutation::with_seed(n, seed).unwrap();
+                let mut values = p.iter().collect::<Vec<_>>();
+                values.sort();
+
+                assert!(values.into_iter().eq(0..n));
+                assert_eq!(p.nth(n), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(KenslerPermutation::with_seed(0, 1), None);
+        assert_eq!(KenslerPermutation::with_seed(1 << 32, 1), None);
+
+        let p = KenslerPermutation::with_seed(u32::MAX as u64, 1).unwrap();
+        assert!(p.nth(u32::MAX as u64 - 1).is_some());
+        assert_eq!(p.nth(u32::MAX as u64), None);
+    }
+}
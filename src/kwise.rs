@@ -0,0 +1,323 @@
+use rand::{Rng, RngCore};
+
+use crate::{rng::SplitMix64, Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+// Four rounds are needed for the Luby-Rackoff bound to hold for inverse queries too.
+const ROUNDS: usize = 4;
+
+// The round functions are polynomials over the field of integers mod this Mersenne prime.
+const PRIME: u64 = (1 << 61) - 1;
+
+/// A keyed permutation of `0..n` from an approximately k-wise independent family.
+///
+/// Indices are encrypted with a 4-round balanced Feistel network over the smallest even number of
+/// bits `2h` that can hold `n - 1`, like `PhiloxPermutation`, except that each round function is a
+/// polynomial of degree `k - 1` over the integers mod `2^61 - 1`, reduced mod `2^h`.
+///
+/// With `with_rng` (given an RNG with uniform outputs) or `from_coefficients` with uniformly random
+/// coefficients, the polynomials are uniformly random, and the round functions are k-wise
+/// independent (up to the bias of the reduction). On any `k` distinct inputs, the network then
+/// behaves exactly as if its round functions were truly random, and by the Luby-Rackoff theorem
+/// its outputs are within statistical distance `independence_error()` of `k` values drawn without
+/// replacement from `0..2^(2h)`, even for inputs chosen adaptively or given as preimages. This
+/// doesn't hold for `with_key`, which only picks one of `2^64` sets of coefficients.
+///
+/// Outputs outside `0..n` are encrypted again (cycle walking), and each of these steps counts as
+/// an input towards `k`. Each position takes fewer than 4 encryptions on average, so `k` should
+/// be a few times larger than the number of positions that need to be jointly independent.
+///
+/// Evaluating a position takes O(k) time for each encryption, and the permutation stores `4k`
+/// coefficients.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KWisePermutation {
+    num_points: u64,
+    half_bits: u32,
+    // The coefficients of the round functions, with `k` for each round, lowest degree first.
+    coefficients: Vec<u64>,
+}
+
+impl KWisePermutation {
+    #[must_use]
+    pub fn new(n: u64, k: usize) -> Option<Self> {
+        Self::with_rng(n, k, &mut rand::thread_rng())
+    }
+
+    /// Draws each coefficient directly from `rng`, so the independence guarantee holds as long as
+    /// the outputs of `rng` are uniform. Returns `None` if `n == 0` or `k == 0`.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, k: usize, rng: &mut R) -> Option<Self> {
+        Self::generate(n, k, || rng.next_u64())
+    }
+
+    /// Expands `key` into the coefficients with `SplitMix64`. This is convenient for reproducible
+    /// permutations, but there are only `2^64` keys, so the coefficients aren't uniformly random
+    /// and the independence guarantee is only heuristic. Returns `None` if `n == 0` or `k == 0`.
+    #[must_use]
+    pub fn with_key(n: u64, k: usize, key: u64) -> Option<Self> {
+        let mut rng = SplitMix64(key);
+        Self::generate(n, k, || rng.next_u64())
+    }
+
+    /// Uses the given coefficients, `k` for each of the 4 rounds, lowest degree first, as returned
+    /// by `coefficients`. This can be used to store or share a permutation from `with_rng`.
+    ///
+    /// Returns `None` if `n == 0`, if there are no coefficients or their number isn't a multiple
+    /// of 4, or if any coefficient is at least `2^61 - 1`.
+    #[must_use]
+    pub fn from_coefficients(n: u64, coefficients: Vec<u64>) -> Option<Self> {
+        let bits = u64::BITS - n.checked_sub(1)?.leading_zeros();
+        if coefficients.is_empty()
+            || !coefficients.len().is_multiple_of(ROUNDS)
+            || coefficients.iter().any(|&c| c >= PRIME)
+        {
+            return None;
+        }
+
+        Some(Self {
+            num_points: n,
+            half_bits: bits.div_ceil(2).max(1),
+            coefficients,
+        })
+    }
+
+    fn generate<F: FnMut() -> u64>(n: u64, k: usize, mut next_u64: F) -> Option<Self> {
+        if k == 0 {
+            return None;
+        }
+
+        // Rejection sampling, so that each coefficient is uniform mod `PRIME`.
+        let coefficients = (0..ROUNDS * k)
+            .map(|_| loop {
+                let c = next_u64() >> 3;
+                if c < PRIME {
+                    break c;
+                }
+            })
+            .collect();
+        Self::from_coefficients(n, coefficients)
+    }
+
+    #[must_use]
+    pub fn coefficients(&self) -> &[u64] {
+        &self.coefficients
+    }
+
+    /// The independence parameter `k`.
+    #[must_use]
+    pub fn k(&self) -> usize {
+        self.coefficients.len() / ROUNDS
+    }
+
+    /// An upper bound on the statistical distance between the outputs of the underlying Feistel
+    /// network on any `k` distinct inputs and `k` values drawn uniformly without replacement.
+    ///
+    /// This is `k^2 / 2^h` from the Luby-Rackoff theorem, plus `4k * 2^h / (2^61 - 1)` for the
+    /// bias of the round functions, so it is only small when `n` is much larger than `k^4`.
+    #[must_use]
+    pub fn independence_error(&self) -> f64 {
+        let k = self.k() as f64;
+        let range = (1u64 << self.half_bits) as f64;
+        (k * k / range + ROUNDS as f64 * k * range / PRIME as f64).min(1.0)
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.half_bits) - 1
+    }
+
+    fn round_function(&self, x: u64, round: usize) -> u64 {
+        let k = self.k();
+        let coefficients = &self.coefficients[round * k..(round + 1) * k];
+
+        // Horner's method.
+        let y = coefficients
+            .iter()
+            .rev()
+            .fold(0, |y, &c| add_mod(mul_mod(y, x), c));
+        y & self.mask()
+    }
+
+    fn encrypt(&self, x: u64) -> u64 {
+        let (mut left, mut right) = (x >> self.half_bits, x & self.mask());
+        for round in 0..ROUNDS {
+            (left, right) = (right, left ^ self.round_function(right, round));
+        }
+        left << self.half_bits | right
+    }
+
+    fn decrypt(&self, x: u64) -> u64 {
+        let (mut left, mut right) = (x >> self.half_bits, x & self.mask());
+        for round in (0..ROUNDS).rev() {
+            (left, right) = (right ^ self.round_function(left, round), left);
+        }
+        left << self.half_bits | right
+    }
+}
+
+fn add_mod(a: u64, b: u64) -> u64 {
+    let c = a + b;
+    if c >= PRIME {
+        c - PRIME
+    } else {
+        c
+    }
+}
+
+fn mul_mod(a: u64, b: u64) -> u64 {
+    let c = a as u128 * b as u128;
+    let c = (c as u64 & PRIME) + (c >> 61) as u64;
+    add_mod(c & PRIME, c >> 61)
+}
+
+impl Permutation for KWisePermutation {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points {
+            return None;
+        }
+
+        let mut x = self.encrypt(n);
+        while x >= self.num_points {
+            x = self.encrypt(x);
+        }
+        Some(x)
+    }
+}
+
+impl InvertiblePermutation for KWisePermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        if x >= self.num_points {
+            return None;
+        }
+
+        let mut n = self.decrypt(x);
+        while n >= self.num_points {
+            n = self.decrypt(n);
+        }
+        Some(n)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_mul_mod() {
+        for (a, b) in [
+            (0, 5),
+            (1, PRIME - 1),
+            (PRIME - 1, PRIME - 1),
+            (1 << 60, 12345),
+        ] {
+            let expected = (a as u128 * b as u128 % PRIME as u128) as u64;
+            assert_eq!(mul_mod(a, b), expected);
+        }
+    }
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for (n, k) in [(1, 1), (2, 2), (5, 3), (1000, 4), (1009, 8), (65537, 2)] {
+            let p = KWisePermutation::with_rng(n, k, &mut rng).unwrap();
+            assert_eq!(p.k(), k);
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort();
+
+            assert!(values.into_iter().eq(0..n));
+            assert_eq!(p.nth(n), None);
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        for n in [1, 1009, 1 << 20, u64::MAX] {
+            let p = KWisePermutation::with_rng(n, 5, &mut rng).unwrap();
+            for i in (0..1000).map(|i| i * (n / 1000)) {
+                let a = p.nth(i).unwrap();
+                assert_eq!(p.preimage(a), Some(i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let p = KWisePermutation::with_key(1000, 4, 42).unwrap();
+        assert_eq!(p, KWisePermutation::with_key(1000, 4, 42).unwrap());
+        assert!(!p.eq_mapping(&KWisePermutation::with_key(1000, 4, 43).unwrap()));
+        assert!(!p.eq_mapping(&KWisePermutation::with_key(1000, 5, 42).unwrap()));
+
+        assert_eq!(KWisePermutation::with_key(0, 4, 42), None);
+        assert_eq!(KWisePermutation::with_key(1000, 0, 42), None);
+    }
+
+    #[test]
+    fn test_coefficients() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let mut other = rng.clone();
+
+        // Each coefficient comes straight from the RNG, rejecting values that are too large.
+        let p = KWisePermutation::with_rng(1000, 3, &mut rng).unwrap();
+        let expected = std::iter::from_fn(|| Some(other.next_u64() >> 3))
+            .filter(|&c| c < PRIME)
+            .take(12)
+            .collect::<Vec<_>>();
+        assert_eq!(p.coefficients(), expected);
+        assert_eq!(rng, other);
+
+        let q = KWisePermutation::from_coefficients(1000, p.coefficients().to_vec()).unwrap();
+        assert_eq!(q, p);
+        assert_eq!(q.k(), 3);
+
+        assert_eq!(KWisePermutation::from_coefficients(0, vec![0; 4]), None);
+        assert_eq!(KWisePermutation::from_coefficients(1000, vec![]), None);
+        assert_eq!(KWisePermutation::from_coefficients(1000, vec![0; 6]), None);
+        assert_eq!(
+            KWisePermutation::from_coefficients(1000, vec![PRIME; 4]),
+            None
+        );
+        assert!(KWisePermutation::from_coefficients(1000, vec![PRIME - 1; 4]).is_some());
+    }
+
+    #[test]
+    fn test_independence_error() {
+        let p = KWisePermutation::with_key(1 << 40, 4, 0).unwrap();
+        assert!(p.independence_error() < 1e-4);
+
+        let p = KWisePermutation::with_key(1000, 4, 0).unwrap();
+        assert!((p.independence_error() - 0.5).abs() < 1e-6);
+
+        let p = KWisePermutation::with_key(1000, 100, 0).unwrap();
+        assert_eq!(p.independence_error(), 1.0);
+    }
+
+    #[test]
+    fn test_pairwise_uniform() {
+        // With k = 8, the values at two positions should be close to a uniformly random pair of
+        // distinct values, so each value should be uniform and each order equally likely.
+        let n = 1 << 16;
+        let (mut buckets, mut less) = ([0u32; 16], 0);
+        for key in 0..16000 {
+            let p = KWisePermutation::with_key(n, 8, key).unwrap();
+            let (a, b) = (p.nth(3).unwrap(), p.nth(11).unwrap());
+            buckets[(a >> 12) as usize] += 1;
+            less += u32::from(a < b);
+        }
+
+        assert!(buckets.iter().all(|&count| count.abs_diff(1000) < 150));
+        assert!(less.abs_diff(8000) < 300);
+    }
+}
@@ -0,0 +1,246 @@
+use rand::Rng;
+
+use crate::{assert_explicit, rng, Permutation};
+
+/// Calls `f(i, c)` for each `i` in decreasing order, where `c` is the number of `j > i` with
+/// `σ(j) < σ(i)`, for a permutation `σ` of `0..n`.
+fn for_each_digit<S: Fn(usize) -> usize, F: FnMut(usize, u64)>(n: usize, sigma: S, mut f: F) {
+    // Fenwick tree counting the values seen so far.
+    let mut tree = vec![0u64; n + 1];
+
+    for i in (0..n).rev() {
+        let value = sigma(i);
+
+        let mut count = 0;
+        let mut j = value;
+        while j > 0 {
+            count += tree[j];
+            j &= j - 1;
+        }
+        f(i, count);
+
+        let mut j = value + 1;
+        while j <= n {
+            tree[j] += 1;
+            j += j & j.wrapping_neg();
+        }
+    }
+}
+
+pub fn lehmer_code<P: Permutation>(perm: &P) -> Vec<u64> {
+    assert_explicit(perm.num_points(), "lehmer_code");
+
+    let n = perm.num_points() as usize;
+    let mut code = vec![0; n];
+    for_each_digit(
+        n,
+        |i| perm.nth(i as u64).unwrap() as usize,
+        |i, count| {
+            code[i] = count;
+        },
+    );
+    code
+}
+
+/// The Lehmer code of the permutation that maps `i` to `table[i]`.
+pub fn table_lehmer_code(table: &[u64]) -> Vec<u64> {
+    let mut code = vec![0; table.len()];
+    for_each_digit(
+        table.len(),
+        |i| table[i] as usize,
+        |i, count| code[i] = count,
+    );
+    code
+}
+
+/// The inverse of `table_lehmer_code`. Returns `None` if `code[i] >= code.len() - i` for any `i`.
+pub fn from_lehmer_code(code: &[u64]) -> Option<Vec<u64>> {
+    let n = code.len();
+
+    // Fenwick tree with a 1 for each value that hasn't been used yet.
+    let mut tree = (0..=n)
+        .map(|j| (j & j.wrapping_neg()) as u64)
+        .collect::<Vec<_>>();
+    tree[0] = 0;
+    let top = if n == 0 { 0 } else { 1 << n.ilog2() };
+
+    code.iter()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if digit >= (n - i) as u64 {
+                return None;
+            }
+
+            // Find the unused value with `digit` smaller unused values, by descending the tree.
+            let (mut pos, mut remaining) = (0, digit);
+            let mut step = top;
+            while step > 0 {
+                if pos + step <= n && tree[pos + step] <= remaining {
+                    pos += step;
+                    remaining -= tree[pos];
+                }
+                step /= 2;
+            }
+
+            let mut j = pos + 1;
+            while j <= n {
+                tree[j] -= 1;
+                j += j & j.wrapping_neg();
+            }
+            Some(pos as u64)
+        })
+        .collect()
+}
+
+pub fn inversions<P: Permutation>(perm: &P) -> u64 {
+    assert_explicit(perm.num_points(), "inversions");
+    let mut total = 0;
+    for_each_digit(
+        perm.num_points() as usize,
+        |i| perm.nth(i as u64).unwrap() as usize,
+        |_, count| total += count,
+    );
+    total
+}
+
+pub fn estimate_inversions<P: Permutation, R: Rng + ?Sized>(
+    perm: &P,
+    samples: usize,
+    rng: &mut R,
+) -> f64 {
+    let n = perm.num_points();
+    if n < 2 || samples == 0 {
+        return 0.0;
+    }
+
+    let inverted = (0..samples)
+        .filter(|_| {
+            // A uniformly random pair of distinct positions.
+            let i = rng::gen_below(rng, n);
+            let j = rng::gen_below(rng, n - 1);
+            let j = if j >= i { j + 1 } else { j };
+            (i < j) == (perm.nth(i) > perm.nth(j))
+        })
+        .count();
+
+    inverted as f64 / samples as f64 * (n as f64 * (n - 1) as f64 / 2.0)
+}
+
+pub fn factoradic_rank(code: &[u64]) -> Option<u128> {
+    code.iter()
+        .enumerate()
+        .try_fold(0u128, |rank, (i, &digit)| {
+            let base = (code.len() - i) as u128;
+            rank.checked_mul(base)?.checked_add(digit as u128)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{
+        FnPermutation, PhiloxPermutation, RandomPermutation, SmallPermutation, UniformPermutation,
+        MAX_EXPLICIT_POINTS,
+    };
+
+    #[test]
+    fn test_lehmer_code() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(12, &mut rng).unwrap();
+        let values = p.iter().collect::<Vec<_>>();
+
+        let expected = (0..12)
+            .map(|i| values[i + 1..].iter().filter(|&&v| v < values[i]).count() as u64)
+            .collect::<Vec<_>>();
+
+        assert_eq!(p.lehmer_code(), expected);
+    }
+
+    #[test]
+    fn test_factoradic_rank() {
+        let id = SmallPermutation::<5>::identity();
+        assert_eq!(id.factoradic_rank(), Some(0));
+
+        // The reversal is the last permutation in lexicographic order.
+        assert_eq!(factoradic_rank(&[4, 3, 2, 1, 0]), Some(119));
+        assert_eq!(factoradic_rank(&[1, 0, 0]), Some(2));
+        assert_eq!(factoradic_rank(&[]), Some(0));
+    }
+
+    #[test]
+    fn test_factoradic_rank_overflow() {
+        let reversed_34 = (0..34).rev().collect::<Vec<_>>();
+        let reversed_35 = (0..35).rev().collect::<Vec<_>>();
+
+        assert_eq!(
+            factoradic_rank(&reversed_34),
+            (1..=34u128).product::<u128>().checked_sub(1)
+        );
+        assert_eq!(factoradic_rank(&reversed_35), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lehmer_code_too_large() {
+        let p = PhiloxPermutation::with_key(MAX_EXPLICIT_POINTS + 1, 0).unwrap();
+        let _ = p.lehmer_code();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_inversions_too_large() {
+        let p = PhiloxPermutation::with_key(MAX_EXPLICIT_POINTS + 1, 0).unwrap();
+        let _ = p.inversions();
+    }
+
+    #[test]
+    fn test_inversions() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        for n in [1, 2, 12, 1000] {
+            let p = RandomPermutation::hybrid_with_rng(n, u64::MAX, &mut rng).unwrap();
+            assert_eq!(p.inversions(), p.lehmer_code().iter().sum::<u64>());
+        }
+
+        let identity = SmallPermutation::<10>::identity();
+        assert_eq!(identity.inversions(), 0);
+        assert_eq!(identity.inverse().inversions(), 0);
+        let reversed = FnPermutation::new(10, |i| 9 - i);
+        assert_eq!(reversed.inversions(), 45);
+    }
+
+    #[test]
+    fn test_estimate_inversions() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng(62208, &mut rng).unwrap();
+        let exact = p.inversions() as f64;
+        let estimate = p.estimate_inversions(100000, &mut rng);
+        assert!((estimate - exact).abs() < exact * 0.02);
+
+        let reversed = FnPermutation::new(1 << 40, |i| (1 << 40) - 1 - i);
+        let pairs = (1u64 << 40) as f64 * ((1u64 << 40) - 1) as f64 / 2.0;
+        assert_eq!(reversed.estimate_inversions(1000, &mut rng), pairs);
+        assert_eq!(
+            SmallPermutation::<1>::identity().estimate_inversions(10, &mut rng),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_from_lehmer_code() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+
+        for n in [0, 1, 2, 12, 1000] {
+            let table = UniformPermutation::with_rng(n, &mut rng).into_vec();
+            let code = table_lehmer_code(&table);
+            assert_eq!(from_lehmer_code(&code), Some(table));
+        }
+
+        assert_eq!(from_lehmer_code(&[2, 1, 0]), Some(vec![2, 1, 0]));
+        assert_eq!(from_lehmer_code(&[3, 0, 0]), None);
+        assert_eq!(from_lehmer_code(&[0, 0, 1]), None);
+    }
+}
@@ -0,0 +1,320 @@
+use rand::{Rng, RngCore};
+
+use crate::{
+    rng::{self, SplitMix64},
+    Permutation,
+};
+
+// The distinct prime factors of `2^k - 1`, indexed by `k`.
+const MERSENNE_FACTORS: [&[u64]; 64] = [
+    &[],
+    &[],
+    &[3],
+    &[7],
+    &[3, 5],
+    &[31],
+    &[3, 7],
+    &[127],
+    &[3, 5, 17],
+    &[7, 73],
+    &[3, 11, 31],
+    &[23, 89],
+    &[3, 5, 7, 13],
+    &[8191],
+    &[3, 43, 127],
+    &[7, 31, 151],
+    &[3, 5, 17, 257],
+    &[131071],
+    &[3, 7, 19, 73],
+    &[524287],
+    &[3, 5, 11, 31, 41],
+    &[7, 127, 337],
+    &[3, 23, 89, 683],
+    &[47, 178481],
+    &[3, 5, 7, 13, 17, 241],
+    &[31, 601, 1801],
+    &[3, 2731, 8191],
+    &[7, 73, 262657],
+    &[3, 5, 29, 43, 113, 127],
+    &[233, 1103, 2089],
+    &[3, 7, 11, 31, 151, 331],
+    &[2147483647],
+    &[3, 5, 17, 257, 65537],
+    &[7, 23, 89, 599479],
+    &[3, 43691, 131071],
+    &[31, 71, 127, 122921],
+    &[3, 5, 7, 13, 19, 37, 73, 109],
+    &[223, 616318177],
+    &[3, 174763, 524287],
+    &[7, 79, 8191, 121369],
+    &[3, 5, 11, 17, 31, 41, 61681],
+    &[13367, 164511353],
+    &[3, 7, 43, 127, 337, 5419],
+    &[431, 9719, 2099863],
+    &[3, 5, 23, 89, 397, 683, 2113],
+    &[7, 31, 73, 151, 631, 23311],
+    &[3, 47, 178481, 2796203],
+    &[2351, 4513, 13264529],
+    &[3, 5, 7, 13, 17, 97, 241, 257, 673],
+    &[127, 4432676798593],
+    &[3, 11, 31, 251, 601, 1801, 4051],
+    &[7, 103, 2143, 11119, 131071],
+    &[3, 5, 53, 157, 1613, 2731, 8191],
+    &[6361, 69431, 20394401],
+    &[3, 7, 19, 73, 87211, 262657],
+    &[23, 31, 89, 881, 3191, 201961],
+    &[3, 5, 17, 29, 43, 113, 127, 15790321],
+    &[7, 32377, 524287, 1212847],
+    &[3, 59, 233, 1103, 2089, 3033169],
+    &[179951, 3203431780337],
+    &[3, 5, 7, 11, 13, 31, 41, 61, 151, 331, 1321],
+    &[2305843009213693951],
+    &[3, 715827883, 2147483647],
+    &[7, 73, 127, 337, 92737, 649657],
+];
+
+/// A permutation of `0..n` given by the sequence of states of a maximal-length linear feedback
+/// shift register, for `n = 2^k - 1` or `n = 2^k` with `1 <= k <= 63`.
+///
+/// The register is in Galois form: each step shifts the `k`-bit state left, and XORs the taps into
+/// it if the bit shifted out was set. This multiplies the state by `x` in `GF(2)[x] / P(x)`, where
+/// `P(x) = x^k + taps`, so with a primitive `P` the states starting from `start` run through every
+/// nonzero `k`-bit value before repeating. For `n = 2^k - 1`, position `i` maps to the `i`th state
+/// minus 1. For `n = 2^k`, position 0 maps to the zero state (which the register never reaches on
+/// its own), and position `i > 0` maps to the `(i - 1)`th state.
+///
+/// Only the taps and the start state are stored, and `nth` jumps ahead by computing `start * x^i`
+/// in O(k^2) time, while `successor` steps the register in O(1) time. The sequence is the classic
+/// hardware construction, but it is far from a uniformly random permutation: consecutive values
+/// are related by a shift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LfsrPermutation {
+    bits: u32,
+    // Whether the zero state is patched in at position 0, i.e. `n = 2^k`.
+    with_zero: bool,
+    taps: u64,
+    start: u64,
+}
+
+impl LfsrPermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::with_key(n, rng.next_u64())
+    }
+
+    /// Chooses the taps uniformly from the primitive polynomials of degree `k`, and the start
+    /// state uniformly from the nonzero states. Returns `None` if `n` isn't `2^k - 1` or `2^k`
+    /// with `1 <= k <= 63`.
+    #[must_use]
+    pub fn with_key(n: u64, key: u64) -> Option<Self> {
+        let (bits, _) = Self::bits(n)?;
+        let mask = u64::MAX >> (64 - bits);
+        let mut rng = SplitMix64(key);
+
+        // Roughly `1 / k` of the polynomials with a constant term are primitive.
+        loop {
+            let taps = rng.next_u64() & mask | 1;
+            let start = rng::gen_below(&mut rng, mask) + 1;
+            if let Some(perm) = Self::from_taps(n, taps, start) {
+                return Some(perm);
+            }
+        }
+    }
+
+    /// Returns the permutation with the given taps (the coefficients of `x^0, ..., x^(k - 1)` in
+    /// `P(x)`) and start state, or `None` if `n` isn't `2^k - 1` or `2^k` with `1 <= k <= 63`,
+    /// `P` isn't primitive, or `start` isn't a nonzero `k`-bit value.
+    #[must_use]
+    pub fn from_taps(n: u64, taps: u64, start: u64) -> Option<Self> {
+        let (bits, with_zero) = Self::bits(n)?;
+        let perm = Self {
+            bits,
+            with_zero,
+            taps,
+            start,
+        };
+
+        let order = perm.mask();
+        let x = perm.step(1);
+        let is_primitive = taps <= order
+            && perm.pow(x, order) == 1
+            && MERSENNE_FACTORS[bits as usize]
+                .iter()
+                .all(|&q| perm.pow(x, order / q) != 1);
+
+        (is_primitive && (1..=order).contains(&start)).then_some(perm)
+    }
+
+    // Returns `k` and whether `n = 2^k`.
+    fn bits(n: u64) -> Option<(u32, bool)> {
+        if n.checked_add(1).is_some_and(u64::is_power_of_two) && n != 0 {
+            Some(((n + 1).trailing_zeros(), false))
+        } else if n.is_power_of_two() && n != 1 {
+            Some((n.trailing_zeros(), true))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn taps(&self) -> u64 {
+        self.taps
+    }
+
+    #[must_use]
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the value after `x` in the sequence `nth(0), nth(1), ...`, wrapping around from
+    /// `nth(n - 1)` to `nth(0)`, or `None` if `x >= n`.
+    #[must_use]
+    pub fn successor(&self, x: u64) -> Option<u64> {
+        if x >= self.num_points() {
+            return None;
+        }
+
+        Some(if self.with_zero {
+            match x {
+                0 => self.start,
+                _ if self.step(x) == self.start => 0,
+                _ => self.step(x),
+            }
+        } else {
+            self.step(x + 1) - 1
+        })
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.bits)
+    }
+
+    // Multiplies `s` by `x` modulo `P`.
+    fn step(&self, s: u64) -> u64 {
+        let carry = s >> (self.bits - 1) & 1;
+        (s << 1 & self.mask()) ^ (self.taps * carry)
+    }
+
+    fn mul(&self, mut a: u64, b: u64) -> u64 {
+        let mut product = 0;
+        for i in 0..self.bits {
+            if b >> i & 1 == 1 {
+                product ^= a;
+            }
+            a = self.step(a);
+        }
+        product
+    }
+
+    fn pow(&self, mut base: u64, mut exp: u64) -> u64 {
+        let mut result = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+impl Permutation for LfsrPermutation {
+    fn num_points(&self) -> u64 {
+        self.mask() + u64::from(self.with_zero)
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points() {
+            return None;
+        }
+
+        let x = self.step(1);
+        Some(match (self.with_zero, n) {
+            (true, 0) => 0,
+            (true, _) => self.mul(self.start, self.pow(x, n - 1)),
+            (false, _) => self.mul(self.start, self.pow(x, n)) - 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::primes::is_prime;
+
+    #[test]
+    fn test_factors() {
+        for (k, factors) in MERSENNE_FACTORS.iter().enumerate().skip(1) {
+            let mut m = u64::MAX >> (64 - k);
+            for &q in *factors {
+                assert!(is_prime(q), "{q}");
+                while m % q == 0 {
+                    m /= q;
+                }
+            }
+            assert_eq!(m, 1, "{k}");
+        }
+    }
+
+    #[test]
+    fn test_with_rng() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        for n in [1, 2, 3, 4, 7, 8, 255, 256, 1 << 12, (1 << 13) - 1] {
+            let p = LfsrPermutation::with_rng(n, &mut rng).unwrap();
+            assert_eq!(p.num_points(), n);
+
+            let values = p.iter().collect::<Vec<_>>();
+            let mut sorted = values.clone();
+            sorted.sort_unstable();
+            assert!(sorted.into_iter().eq(0..n));
+
+            for i in 0..n {
+                let next = values[((i + 1) % n) as usize];
+                assert_eq!(p.successor(values[i as usize]), Some(next));
+            }
+            assert_eq!(p.nth(n), None);
+            assert_eq!(p.successor(n), None);
+        }
+
+        for n in [0, 5, 6, 9, u64::MAX] {
+            assert_eq!(LfsrPermutation::with_rng(n, &mut rng), None);
+        }
+    }
+
+    #[test]
+    fn test_from_taps() {
+        // x^4 + x + 1 is primitive, x^4 + x^3 + x^2 + x + 1 is irreducible but not primitive, and
+        // x^4 + x^2 + 1 is reducible.
+        let p = LfsrPermutation::from_taps(15, 0b0011, 1).unwrap();
+        assert_eq!(p.iter().take(5).collect::<Vec<_>>(), [0, 1, 3, 7, 2]);
+        assert_eq!(LfsrPermutation::from_taps(15, 0b1111, 1), None);
+        assert_eq!(LfsrPermutation::from_taps(15, 0b0101, 1), None);
+        assert_eq!(LfsrPermutation::from_taps(15, 0b0011, 0), None);
+        assert_eq!(LfsrPermutation::from_taps(15, 0b0011, 16), None);
+        assert_eq!(LfsrPermutation::from_taps(15, 0b10011, 1), None);
+
+        let p = LfsrPermutation::from_taps(16, 0b0011, 2).unwrap();
+        assert_eq!(p.iter().take(5).collect::<Vec<_>>(), [0, 2, 4, 8, 3]);
+    }
+
+    #[test]
+    fn test_large() {
+        let p = LfsrPermutation::with_key(1 << 63, 1).unwrap();
+        let q = LfsrPermutation::with_key((1 << 63) - 1, 1).unwrap();
+        for i in [0, 1, 2, 1234567898765, (1 << 63) - 2] {
+            let x = p.nth(i).unwrap();
+            assert_eq!(p.successor(x), p.nth(i + 1));
+
+            let y = q.nth(i).unwrap();
+            assert_eq!(q.successor(y), q.nth((i + 1) % q.num_points()));
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#![cfg_attr(feature = "simd", feature(portable_simd))]
 #![warn(clippy::must_use_candidate)]
 #![deny(clippy::use_self)]
 #![deny(clippy::double_must_use)]
@@ -8,18 +9,167 @@
 #![deny(clippy::mod_module_files)]
 #![deny(clippy::semicolon_if_nothing_returned)]
 
-mod crt;
-
-use rand::Rng;
+#[cfg(feature = "ndarray")]
+mod array;
+mod block;
+mod builder;
+mod bytes;
+pub mod checkpoint;
+#[cfg(feature = "permutation")]
+mod compat;
+pub mod crt;
+mod cursor;
+mod cycle;
+mod decompose;
+mod distribution;
+mod divisor;
+mod exclude;
+#[cfg(feature = "serde")]
+pub mod explicit;
+mod ext;
+pub mod external;
+mod family;
+mod fixed;
+mod function;
+mod hilbert;
+mod kensler;
+mod kwise;
+mod lehmer;
+mod lfsr;
+mod linear;
+mod matching;
+mod minhash;
+#[cfg(feature = "mmap")]
+mod mmap;
+pub mod modmath;
+mod morton;
+mod order;
+mod parallel;
+mod parity;
+mod persist;
+mod philox;
+mod primes;
+mod probe;
+mod quadratic;
+#[cfg(feature = "rand_core_09")]
+mod rand09;
+mod range;
+mod rank;
+mod rng;
+mod round;
+pub mod sample;
+mod shape;
+mod shuffle;
+#[cfg(feature = "simd")]
+mod simd;
+mod small;
+mod smooth;
+mod stats;
+mod subset;
+mod uniform;
+mod worker;
+
+use std::{
+    borrow::Borrow,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+};
+
+use rand::{Rng, RngCore};
+use round::{Round, SubPerms};
+use smallvec::SmallVec;
+
+pub use block::BlockPermutation;
+pub use builder::{BuildError, RandomPermutationBuilder};
+pub use bytes::FromBytesError;
+pub use cursor::PermutationCursor;
+pub use cycle::CyclicPermutation;
+pub use decompose::{Component, FromSubPermsError, RoundView};
+pub use distribution::RandPerm;
+pub use exclude::ExcludingPermutation;
+pub use ext::{
+    Complement, Inversed, InvertiblePermutation, Offset, PermutationExt, RestrictedIter, Shard,
+    Then,
+};
+pub use family::PermutationFamily;
+pub use fixed::FixedPermutation;
+pub use function::FnPermutation;
+pub use hilbert::HilbertPermutation;
+pub use kensler::KenslerPermutation;
+pub use kwise::KWisePermutation;
+pub use lfsr::LfsrPermutation;
+pub use linear::LinearPermutation;
+pub use matching::SliceBijection;
+pub use minhash::{estimate_similarity, MinHasher};
+pub use morton::MortonPermutation;
+pub use order::OrderDividingPermutation;
+pub use parallel::ParallelPermutation;
+pub use parity::{Parity, ParityPermutation};
+pub use philox::PhiloxPermutation;
+pub use primes::PrimeSet;
+pub use probe::ProbeSequence;
+pub use quadratic::QuadraticResiduePermutation;
+#[cfg(feature = "rand_core_09")]
+pub use rand09::Rng09;
+pub use range::{PermutedRange, RangeBijection};
+pub use shape::{AxesPermutation, Shape, Shaped};
+pub use shuffle::{shuffle_blocks, shuffle_slice, SliceShuffle};
+pub use small::SmallPermutation;
+pub use stats::CycleStats;
+pub use subset::{StabilizingPermutation, SubsetPermutation};
+pub use uniform::{FromVecError, UniformPermutation};
+pub use worker::WorkerIter;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct FactoredInteger {
-    factors: Vec<(u8, u8)>,
+    // A `u64` has at most 15 distinct prime factors.
+    factors: SmallVec<[(u32, u8); 15]>,
+}
+
+// The odd primes below 256, with their inverses modulo 2^64 and `u64::MAX / p`. An integer `n` is
+// divisible by `p` if and only if `n * inverse <= limit`, and then `n / p == n * inverse`, which
+// is much faster than dividing.
+const ODD_PRIMES: [(u8, u64, u64); 53] = odd_primes();
+
+const fn odd_primes() -> [(u8, u64, u64); 53] {
+    let mut table = [(0, 0, 0); 53];
+    let mut len = 0;
+
+    let mut p = 3u64;
+    while p < 256 {
+        let mut d = 3;
+        while d * d <= p && !p.is_multiple_of(d) {
+            d += 2;
+        }
+
+        if d * d > p {
+            // `p * p ≡ 1 (mod 8)`, so `p` is its own inverse modulo 2^3, and each Newton step
+            // doubles the number of correct bits.
+            let mut inverse = p;
+            let mut i = 0;
+            while i < 5 {
+                inverse = inverse.wrapping_mul(2u64.wrapping_sub(p.wrapping_mul(inverse)));
+                i += 1;
+            }
+
+            table[len] = (p as u8, inverse, u64::MAX / p);
+            len += 1;
+        }
+
+        p += 2;
+    }
+
+    table
 }
 
 impl FactoredInteger {
     fn new(mut n: u64) -> Option<Self> {
-        let mut factors = Vec::new();
+        if n == 0 {
+            return None;
+        }
+
+        let mut factors = SmallVec::new();
 
         let pow2 = n.trailing_zeros() as u8;
         if pow2 != 0 {
@@ -27,26 +177,64 @@ impl FactoredInteger {
             factors.push((2, pow2));
         }
 
-        for p in (3..u8::MAX).step_by(2) {
-            let q = p as u64;
+        for &(p, inverse, limit) in &ODD_PRIMES {
+            // `n` has no prime factors less than `p`, so if `n < p^2` then it's 1 or a prime.
+            if n < (p as u64).pow(2) {
+                break;
+            }
 
             let mut counter = 0;
-            while n % q == 0 {
+            while n.wrapping_mul(inverse) <= limit {
                 counter += 1;
-                n /= q;
+                n = n.wrapping_mul(inverse);
             }
 
             if counter > 0 {
-                factors.push((p, counter));
+                factors.push((p as u32, counter));
             }
+        }
 
-            if n == 1 {
+        match n {
+            1 => Some(Self { factors }),
+            2..=255 => {
+                factors.push((n as u32, 1));
+                Some(Self { factors })
+            }
+            _ => None,
+        }
+    }
+
+    /// Like `new`, but only allows the prime factors in `primes`.
+    fn with_primes(mut n: u64, primes: &PrimeSet) -> Option<Self> {
+        if n == 0 {
+            return None;
+        }
+
+        let mut factors = SmallVec::new();
+
+        for &p in primes.primes() {
+            let q = p as u64;
+            if n == 1 || primes.is_complete() && n < q * q {
                 break;
             }
+
+            let mut counter = 0;
+            while n.is_multiple_of(q) {
+                counter += 1;
+                n /= q;
+            }
+
+            if counter > 0 {
+                factors.push((p, counter));
+            }
         }
 
+        // If we stopped early, `n` is either 1 or a prime larger than all of the factors so far.
         if n == 1 {
             Some(Self { factors })
+        } else if primes.contains(n) {
+            factors.push((n as u32, 1));
+            Some(Self { factors })
         } else {
             None
         }
@@ -57,15 +245,171 @@ pub trait Permutation: Sized {
     fn num_points(&self) -> u64;
     fn nth(&self, n: u64) -> Option<u64>;
 
+    fn nth_usize(&self, n: usize) -> Option<usize> {
+        let n = u64::try_from(n).ok()?;
+        usize::try_from(self.nth(n)?).ok()
+    }
+
     fn iter(&self) -> PermutationIter<'_, Self> {
         PermutationIter { perm: self, idx: 0 }
     }
+
+    /// Returns an iterator over the values at positions `start..n`, e.g. for resuming an
+    /// iteration that was interrupted after `start` values.
+    fn iter_from(&self, start: u64) -> PermutationIter<'_, Self> {
+        PermutationIter {
+            perm: self,
+            idx: start.min(self.num_points()),
+        }
+    }
+
+    /// Returns an iterator over `(i, σ(i))` for each `i` in `0..n`.
+    fn pairs(&self) -> Pairs<'_, Self> {
+        Pairs { iter: self.iter() }
+    }
+
+    /// Returns the values at positions `index * page_size..(index + 1) * page_size`, clamped to
+    /// `0..n`. The last page may be short, and pages past the end are empty.
+    fn page(&self, index: u64, page_size: u64) -> Vec<u64> {
+        let num_points = self.num_points();
+        let start = index.saturating_mul(page_size).min(num_points);
+        let end = start.saturating_add(page_size).min(num_points);
+        (start..end).map(|i| self.nth(i).unwrap()).collect()
+    }
+
+    /// Returns the Lehmer code of the permutation, i.e. the number of `j > i` with `σ(j) < σ(i)`
+    /// for each `i`. This is also the sequence of digits of the factoradic rank.
+    ///
+    /// This uses O(n) memory, so it is only suitable for small `n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n > MAX_EXPLICIT_POINTS`.
+    fn lehmer_code(&self) -> Vec<u64> {
+        lehmer::lehmer_code(self)
+    }
+
+    /// Returns the number of inversions of the permutation, i.e. the number of pairs `i < j` with
+    /// `σ(i) > σ(j)`.
+    ///
+    /// This takes O(n log n) time and uses O(n) memory, so it is only suitable for small `n`. Use
+    /// `estimate_inversions` for larger `n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n > MAX_EXPLICIT_POINTS`.
+    fn inversions(&self) -> u64 {
+        lehmer::inversions(self)
+    }
+
+    /// Estimates the number of inversions from `samples` uniformly random pairs of positions. The
+    /// relative standard error is about `1 / sqrt(samples)` for a random permutation.
+    fn estimate_inversions<R: Rng + ?Sized>(&self, samples: usize, rng: &mut R) -> f64 {
+        lehmer::estimate_inversions(self, samples, rng)
+    }
+
+    /// Returns the index of the permutation in the lexicographic ordering of all permutations of
+    /// `0..n`, or `None` if it doesn't fit in a `u128`. This can only happen when `n > 34`, since
+    /// `34! < 2^128 < 35!`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n > MAX_EXPLICIT_POINTS`, since this computes the Lehmer code.
+    fn factoradic_rank(&self) -> Option<u128> {
+        lehmer::factoradic_rank(&self.lehmer_code())
+    }
+
+    /// Returns the parity of the permutation, by counting its cycles.
+    ///
+    /// This takes O(n) time and uses n bits of memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n > MAX_EXPLICIT_POINTS`.
+    fn parity(&self) -> Parity {
+        parity::parity(self)
+    }
+
+    /// Returns the number of cycles of each length.
+    ///
+    /// By default this follows every cycle, which takes O(n) time and uses n bits of memory. Only
+    /// `OrderDividingPermutation`, which combines the cycle types of its components, and
+    /// `CyclicPermutation`, which takes O(1) time, avoid this walk. Other implementations, e.g.
+    /// `RandomPermutation`, at most make each step of the walk cheaper.
+    ///
+    /// # Panics
+    ///
+    /// The default implementation panics if `n > MAX_EXPLICIT_POINTS`.
+    fn cycle_stats(&self) -> CycleStats {
+        stats::cycle_stats(self)
+    }
+
+    /// Returns a copy of `array` with the lanes along `axis` rearranged, so that lane `i` of the
+    /// result is lane `σ(i)` of `array`.
+    ///
+    /// Returns `None` if `array` has no such axis, or if its length isn't `num_points`.
+    #[cfg(feature = "ndarray")]
+    fn permute_axis<A, S, D>(
+        &self,
+        array: &ndarray::ArrayBase<S, D>,
+        axis: ndarray::Axis,
+    ) -> Option<ndarray::Array<A, D>>
+    where
+        A: Clone,
+        S: ndarray::Data<Elem = A>,
+        D: ndarray::RemoveAxis,
+    {
+        array::permute_axis(self, array, axis)
+    }
+
+    /// Checks whether `self` and `other` are the same function, by evaluating both at every point.
+    fn eq_mapping<Q: Permutation>(&self, other: &Q) -> bool {
+        self.num_points() == other.num_points() && self.iter().eq(other.iter())
+    }
+}
+
+/// The size below which `RandomPermutation::new_hybrid` generates an explicit table.
+pub const DEFAULT_UNIFORM_THRESHOLD: u64 = 1 << 20;
+
+/// The size above which a prime power component is represented by a table-free bijection instead
+/// of a table, so that e.g. `n = 2^40` doesn't need a table with `2^40` entries. See
+/// `RandomPermutationBuilder::implicit_threshold`.
+pub const DEFAULT_IMPLICIT_THRESHOLD: u64 = 1 << 32;
+
+/// The largest number of points supported by the `Permutation` methods that store O(n) data about
+/// the permutation, such as `lehmer_code`. They panic for larger permutations instead of trying to
+/// allocate tens of gigabytes.
+pub const MAX_EXPLICIT_POINTS: u64 = 1 << 32;
+
+// Panics if `method`, which needs O(n) memory, is called on a permutation with `n` points.
+fn assert_explicit(n: u64, method: &str) {
+    assert!(
+        n <= MAX_EXPLICIT_POINTS,
+        "`{method}` needs O(n) memory, but the permutation has {n} points"
+    );
+}
+
+/// How a permutation was generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Combined from independent permutations of the prime power factors of `n` using the Chinese
+    /// Remainder Theorem. Only a tiny fraction of all permutations can be generated this way.
+    Crt,
+    /// Uniformly distributed over all permutations of `0..n`.
+    Uniform,
+}
+
+fn shuffled_table<R: Rng + ?Sized>(len: u64, rng: &mut R) -> Vec<u64> {
+    let mut vec = Vec::with_capacity(len as usize);
+    vec.extend(0..len);
+    rng::shuffle(rng, &mut vec);
+    vec
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RandomPermutation {
     num_points: u64,
-    sub_perms: Vec<Vec<u64>>,
+    rounds: Vec<Round>,
 }
 
 impl RandomPermutation {
@@ -74,42 +418,312 @@ impl RandomPermutation {
         Self::with_rng(n, &mut rand::thread_rng())
     }
 
-    pub fn with_rng<R: Rng>(n: u64, rng: &mut R) -> Option<Self> {
-        let factored_n = FactoredInteger::new(n)?;
-        let num_prime_powers = factored_n.factors.len();
+    #[must_use]
+    pub fn new_usize(n: usize) -> Option<Self> {
+        Self::new(u64::try_from(n).ok()?)
+    }
 
-        let mut order = (0..num_prime_powers).collect::<Vec<_>>();
-        for a in 0..num_prime_powers {
-            let b = rng.gen_range(a..num_prime_powers);
-            order.swap(a, b);
-        }
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::with_rng_rounds(n, 1, rng)
+    }
 
-        let sub_perms = (0..num_prime_powers)
-            .map(|i| {
-                let (p, k) = factored_n.factors[order[i]];
-                let pk = (p as u64).pow(k as u32);
-                let mut vec = (0..pk).collect::<Vec<_>>();
+    /// A non-generic version of `with_rng`, for use with RNG trait objects.
+    pub fn with_dyn_rng(n: u64, rng: &mut dyn RngCore) -> Option<Self> {
+        Self::with_rng(n, rng)
+    }
 
-                let pk = pk as usize;
-                for a in 0..pk {
-                    let b = rng.gen_range(a..pk);
-                    vec.swap(a, b);
-                }
+    /// Generates `rounds` independent CRT permutations (each with its own factor order) and
+    /// composes them, applying them in the order they were generated. More rounds give output that
+    /// looks more random, at the cost of slower evaluation.
+    ///
+    /// Returns `None` if `rounds == 0`.
+    pub fn with_rng_rounds<R: Rng + ?Sized>(n: u64, rounds: usize, rng: &mut R) -> Option<Self> {
+        if rounds == 0 {
+            return None;
+        }
 
-                vec
-            })
-            .collect();
+        let factors = FactoredInteger::new(n)?.factors;
+        Some(Self::from_factors(
+            n,
+            &factors,
+            rounds,
+            DEFAULT_IMPLICIT_THRESHOLD,
+            rng,
+        ))
+    }
 
-        Some(Self {
+    fn from_factors<R: Rng + ?Sized>(
+        n: u64,
+        factors: &[(u32, u8)],
+        rounds: usize,
+        implicit_threshold: u64,
+        rng: &mut R,
+    ) -> Self {
+        Self {
             num_points: n,
-            sub_perms,
-        })
+            rounds: (0..rounds)
+                .map(|_| Round::with_rng(factors, implicit_threshold, rng))
+                .collect(),
+        }
+    }
+
+    /// Returns whether `new` and `with_rng` accept `n`, i.e. whether `n` is a nonzero product of
+    /// primes less than 256. This doesn't allocate or generate anything.
+    #[must_use]
+    pub fn is_supported(n: u64) -> bool {
+        FactoredInteger::new(n).is_some()
+    }
+
+    /// Returns the smallest supported integer that is at least `n`, e.g. for padding a domain to
+    /// a size that `new` accepts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no supported `u64` that is at least `n`, which only happens very close
+    /// to `u64::MAX`.
+    #[must_use]
+    pub fn next_supported(n: u64) -> u64 {
+        Self::next_supported_with_bound(n, u8::MAX as u64)
+            .expect("no supported integer fits in a u64")
+    }
+
+    /// Like `next_supported`, but only allows prime factors up to `bound`, as in
+    /// `RandomPermutationBuilder::smoothness_bound`. Returns `None` if there is no such integer
+    /// that fits in a `u64`. See also `PrimeSet::next_smooth`.
+    #[must_use]
+    pub fn next_supported_with_bound(n: u64, bound: u64) -> Option<u64> {
+        PrimeSet::up_to(bound).next_smooth(n)
+    }
+
+    /// Returns a builder for configuring how the permutation is generated.
+    #[must_use]
+    pub fn builder(n: u64) -> RandomPermutationBuilder {
+        RandomPermutationBuilder::new(n)
+    }
+
+    /// Like `new`, but uses a uniformly random explicit table when `n` is below
+    /// `DEFAULT_UNIFORM_THRESHOLD`. See `hybrid_with_rng`.
+    #[must_use]
+    pub fn new_hybrid(n: u64) -> Option<Self> {
+        Self::hybrid_with_rng(n, DEFAULT_UNIFORM_THRESHOLD, &mut rand::thread_rng())
+    }
+
+    /// Generates a uniformly random permutation stored as a single explicit table if
+    /// `n < threshold`, and uses the CRT construction otherwise. Below the threshold, `n` doesn't
+    /// need to be a product of small prime powers.
+    pub fn hybrid_with_rng<R: Rng + ?Sized>(n: u64, threshold: u64, rng: &mut R) -> Option<Self> {
+        if n == 0 {
+            None
+        } else if n < threshold {
+            Some(UniformPermutation::with_rng(n, rng).into())
+        } else {
+            Self::with_rng(n, rng)
+        }
+    }
+
+    // Like `hybrid_with_rng` with the default threshold, but falls back to a single implicit
+    // component for large `n` that the CRT construction doesn't support, so it only fails if
+    // `n == 0`.
+    pub(crate) fn any_size_with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::hybrid_with_rng(n, DEFAULT_UNIFORM_THRESHOLD, rng)
+            .or_else(|| PhiloxPermutation::with_rng(n, rng).map(Into::into))
+    }
+
+    /// Replaces the permutation with a new random one of the same size, generated in the same way
+    /// (e.g. with the same number of rounds), reusing the existing tables instead of allocating new
+    /// ones.
+    ///
+    /// For a permutation from `with_rng_rounds`, this gives the same result as calling it again with
+    /// `rng`. A uniformly random table stays uniformly random.
+    pub fn regenerate<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for round in &mut self.rounds {
+            round.regenerate(rng);
+        }
     }
 
     #[must_use]
     pub fn inverse(&self) -> Inverse<'_> {
         Inverse { perm: self }
     }
+
+    /// Replaces the permutation with its inverse, by inverting each table in place and reversing
+    /// the order of the rounds. Unlike `inverse`, the result is a `RandomPermutation` that doesn't
+    /// borrow `self`, and unlike `UniformPermutation::inverse`, it doesn't allocate.
+    pub fn invert_in_place(&mut self) {
+        self.rounds.reverse();
+        for round in &mut self.rounds {
+            round.invert();
+        }
+    }
+
+    /// Like `invert_in_place`, but takes ownership.
+    #[must_use]
+    pub fn into_inverted(mut self) -> Self {
+        self.invert_in_place();
+        self
+    }
+
+    /// Replaces the permutation with `other ∘ self`, i.e. `self` followed by `other`, stored as a
+    /// single explicit table. Unlike a `Composition`, evaluating the result stays a single lookup
+    /// however many permutations are composed into it. If the permutation is already a single
+    /// table, it is updated in place.
+    ///
+    /// This uses O(n) memory, so it is only suitable for small `n`. Returns
+    /// `CompositionError::TooLarge` without allocating if `n > MAX_EXPLICIT_POINTS` and the
+    /// permutation isn't a single table already.
+    pub fn compose_assign<P: Permutation>(&mut self, other: &P) -> Result<(), CompositionError> {
+        if other.num_points() != self.num_points {
+            return Err(CompositionError::SizeMismatch {
+                index: 1,
+                expected: self.num_points,
+                found: other.num_points(),
+            });
+        }
+
+        match &mut self.rounds[..] {
+            [] => {}
+            [round] if round.single_table().is_some() => {
+                for a in round.single_table_mut().unwrap() {
+                    *a = other.nth(*a).unwrap();
+                }
+            }
+            _ if self.num_points > MAX_EXPLICIT_POINTS => {
+                return Err(CompositionError::TooLarge {
+                    num_points: self.num_points,
+                });
+            }
+            _ => {
+                let mut table = vec![0; self.num_points as usize];
+                self.fill_range(0, &mut table);
+                for a in &mut table {
+                    *a = other.nth(*a).unwrap();
+                }
+                self.rounds = vec![Round::new(vec![table]).unwrap()];
+            }
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn num_rounds(&self) -> usize {
+        self.rounds.len()
+    }
+
+    /// With a single nontrivial component (e.g. when `n` is a prime power), the CRT construction is
+    /// just a Fisher-Yates shuffle, so it is exactly uniform.
+    #[must_use]
+    pub fn mode(&self) -> Mode {
+        if self.rounds.iter().any(|round| round.num_nontrivial() > 1) {
+            Mode::Crt
+        } else {
+            Mode::Uniform
+        }
+    }
+
+    /// Puts the internal tables into a canonical form, so that `==` agrees with `eq_mapping` for
+    /// permutations with a single round.
+    ///
+    /// The order of the nontrivial components is already determined by the mapping (two different
+    /// orders can never give the same function), so this only needs to remove trivial components.
+    pub fn canonicalize(&mut self) {
+        for round in &mut self.rounds {
+            round.canonicalize();
+        }
+    }
+
+    /// Checks that the internal tables describe a bijection on `0..n`.
+    ///
+    /// This takes time proportional to the total size of the tables.
+    #[must_use]
+    pub fn verify(&self) -> bool {
+        self.rounds
+            .iter()
+            .all(|round| round.verify(self.num_points))
+    }
+
+    /// A cheaper version of `verify` that checks the moduli and then evaluates the permutation at
+    /// `samples` random points, checking that each table entry used is in range and that the CRT
+    /// reconstruction is consistent with it. Unlike `verify`, this can't detect every invalid
+    /// table.
+    pub fn verify_sampled<R: Rng + ?Sized>(&self, samples: usize, rng: &mut R) -> bool {
+        if !self
+            .rounds
+            .iter()
+            .all(|round| round.verify_moduli(self.num_points))
+        {
+            return false;
+        }
+        if self.num_points == 0 {
+            return true;
+        }
+
+        (0..samples).all(|_| {
+            let i = rng::gen_below(rng, self.num_points);
+            self.rounds
+                .iter()
+                .try_fold(i, |n, round| round.nth_checked(n))
+                .is_some()
+        })
+    }
+}
+
+impl From<UniformPermutation> for RandomPermutation {
+    fn from(perm: UniformPermutation) -> Self {
+        let table = perm.into_vec();
+        let n = table.len() as u64;
+
+        // A single component is an explicit table, and the CRT step is the identity.
+        Self {
+            num_points: n,
+            rounds: if n == 0 {
+                Vec::new()
+            } else {
+                vec![Round::new(vec![table]).unwrap()]
+            },
+        }
+    }
+}
+
+/// Wraps the permutation as a single implicit component, e.g. for sizes that the CRT construction
+/// doesn't support and that are too large for an explicit table.
+impl From<PhiloxPermutation> for RandomPermutation {
+    fn from(perm: PhiloxPermutation) -> Self {
+        let mut sub_perms = SubPerms::new();
+        sub_perms.push_implicit(perm.into());
+
+        Self {
+            num_points: perm.num_points(),
+            rounds: vec![Round::new(sub_perms).unwrap()],
+        }
+    }
+}
+
+// Only the tables are secret. The moduli only depend on `n`.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for RandomPermutation {
+    fn zeroize(&mut self) {
+        self.rounds.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for RandomPermutation {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for RandomPermutation {}
+
+impl Hash for RandomPermutation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.num_points.hash(state);
+        for round in &self.rounds {
+            round.hash(state);
+        }
+    }
 }
 
 impl Permutation for RandomPermutation {
@@ -117,25 +731,24 @@ impl Permutation for RandomPermutation {
         self.num_points
     }
 
-    fn nth(&self, mut n: u64) -> Option<u64> {
+    fn nth(&self, n: u64) -> Option<u64> {
         if n >= self.num_points {
             return None;
         }
 
-        let remainders = self.sub_perms.iter().fold(Vec::new(), |mut rem, perm| {
-            let pk = perm.len() as u64;
-            rem.push(perm[(n % pk) as usize]);
-            n /= pk;
-            rem
-        });
-
-        let moduli = self
-            .sub_perms
-            .iter()
-            .map(|perm| perm.len() as u64)
-            .collect::<Vec<_>>();
+        Some(self.rounds.iter().fold(n, |n, round| round.nth(n)))
+    }
 
-        Some(crt::chinese_remainder(&remainders, &moduli).unwrap())
+    /// A single table is walked directly, which is faster than evaluating `nth` at each point.
+    /// Anything else, including a single CRT round, is walked with `nth`, as in the default
+    /// implementation.
+    fn cycle_stats(&self) -> CycleStats {
+        match self.rounds.as_slice() {
+            [round] => round
+                .single_table()
+                .map_or_else(|| stats::cycle_stats(self), CycleStats::from_table),
+            _ => stats::cycle_stats(self),
+        }
     }
 }
 
@@ -152,11 +765,13 @@ impl Permutation for Inverse<'_> {
         if n >= self.num_points() {
             None
         } else {
-            Some(self.perm.sub_perms.iter().rev().fold(0, |idx, perm| {
-                let pk = perm.len() as u64;
-                let pos = perm.iter().position(|&a| a == n % pk).unwrap() as u64;
-                idx * pk + pos
-            }))
+            Some(
+                self.perm
+                    .rounds
+                    .iter()
+                    .rev()
+                    .fold(n, |n, round| round.preimage(n)),
+            )
         }
     }
 }
@@ -166,44 +781,200 @@ pub struct PermutationIter<'a, P: Permutation> {
     idx: u64,
 }
 
+impl<P: Permutation> PermutationIter<'_, P> {
+    fn remaining(&self) -> u64 {
+        self.perm.num_points().saturating_sub(self.idx)
+    }
+}
+
 impl<P: Permutation> Iterator for PermutationIter<'_, P> {
     type Item = u64;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let a = self.perm.nth(self.idx);
+        let a = self.perm.nth(self.idx)?;
         self.idx += 1;
-        a
+        Some(a)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = usize::try_from(self.remaining()).ok();
+        (len.unwrap_or(usize::MAX), len)
+    }
+
+    fn count(self) -> usize {
+        usize::try_from(self.remaining()).expect("iterator length overflowed a usize")
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        match self.remaining() {
+            0 => None,
+            _ => self.perm.nth(self.perm.num_points() - 1),
+        }
     }
 
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
-        self.idx += n as u64;
-        self.perm.nth(self.idx)
+        self.idx = self
+            .idx
+            .saturating_add(n as u64)
+            .min(self.perm.num_points());
+        self.next()
     }
 }
 
-pub struct Composition<'a> {
-    perms: &'a [RandomPermutation],
+impl<P: Permutation> FusedIterator for PermutationIter<'_, P> {}
+
+/// An owning iterator over the values of a `RandomPermutation`, in order.
+#[derive(Debug, Clone)]
+pub struct IntoIter {
+    perm: RandomPermutation,
+    idx: u64,
 }
 
-impl<'a> Composition<'a> {
-    #[must_use]
-    pub fn new(perms: &'a [RandomPermutation]) -> Option<Self> {
-        if perms.is_empty() {
-            return None;
+impl Iterator for IntoIter {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let a = self.perm.nth(self.idx)?;
+        self.idx += 1;
+        Some(a)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.perm.num_points() - self.idx;
+        let len = usize::try_from(remaining).ok();
+        (len.unwrap_or(usize::MAX), len)
+    }
+}
+
+impl IntoIterator for RandomPermutation {
+    type Item = u64;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { perm: self, idx: 0 }
+    }
+}
+
+pub struct Pairs<'a, P: Permutation> {
+    iter: PermutationIter<'a, P>,
+}
+
+impl<P: Permutation> Iterator for Pairs<'_, P> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.iter.idx;
+        self.iter.next().map(|a| (i, a))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositionError {
+    /// There were no permutations to compose.
+    Empty,
+    /// Permutation `index` has `found` points, but the first one has `expected`.
+    SizeMismatch {
+        index: usize,
+        expected: u64,
+        found: u64,
+    },
+    /// The result would be an explicit table with `num_points` entries, more than
+    /// `MAX_EXPLICIT_POINTS`.
+    TooLarge { num_points: u64 },
+}
+
+impl fmt::Display for CompositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no permutations to compose"),
+            Self::SizeMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "permutation {index} has {found} points, but the first one has {expected}"
+            ),
+            Self::TooLarge { num_points } => {
+                write!(f, "{num_points} points is too many for an explicit table")
+            }
         }
+    }
+}
+
+impl std::error::Error for CompositionError {}
 
-        if !perms
+/// Applies a sequence of permutations of the same size, in order.
+///
+/// The permutations can be owned, borrowed (`&P`), or a mix of both (`Cow<'_, P>`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Composition<P> {
+    perms: Vec<P>,
+}
+
+impl<P: Permutation> Composition<P> {
+    pub fn new<I: IntoIterator<Item = P>>(perms: I) -> Result<Self, CompositionError> {
+        let perms = perms.into_iter().collect::<Vec<_>>();
+        let expected = perms.first().ok_or(CompositionError::Empty)?.num_points();
+
+        if let Some((index, p)) = perms
             .iter()
-            .all(|p| p.num_points() == perms[0].num_points())
+            .enumerate()
+            .find(|(_, p)| p.num_points() != expected)
         {
-            return None;
+            return Err(CompositionError::SizeMismatch {
+                index,
+                expected,
+                found: p.num_points(),
+            });
+        }
+
+        Ok(Self { perms })
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> Vec<P> {
+        self.perms
+    }
+}
+
+impl<P: Borrow<RandomPermutation>> Composition<P> {
+    /// Combines the rounds of all the permutations into a single `RandomPermutation` with the
+    /// same mapping, which avoids evaluating each permutation separately.
+    ///
+    /// Adjacent rounds that consist of a single table (e.g. when `n` is a prime power, or for
+    /// uniformly random tables) are precomposed into one table, so any number of them cost a
+    /// single lookup. Other rounds are kept as they are, since the conversion between the CRT
+    /// residues of one round and the mixed-radix digits of the next doesn't act on each component
+    /// separately.
+    #[must_use]
+    pub fn precompose(&self) -> RandomPermutation {
+        let mut rounds = Vec::<Round>::new();
+
+        for round in self.perms.iter().flat_map(|perm| &perm.borrow().rounds) {
+            let mut round = round.clone();
+            round.canonicalize();
+
+            if let (Some(prev), Some(table)) = (
+                rounds.last_mut().and_then(Round::single_table_mut),
+                round.single_table(),
+            ) {
+                for a in prev {
+                    *a = table[*a as usize];
+                }
+            } else {
+                rounds.push(round);
+            }
         }
 
-        Some(Self { perms })
+        RandomPermutation {
+            num_points: self.perms[0].borrow().num_points,
+            rounds,
+        }
     }
 }
 
-impl Permutation for Composition<'_> {
+impl<P: Permutation> Permutation for Composition<P> {
     fn num_points(&self) -> u64 {
         self.perms[0].num_points()
     }
@@ -213,10 +984,32 @@ impl Permutation for Composition<'_> {
     }
 }
 
+impl<P: InvertiblePermutation> InvertiblePermutation for Composition<P> {
+    type Inverse<'a>
+        = Composition<P::Inverse<'a>>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.perms
+            .iter()
+            .rev()
+            .try_fold(x, |x, perm| perm.preimage(x))
+    }
+
+    /// The inverses of the permutations, in reverse order.
+    fn inverse(&self) -> Composition<P::Inverse<'_>> {
+        Composition {
+            perms: self.perms.iter().rev().map(P::inverse).collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rand::SeedableRng;
     use rand_xoshiro::Xoshiro256StarStar;
+    use smallvec::smallvec;
 
     use crate::*;
 
@@ -229,7 +1022,7 @@ mod tests {
             assert_eq!(
                 n,
                 Some(FactoredInteger {
-                    factors: vec![
+                    factors: smallvec![
                         (2, 11),
                         (3, 3),
                         (5, 2),
@@ -249,7 +1042,7 @@ mod tests {
             assert_eq!(
                 n,
                 Some(FactoredInteger {
-                    factors: vec![(3, 25), (199, 1), (211, 1), (251, 1)]
+                    factors: smallvec![(3, 25), (199, 1), (211, 1), (251, 1)]
                 })
             );
         }
@@ -260,7 +1053,7 @@ mod tests {
             assert_eq!(
                 n,
                 Some(FactoredInteger {
-                    factors: vec![(2, 63)]
+                    factors: smallvec![(2, 63)]
                 })
             );
         }
@@ -271,6 +1064,41 @@ mod tests {
             assert_eq!(n, None);
         }
 
+        #[test]
+        fn test_new_exhaustive() {
+            fn naive(mut n: u64) -> Option<Vec<(u32, u8)>> {
+                let mut factors = Vec::new();
+                for p in 2..256 {
+                    let mut k = 0;
+                    while n.is_multiple_of(p) {
+                        n /= p;
+                        k += 1;
+                    }
+                    if k > 0 {
+                        factors.push((p as u32, k));
+                    }
+                }
+                (n == 1).then_some(factors)
+            }
+
+            let large = [
+                251u64.pow(8),
+                251 * 241,
+                241u64.pow(2) * 257,
+                1 << 63,
+                65521,
+            ];
+            for n in (1..100000).chain(large) {
+                let factors = FactoredInteger::new(n).map(|n| n.factors.to_vec());
+                assert_eq!(factors, naive(n), "{n}");
+            }
+        }
+
+        #[test]
+        fn test_new_zero() {
+            assert_eq!(FactoredInteger::new(0), None);
+        }
+
         #[test]
         fn test_new_5() {
             let n = FactoredInteger::new(1297068779 * 3196491187);
@@ -311,6 +1139,237 @@ mod tests {
             assert_eq!(p.nth(u64::MAX), None);
         }
 
+        #[test]
+        fn test_is_supported() {
+            assert!(RandomPermutation::is_supported(1));
+            assert!(RandomPermutation::is_supported(14237396402848819200));
+            assert!(RandomPermutation::is_supported(251u64.pow(8)));
+            assert!(RandomPermutation::is_supported(1 << 63));
+            assert!(!RandomPermutation::is_supported(0));
+            assert!(!RandomPermutation::is_supported(257));
+            assert!(!RandomPermutation::is_supported(2 * 257));
+            assert!(!RandomPermutation::is_supported(u64::MAX));
+
+            for n in 0..1000 {
+                assert_eq!(
+                    RandomPermutation::is_supported(n),
+                    RandomPermutation::new(n).is_some()
+                );
+            }
+        }
+
+        #[test]
+        fn test_next_supported() {
+            assert_eq!(RandomPermutation::next_supported(0), 1);
+            assert_eq!(RandomPermutation::next_supported(257), 258);
+            assert_eq!(RandomPermutation::next_supported(1 << 63), 1 << 63);
+            assert_eq!(
+                RandomPermutation::next_supported_with_bound(1000, 2),
+                Some(1024)
+            );
+            assert_eq!(
+                RandomPermutation::next_supported_with_bound(1001, 10),
+                Some(1008)
+            );
+            assert_eq!(
+                RandomPermutation::next_supported_with_bound(u64::MAX, 255),
+                None
+            );
+
+            for n in [12345, 10u64.pow(9) + 7, 999999999989] {
+                let m = RandomPermutation::next_supported(n);
+                assert!(m >= n);
+                assert!(RandomPermutation::is_supported(m));
+                assert!((n..m).all(|k| !RandomPermutation::is_supported(k)));
+            }
+        }
+
+        #[test]
+        fn test_mode() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+            for (n, mode) in [(1, Mode::Uniform), (243, Mode::Uniform), (300, Mode::Crt)] {
+                let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+                assert_eq!(p.mode(), mode);
+            }
+        }
+
+        #[test]
+        fn test_hybrid() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+            let p = RandomPermutation::hybrid_with_rng(1009, 1 << 20, &mut rng).unwrap();
+            assert_eq!(p.mode(), Mode::Uniform);
+            assert!(p.verify());
+
+            let mut vec = p.iter().collect::<Vec<_>>();
+            vec.sort();
+            assert!(vec.into_iter().eq(0..1009));
+
+            let inv = p.inverse();
+            for i in 0..1009 {
+                assert_eq!(inv.nth(p.nth(i).unwrap()), Some(i));
+            }
+
+            let p = RandomPermutation::hybrid_with_rng(362880, 1000, &mut rng).unwrap();
+            assert_eq!(p.mode(), Mode::Crt);
+
+            assert!(RandomPermutation::hybrid_with_rng(1009, 1000, &mut rng).is_none());
+            assert!(RandomPermutation::hybrid_with_rng(0, 1000, &mut rng).is_none());
+        }
+
+        #[test]
+        fn test_from_uniform() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let u = UniformPermutation::with_rng(100, &mut rng);
+            let p = RandomPermutation::from(u.clone());
+
+            assert!(p.eq_mapping(&u));
+
+            let empty = RandomPermutation::from(UniformPermutation::with_rng(0, &mut rng));
+            assert_eq!(empty.num_points(), 0);
+            assert_eq!(empty.nth(0), None);
+        }
+
+        #[test]
+        fn test_from_philox() {
+            let philox = PhiloxPermutation::with_key(1009, 5).unwrap();
+            let p = RandomPermutation::from(philox);
+
+            assert_eq!(p.num_points(), 1009);
+            assert!(p.eq_mapping(&philox));
+            assert!(p.verify());
+            for i in 0..1009 {
+                assert_eq!(p.preimage(i), philox.preimage(i));
+            }
+
+            let q = RandomPermutation::from(PhiloxPermutation::with_key(1 << 40, 5).unwrap());
+            assert_eq!(q.preimage(q.nth(12345678).unwrap()), Some(12345678));
+        }
+
+        #[test]
+        fn test_with_dyn_rng() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(362880, &mut rng).unwrap();
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let dyn_rng: &mut dyn RngCore = &mut rng;
+            let q = RandomPermutation::with_dyn_rng(362880, dyn_rng).unwrap();
+
+            assert_eq!(p, q);
+        }
+
+        #[test]
+        fn test_with_rng_rounds() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng_rounds(362880, 3, &mut rng).unwrap();
+            assert_eq!(p.num_rounds(), 3);
+            assert!(p.verify());
+
+            let mut vec = p.iter().collect::<Vec<_>>();
+            vec.sort();
+            assert!(vec.into_iter().eq(0..362880));
+
+            let inv = p.inverse();
+            for i in 0..1000 {
+                assert_eq!(inv.nth(p.nth(i).unwrap()), Some(i));
+            }
+
+            assert!(RandomPermutation::with_rng_rounds(362880, 0, &mut rng).is_none());
+        }
+
+        #[test]
+        fn test_with_rng_rounds_composition() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let p = RandomPermutation::with_rng_rounds(3600, 2, &mut rng).unwrap();
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let v = vec![
+                RandomPermutation::with_rng(3600, &mut rng).unwrap(),
+                RandomPermutation::with_rng(3600, &mut rng).unwrap(),
+            ];
+            let comp = Composition::new(&v).unwrap();
+
+            assert!(p.eq_mapping(&comp));
+        }
+
+        #[test]
+        fn test_invert_in_place() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+
+            for (n, rounds) in [(1, 1), (360, 1), (5040, 3), (62208, 2)] {
+                let p = RandomPermutation::with_rng_rounds(n, rounds, &mut rng).unwrap();
+                let mut q = p.clone().into_inverted();
+                assert!(q.verify());
+                assert!(q.eq_mapping(&p.inverse()));
+                assert!(q.pairs().all(|(i, a)| q.preimage(a) == Some(i)));
+
+                let mut values = vec![0; n as usize];
+                q.fill_range(0, &mut values);
+                assert!(values.into_iter().eq(q.iter()));
+
+                q.invert_in_place();
+                assert_eq!(q, p);
+            }
+
+            let p = RandomPermutation::builder(3 << 40)
+                .rng(&mut rng)
+                .rounds(2)
+                .build()
+                .unwrap();
+            let q = p.clone().into_inverted();
+            for i in (0..3 << 40).step_by(1 << 36) {
+                assert_eq!(q.nth(p.nth(i).unwrap()), Some(i));
+            }
+        }
+
+        #[test]
+        fn test_regenerate() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+
+            for (n, rounds) in [(1, 1), (360, 1), (5040, 3), (1 << 20, 1), (23 << 33, 2)] {
+                let mut p = RandomPermutation::with_rng_rounds(n, rounds, &mut rng).unwrap();
+                let mut other = rng.clone();
+                p.regenerate(&mut rng);
+                assert!(p.verify());
+                assert_eq!(
+                    p,
+                    RandomPermutation::with_rng_rounds(n, rounds, &mut other).unwrap()
+                );
+            }
+
+            let mut p = RandomPermutation::hybrid_with_rng(1000, 2000, &mut rng).unwrap();
+            let q = p.clone();
+            p.regenerate(&mut rng);
+            assert!(p.verify());
+            assert_eq!(p.mode(), Mode::Uniform);
+            assert_ne!(p, q);
+        }
+
+        #[test]
+        fn test_nth_usize() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(300, &mut rng).unwrap();
+
+            for i in 0..300 {
+                assert_eq!(p.nth_usize(i), p.nth(i as u64).map(|a| a as usize));
+            }
+            assert_eq!(p.nth_usize(300), None);
+            assert_eq!(p.nth_usize(usize::MAX), None);
+        }
+
+        #[test]
+        fn test_new_usize() {
+            let p = RandomPermutation::new_usize(5040).unwrap();
+            let mut vec = (0..5040)
+                .map(|i| p.nth_usize(i).unwrap())
+                .collect::<Vec<_>>();
+            vec.sort();
+
+            assert!(vec.into_iter().eq(0..5040));
+            assert!(RandomPermutation::new_usize(257).is_none());
+        }
+
         #[test]
         fn test_nth_2() {
             let mut rng = Xoshiro256StarStar::seed_from_u64(0);
@@ -341,6 +1400,164 @@ mod tests {
                 ]
             );
         }
+
+        #[cfg(feature = "zeroize")]
+        #[test]
+        fn test_zeroize() {
+            use zeroize::Zeroize;
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut p = RandomPermutation::with_rng_rounds(360, 2, &mut rng).unwrap();
+            let mut round = p.rounds[0].clone();
+
+            round.zeroize();
+            assert_eq!(round.sub_perms.len(), 0);
+            assert_eq!(round.basis.product(), 360);
+
+            p.zeroize();
+            assert_eq!(p.num_rounds(), 0);
+        }
+    }
+
+    mod canonicalize {
+        use super::*;
+
+        #[test]
+        fn test_canonicalize() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(5040, &mut rng).unwrap();
+
+            let mut q = p.clone();
+            let mut sub_perms = q.rounds[0]
+                .sub_perms
+                .iter()
+                .map(|perm| perm.table().unwrap().to_vec())
+                .collect::<Vec<_>>();
+            sub_perms.insert(1, vec![0]);
+            q.rounds[0] = Round::new(sub_perms).unwrap();
+
+            assert_ne!(p, q);
+            assert!(p.eq_mapping(&q));
+
+            q.canonicalize();
+            assert_eq!(p, q);
+        }
+
+        #[test]
+        fn test_eq_mapping() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p1 = RandomPermutation::with_rng(300, &mut rng).unwrap();
+            let p2 = RandomPermutation::with_rng(300, &mut rng).unwrap();
+            let p3 = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+            let v = vec![p1.clone()];
+            let comp = Composition::new(&v).unwrap();
+
+            assert!(p1.eq_mapping(&comp));
+            assert!(!p1.eq_mapping(&p2));
+            assert!(!p1.eq_mapping(&p3));
+        }
+    }
+
+    mod hash {
+        use std::{collections::HashSet, hash::BuildHasher};
+
+        use super::*;
+
+        #[test]
+        fn test_hash_set() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p1 = RandomPermutation::with_rng(6, &mut rng).unwrap();
+
+            let set = (0..1000)
+                .map(|_| RandomPermutation::with_rng(6, &mut rng).unwrap())
+                .chain([p1.clone()])
+                .collect::<HashSet<_>>();
+
+            // There are only 2! * 3! * 2 = 24 reachable permutations, so there will be duplicates.
+            assert!(set.len() < 1000);
+            assert!(set.contains(&p1));
+        }
+
+        #[test]
+        fn test_hash_canonical() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(5040, &mut rng).unwrap();
+
+            let mut q = p.clone();
+            let mut sub_perms = q.rounds[0]
+                .sub_perms
+                .iter()
+                .map(|perm| perm.table().unwrap().to_vec())
+                .collect::<Vec<_>>();
+            sub_perms.push(vec![0]);
+            q.rounds[0] = Round::new(sub_perms).unwrap();
+
+            let state = std::hash::RandomState::new();
+            assert_eq!(state.hash_one(&p), state.hash_one(&q));
+        }
+    }
+
+    mod verify {
+        use super::*;
+
+        #[test]
+        fn test_verify() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng((1..=20).product(), &mut rng).unwrap();
+
+            assert!(p.verify());
+            assert!(p.verify_sampled(1000, &mut rng));
+        }
+
+        #[test]
+        fn test_verify_empty() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::from(UniformPermutation::try_from(vec![]).unwrap());
+
+            assert_eq!(p.num_points(), 0);
+            assert!(p.verify());
+            assert!(p.verify_sampled(10, &mut rng));
+        }
+
+        #[test]
+        fn test_verify_duplicate() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut p = RandomPermutation::with_rng(3628800, &mut rng).unwrap();
+            let perm = &mut p.rounds[0].sub_perms[0];
+            perm[0] = perm[1];
+
+            assert!(!p.verify());
+        }
+
+        #[test]
+        fn test_verify_out_of_range() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+            let sub_perms = &mut p.rounds[0].sub_perms;
+            for i in 0..sub_perms.len() {
+                sub_perms[i].fill(1000);
+            }
+
+            assert!(!p.verify());
+            assert!(!p.verify_sampled(10, &mut rng));
+        }
+
+        #[test]
+        fn test_verify_moduli() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+            p.num_points = 720;
+
+            assert!(!p.verify());
+            assert!(!p.verify_sampled(10, &mut rng));
+
+            let mut p = RandomPermutation::with_rng(36, &mut rng).unwrap();
+            p.rounds[0].sub_perms = vec![vec![0, 1, 2, 3, 4, 5], vec![5, 4, 3, 2, 1, 0]].into();
+
+            assert!(!p.verify());
+            assert!(!p.verify_sampled(10, &mut rng));
+        }
     }
 
     mod inverse {
@@ -427,18 +1644,132 @@ mod tests {
             let mut iter = p.iter();
 
             for i in 0..1000 {
-                assert_eq!(iter.nth(1000000), p.nth((i + 1) * 1000000));
+                assert_eq!(iter.nth(1000000), p.nth(i * 1000001 + 1000000));
+            }
+        }
+
+        #[test]
+        fn test_iter_from() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(123456789);
+            let p = RandomPermutation::with_rng(3113510400, &mut rng).unwrap();
+
+            for start in [0, 1, 123456789, 3113510399] {
+                let mut iter = p.iter_from(start);
+                for i in start..(start + 1000).min(3113510400) {
+                    assert_eq!(iter.next(), p.nth(i));
+                }
+            }
+
+            assert_eq!(p.iter_from(3113510399).count(), 1);
+            assert_eq!(p.iter_from(3113510400).next(), None);
+            assert_eq!(p.iter_from(u64::MAX).next(), None);
+        }
+
+        #[test]
+        fn test_iter_size_hint() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(3113510400, &mut rng).unwrap();
+
+            let mut iter = p.iter();
+            assert_eq!(iter.size_hint(), (3113510400, Some(3113510400)));
+            iter.next();
+            assert_eq!(iter.size_hint(), (3113510399, Some(3113510399)));
+            assert_eq!(iter.count(), 3113510399);
+            assert_eq!(p.iter().last(), p.nth(3113510399));
+            assert_eq!(p.iter_from(3113510400).last(), None);
+
+            let mut iter = p.iter_from(3113510399);
+            assert_eq!(iter.next(), p.nth(3113510399));
+            assert_eq!(iter.size_hint(), (0, Some(0)));
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.size_hint(), (0, Some(0)));
+
+            let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+            assert_eq!(p.iter().collect::<Vec<_>>().capacity(), 360);
+        }
+
+        #[test]
+        fn test_iter_nth() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+            let all = p.iter().collect::<Vec<_>>();
+
+            let mut iter = p.iter();
+            assert_eq!(iter.nth(1), Some(all[1]));
+            assert_eq!(iter.nth(4), Some(all[6]));
+            assert_eq!(iter.next(), Some(all[7]));
+            assert_eq!(iter.nth(3591), Some(all[3599]));
+            assert_eq!(iter.next(), None);
+
+            let mut iter = p.iter();
+            assert_eq!(iter.nth(usize::MAX), None);
+            assert_eq!(iter.nth(usize::MAX), None);
+            assert_eq!(iter.next(), None);
+
+            let mut iter = p.iter_from(3599);
+            assert_eq!(iter.nth(usize::MAX), None);
+            assert_eq!(iter.size_hint(), (0, Some(0)));
+
+            assert!(p.iter().skip(100).eq(all[100..].iter().copied()));
+            assert!(p.iter().step_by(7).eq(all.iter().copied().step_by(7)));
+            assert_eq!(p.iter().skip(3600).count(), 0);
+        }
+
+        #[test]
+        fn test_into_iter() {
+            fn shuffled(n: u64, rng: &mut Xoshiro256StarStar) -> impl Iterator<Item = u64> {
+                RandomPermutation::with_rng(n, rng).unwrap().into_iter()
+            }
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let iter = shuffled(360, &mut rng);
+            assert_eq!(iter.size_hint(), (360, Some(360)));
+            assert!(iter.eq(p.iter()));
+        }
+
+        #[test]
+        fn test_pairs() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+            assert_eq!(p.pairs().count(), 360);
+            for (i, a) in p.pairs() {
+                assert_eq!(p.nth(i), Some(a));
             }
         }
+
+        #[test]
+        fn test_page() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(1000, &mut rng).unwrap();
+            let all = p.iter().collect::<Vec<_>>();
+
+            assert_eq!(p.page(0, 30), all[..30]);
+            assert_eq!(p.page(3, 30), all[90..120]);
+            assert_eq!(p.page(33, 30), all[990..]);
+            assert_eq!(p.page(34, 30), [] as [u64; 0]);
+            assert_eq!(p.page(0, 0), [] as [u64; 0]);
+            assert_eq!(p.page(0, 5000), all);
+            assert_eq!(p.page(u64::MAX, u64::MAX), [] as [u64; 0]);
+
+            let pages = (0..34).flat_map(|i| p.page(i, 30)).collect::<Vec<_>>();
+            assert_eq!(pages, all);
+        }
     }
 
     mod composition {
+        use std::borrow::Cow;
+
         use super::*;
 
         #[test]
         fn test_new_1() {
-            let comp = Composition::new(&[]);
-            assert!(comp.is_none());
+            let comp = Composition::<RandomPermutation>::new([]);
+            assert_eq!(comp, Err(CompositionError::Empty));
         }
 
         #[test]
@@ -450,7 +1781,29 @@ mod tests {
             let v = vec![p1, p2];
             let comp = Composition::new(&v);
 
-            assert!(comp.is_none());
+            assert_eq!(
+                comp,
+                Err(CompositionError::SizeMismatch {
+                    index: 1,
+                    expected: 300,
+                    found: 400
+                })
+            );
+        }
+
+        #[test]
+        fn test_preimage() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(7777778);
+            let p1 = RandomPermutation::with_rng(300, &mut rng).unwrap();
+            let p2 = RandomPermutation::with_rng(300, &mut rng).unwrap();
+
+            let v = vec![p1, p2];
+            let comp = Composition::new(&v).unwrap();
+
+            for i in 0..300 {
+                assert_eq!(comp.preimage(comp.nth(i).unwrap()), Some(i));
+            }
+            assert_eq!(comp.preimage(300), None);
         }
 
         #[test]
@@ -466,5 +1819,98 @@ mod tests {
                 assert_eq!(comp.nth(i), p2.nth(p1.nth(i).unwrap()));
             }
         }
+
+        #[test]
+        fn test_compose_assign() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(7777777);
+            let perms = (0..5)
+                .map(|_| RandomPermutation::with_rng_rounds(3600, 2, &mut rng).unwrap())
+                .collect::<Vec<_>>();
+            let comp = Composition::new(&perms).unwrap();
+
+            let mut p = perms[0].clone();
+            for q in &perms[1..] {
+                p.compose_assign(q).unwrap();
+                assert_eq!(p.num_rounds(), 1);
+            }
+            assert!(p.verify());
+            assert!(p.eq_mapping(&comp));
+
+            let philox = PhiloxPermutation::with_key(3600, 0).unwrap();
+            let mut q = p.clone();
+            q.compose_assign(&philox).unwrap();
+            assert!(q
+                .pairs()
+                .all(|(i, a)| philox.nth(p.nth(i).unwrap()) == Some(a)));
+
+            assert_eq!(
+                p.compose_assign(&RandomPermutation::with_rng(360, &mut rng).unwrap()),
+                Err(CompositionError::SizeMismatch {
+                    index: 1,
+                    expected: 3600,
+                    found: 360
+                })
+            );
+
+            let mut empty = RandomPermutation::from(UniformPermutation::try_from(vec![]).unwrap());
+            assert_eq!(empty.compose_assign(&empty.clone()), Ok(()));
+
+            let n = 2 * MAX_EXPLICIT_POINTS;
+            let mut large = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            let before = large.clone();
+            assert_eq!(
+                large.compose_assign(&PhiloxPermutation::with_key(n, 0).unwrap()),
+                Err(CompositionError::TooLarge { num_points: n })
+            );
+            assert_eq!(large, before);
+        }
+
+        #[test]
+        fn test_precompose() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(7777777);
+
+            // A prime power has a single table in each round, so everything collapses into one.
+            let perms = (0..4)
+                .map(|_| RandomPermutation::with_rng_rounds(3125, 2, &mut rng).unwrap())
+                .collect::<Vec<_>>();
+            let comp = Composition::new(&perms).unwrap();
+            let p = comp.precompose();
+            assert_eq!(p.num_rounds(), 1);
+            assert!(p.verify());
+            assert!(p.eq_mapping(&comp));
+
+            let perms = [
+                RandomPermutation::with_rng_rounds(3600, 2, &mut rng).unwrap(),
+                UniformPermutation::with_rng(3600, &mut rng).into(),
+                UniformPermutation::with_rng(3600, &mut rng).into(),
+                RandomPermutation::with_rng(3600, &mut rng).unwrap(),
+            ];
+            let comp = Composition::new(perms.iter().map(Cow::Borrowed)).unwrap();
+            let p = comp.precompose();
+            assert_eq!(p.num_rounds(), 4);
+            assert!(p.eq_mapping(&comp));
+
+            let p = Composition::new([RandomPermutation::with_rng(1, &mut rng).unwrap()])
+                .unwrap()
+                .precompose();
+            assert!(p.iter().eq([0]));
+        }
+
+        #[test]
+        fn test_mixed_ownership() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(7777777);
+            let p1 = RandomPermutation::with_rng(300, &mut rng).unwrap();
+            let p2 = RandomPermutation::with_rng(300, &mut rng).unwrap();
+
+            let owned = Composition::new([p1.clone(), p2.clone()]).unwrap();
+            let mixed = Composition::new([Cow::Borrowed(&p1), Cow::Owned(p2.clone())]).unwrap();
+            let iter = Composition::new([&p1, &p2].into_iter().cycle().take(3)).unwrap();
+
+            assert!(owned.eq_mapping(&mixed));
+            assert!(iter
+                .pairs()
+                .all(|(i, a)| p1.nth(owned.nth(i).unwrap()) == Some(a)));
+            assert_eq!(owned.into_inner(), vec![p1, p2]);
+        }
     }
 }
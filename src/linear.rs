@@ -0,0 +1,193 @@
+use rand::{Rng, RngCore};
+
+use crate::{rng::SplitMix64, Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+/// A random affine permutation `x ↦ Ax ⊕ c` of `0..2^k`, where the bits of `x` are a vector over
+/// GF(2), `A` is an invertible `k × k` bit matrix, and `c` is a constant.
+///
+/// The matrix is uniformly distributed over all invertible matrices. This is a cheap mixer that
+/// only stores `2k + 1` words, and the inverse `x ↦ A⁻¹(x ⊕ c)` is just as fast, since `A⁻¹` is
+/// computed up front. Being linear, it is far from uniform over all permutations: e.g. the images
+/// of any `x`, `y` and `x ⊕ y` determine the image of `0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LinearPermutation {
+    // Column `i` of `A`, i.e. the image of `1 << i` before adding `c`.
+    columns: Vec<u64>,
+    inverse: Vec<u64>,
+    constant: u64,
+}
+
+impl LinearPermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::with_key(n, rng.next_u64())
+    }
+
+    /// Returns `None` if `n` isn't a power of two.
+    #[must_use]
+    pub fn with_key(n: u64, key: u64) -> Option<Self> {
+        if !n.is_power_of_two() {
+            return None;
+        }
+
+        let bits = n.trailing_zeros();
+        let mask = n - 1;
+        let mut rng = SplitMix64(key);
+        let constant = rng.next_u64() & mask;
+
+        // About 29% of random matrices are invertible, so this takes a few attempts on average.
+        loop {
+            let columns = (0..bits).map(|_| rng.next_u64() & mask).collect();
+            if let Some(perm) = Self::from_columns(columns, constant) {
+                return Some(perm);
+            }
+        }
+    }
+
+    /// Returns the permutation `x ↦ Ax ⊕ constant` of `0..2^k`, where `k = columns.len()` and
+    /// column `i` of `A` is `columns[i]`, or `None` if `A` isn't invertible or the columns or
+    /// `constant` don't fit in `k` bits. Use a zero constant for a linear permutation.
+    #[must_use]
+    pub fn from_columns(columns: Vec<u64>, constant: u64) -> Option<Self> {
+        let bits = u32::try_from(columns.len()).ok().filter(|&k| k < 64)?;
+        if columns.iter().chain([&constant]).any(|&c| c >> bits != 0) {
+            return None;
+        }
+
+        // Column operations that reduce `A` to the identity turn the identity into `A⁻¹`.
+        let mut a = columns.clone();
+        let mut inverse = (0..bits).map(|i| 1 << i).collect::<Vec<u64>>();
+        for r in 0..bits as usize {
+            let pivot = (r..a.len()).find(|&j| a[j] >> r & 1 == 1)?;
+            a.swap(r, pivot);
+            inverse.swap(r, pivot);
+
+            for j in 0..a.len() {
+                if j != r && a[j] >> r & 1 == 1 {
+                    a[j] ^= a[r];
+                    inverse[j] ^= inverse[r];
+                }
+            }
+        }
+
+        Some(Self {
+            columns,
+            inverse,
+            constant,
+        })
+    }
+
+    #[must_use]
+    pub fn columns(&self) -> &[u64] {
+        &self.columns
+    }
+
+    #[must_use]
+    pub fn constant(&self) -> u64 {
+        self.constant
+    }
+
+    fn apply(columns: &[u64], x: u64) -> u64 {
+        columns
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| x >> i & 1 == 1)
+            .fold(0, |y, (_, &c)| y ^ c)
+    }
+}
+
+impl Permutation for LinearPermutation {
+    fn num_points(&self) -> u64 {
+        1 << self.columns.len()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        (n < self.num_points()).then(|| Self::apply(&self.columns, n) ^ self.constant)
+    }
+}
+
+impl InvertiblePermutation for LinearPermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        (x < self.num_points()).then(|| Self::apply(&self.inverse, x ^ self.constant))
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_with_rng() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        assert_eq!(LinearPermutation::with_rng(0, &mut rng), None);
+        assert_eq!(LinearPermutation::with_rng(12, &mut rng), None);
+
+        for n in [1, 2, 64, 1 << 12] {
+            let p = LinearPermutation::with_rng(n, &mut rng).unwrap();
+            assert_eq!(p.num_points(), n);
+
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort_unstable();
+            assert!(values.into_iter().eq(0..n));
+
+            for i in 0..n {
+                assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+            }
+            assert_eq!(p.nth(n), None);
+            assert_eq!(p.preimage(n), None);
+        }
+    }
+
+    #[test]
+    fn test_with_key() {
+        let p = LinearPermutation::with_key(1 << 63, 5).unwrap();
+        assert_eq!(p, LinearPermutation::with_key(1 << 63, 5).unwrap());
+        assert_ne!(p, LinearPermutation::with_key(1 << 63, 6).unwrap());
+
+        for i in [0, 1, 1234567898765, (1 << 63) - 1] {
+            assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_affine() {
+        let p = LinearPermutation::with_key(1 << 20, 7).unwrap();
+        let c = p.constant();
+        for (x, y) in [(3, 5), (12345, 67890), (1 << 19, 1)] {
+            let [px, py, pxy] = [x, y, x ^ y].map(|i| p.nth(i).unwrap());
+            assert_eq!(px ^ py ^ pxy, c);
+        }
+    }
+
+    #[test]
+    fn test_from_columns() {
+        // The matrix [[1, 1], [0, 1]], so `(x0, x1) ↦ (x0 ⊕ x1, x1)`.
+        let p = LinearPermutation::from_columns(vec![0b01, 0b11], 0).unwrap();
+        assert_eq!(p.iter().collect::<Vec<_>>(), [0, 1, 3, 2]);
+
+        let p = LinearPermutation::from_columns(vec![0b01, 0b11], 0b10).unwrap();
+        assert_eq!(p.iter().collect::<Vec<_>>(), [2, 3, 1, 0]);
+
+        assert_eq!(LinearPermutation::from_columns(vec![0b01, 0b01], 0), None);
+        assert_eq!(
+            LinearPermutation::from_columns(vec![0b01, 0b10], 0b100),
+            None
+        );
+        assert_eq!(LinearPermutation::from_columns(vec![0b101], 0), None);
+        assert_eq!(LinearPermutation::from_columns(vec![1; 64], 0), None);
+        assert!(LinearPermutation::from_columns(vec![], 0).is_some());
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashMap, hash::Hash};
+
+use rand::Rng;
+
+use crate::{rng::SplitMix64, InvertiblePermutation, Permutation, RandomPermutation};
+
+/// A random one-to-one pairing of the items of two slices of the same length.
+///
+/// `left[i]` is paired with `right[σ(i)]` for a random permutation `σ` of the indices, and items
+/// can be looked up in either direction. The items of each slice must be distinct.
+#[derive(Debug, Clone)]
+pub struct SliceBijection<'a, T, U = T> {
+    left: &'a [T],
+    right: &'a [U],
+    perm: RandomPermutation,
+    left_indices: HashMap<&'a T, usize>,
+    right_indices: HashMap<&'a U, usize>,
+}
+
+impl<'a, T: Eq + Hash, U: Eq + Hash> SliceBijection<'a, T, U> {
+    #[must_use]
+    pub fn new(left: &'a [T], right: &'a [U]) -> Option<Self> {
+        Self::with_rng(left, right, &mut rand::thread_rng())
+    }
+
+    /// Uses a uniformly random table, as in `RandomPermutation::hybrid_with_rng`, which takes no
+    /// more memory than the slices themselves and supports any length.
+    ///
+    /// Returns `None` if the slices are empty, or for any of the reasons that `from_permutation`
+    /// does.
+    pub fn with_rng<R: Rng + ?Sized>(left: &'a [T], right: &'a [U], rng: &mut R) -> Option<Self> {
+        let perm = RandomPermutation::hybrid_with_rng(left.len() as u64, u64::MAX, rng)?;
+        Self::from_permutation(left, right, perm)
+    }
+
+    /// Generates the pairing from `key`, so that the same slices and key always give the same
+    /// pairing.
+    #[must_use]
+    pub fn with_key(left: &'a [T], right: &'a [U], key: u64) -> Option<Self> {
+        Self::with_rng(left, right, &mut SplitMix64(key))
+    }
+
+    /// Pairs `left[i]` with `right[perm.nth(i)]`. Returns `None` if the slices have different
+    /// lengths, if `perm` has the wrong number of points, or if either slice contains duplicate
+    /// items.
+    #[must_use]
+    pub fn from_permutation(
+        left: &'a [T],
+        right: &'a [U],
+        perm: RandomPermutation,
+    ) -> Option<Self> {
+        if left.len() != right.len() || perm.num_points() != left.len() as u64 {
+            return None;
+        }
+
+        Some(Self {
+            left,
+            right,
+            perm,
+            left_indices: indices(left)?,
+            right_indices: indices(right)?,
+        })
+    }
+
+    /// Returns the item of `right` paired with `item`, or `None` if `item` isn't in `left`.
+    #[must_use]
+    pub fn map(&self, item: &T) -> Option<&'a U> {
+        let i = *self.left_indices.get(item)?;
+        Some(&self.right[self.map_index(i)?])
+    }
+
+    /// Returns the item of `left` paired with `item`, or `None` if `item` isn't in `right`.
+    #[must_use]
+    pub fn map_back(&self, item: &U) -> Option<&'a T> {
+        let j = *self.right_indices.get(item)?;
+        Some(&self.left[self.map_index_back(j)?])
+    }
+}
+
+impl<'a, T, U> SliceBijection<'a, T, U> {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    /// Returns the index into `right` of the item paired with `left[i]`.
+    #[must_use]
+    pub fn map_index(&self, i: usize) -> Option<usize> {
+        self.perm.nth_usize(i)
+    }
+
+    /// Returns the index into `left` of the item paired with `right[j]`.
+    #[must_use]
+    pub fn map_index_back(&self, j: usize) -> Option<usize> {
+        let j = u64::try_from(j).ok()?;
+        Some(self.perm.preimage(j)? as usize)
+    }
+
+    /// Returns an iterator over the pairs, in the order of `left`.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a T, &'a U)> + '_ {
+        let (left, right) = (self.left, self.right);
+        self.perm
+            .iter()
+            .enumerate()
+            .map(move |(i, j)| (&left[i], &right[j as usize]))
+    }
+
+    /// Returns the permutation of the indices.
+    #[must_use]
+    pub fn permutation(&self) -> &RandomPermutation {
+        &self.perm
+    }
+}
+
+fn indices<T: Eq + Hash>(items: &[T]) -> Option<HashMap<&T, usize>> {
+    let mut indices = HashMap::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        if indices.insert(item, i).is_some() {
+            return None;
+        }
+    }
+    Some(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_pairing() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let left = (0..360).map(|i| format!("l{i}")).collect::<Vec<_>>();
+        let right = (0..360u32).map(|i| i * 7).collect::<Vec<_>>();
+        let p = SliceBijection::with_rng(&left, &right, &mut rng).unwrap();
+        assert_eq!(p.len(), 360);
+
+        let mut paired = p.iter().map(|(_, &b)| b).collect::<Vec<_>>();
+        paired.sort();
+        assert_eq!(paired, right);
+
+        for (a, b) in p.iter() {
+            assert_eq!(p.map(a), Some(b));
+            assert_eq!(p.map_back(b), Some(a));
+        }
+
+        assert_eq!(p.map(&"l360".to_string()), None);
+        assert_eq!(p.map_back(&1), None);
+        assert_eq!(p.map_index(360), None);
+        assert_eq!(p.map_index_back(360), None);
+    }
+
+    #[test]
+    fn test_prime_length() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let left = (0..1009).collect::<Vec<_>>();
+        let right = (0..1009).map(|i| i * 3).collect::<Vec<_>>();
+        let p = SliceBijection::with_rng(&left, &right, &mut rng).unwrap();
+        assert_eq!(p.len(), 1009);
+
+        let mut paired = p.iter().map(|(_, &b)| b).collect::<Vec<_>>();
+        paired.sort();
+        assert_eq!(paired, right);
+        for (a, b) in p.iter() {
+            assert_eq!(p.map_back(b), Some(a));
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let items = (0..100).collect::<Vec<_>>();
+        let p = SliceBijection::with_key(&items, &items, 42).unwrap();
+        let q = SliceBijection::with_key(&items, &items, 42).unwrap();
+        assert!(p.iter().eq(q.iter()));
+
+        let q = SliceBijection::with_key(&items, &items, 43).unwrap();
+        assert!(!p.iter().eq(q.iter()));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let items = [1, 2, 3, 4];
+        assert!(SliceBijection::with_rng(&items, &items[..3], &mut rng).is_none());
+        assert!(SliceBijection::with_rng(&items, &[1, 2, 3, 3], &mut rng).is_none());
+        assert!(SliceBijection::<u8>::with_rng(&[], &[], &mut rng).is_none());
+
+        let perm = RandomPermutation::with_rng(5, &mut rng).unwrap();
+        assert!(SliceBijection::from_permutation(&items, &items, perm).is_none());
+    }
+}
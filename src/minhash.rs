@@ -0,0 +1,226 @@
+use rand::Rng;
+
+use crate::{rng::SplitMix64, KWisePermutation, Permutation};
+
+/// The independence of the default permutations. By a theorem of Indyk, `O(log(1/ε))`-wise
+/// independence is enough for ε-approximate min-wise independence.
+const DEFAULT_K: usize = 8;
+
+/// MinHash signatures of sets of ids in `0..n`, for estimating Jaccard similarity.
+///
+/// Each of the `m` hashes is the smallest image of the set under a different keyed permutation of
+/// `0..n`. Two sets get the same value for a hash with probability approximately equal to their
+/// Jaccard similarity, so the fraction of equal values in their signatures estimates it with
+/// standard deviation at most `1 / (2 * sqrt(m))`.
+///
+/// By default, the permutations are `KWisePermutation`s with `k = 8`. With `new` or `with_rng`,
+/// their coefficients are drawn from the RNG, so they are approximately min-wise independent.
+/// `with_key` derives them from a single 64-bit key instead, so its guarantee is only heuristic.
+/// Use `from_permutations` to use other permutations, e.g. `PhiloxPermutation`s, which are faster
+/// but have no stated independence guarantee.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MinHasher<P = KWisePermutation> {
+    perms: Vec<P>,
+}
+
+impl MinHasher {
+    /// Returns `None` if `n == 0` or `num_hashes == 0`.
+    #[must_use]
+    pub fn new(n: u64, num_hashes: usize) -> Option<Self> {
+        Self::with_rng(n, num_hashes, &mut rand::thread_rng())
+    }
+
+    /// Generates each permutation with `KWisePermutation::with_rng`. Returns `None` if `n == 0`
+    /// or `num_hashes == 0`.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, num_hashes: usize, rng: &mut R) -> Option<Self> {
+        let perms = (0..num_hashes)
+            .map(|_| KWisePermutation::with_rng(n, DEFAULT_K, rng))
+            .collect::<Option<_>>()?;
+        Self::from_permutations(perms)
+    }
+
+    /// Generates `num_hashes` permutations of `0..n` from `key`, e.g. `n = 1 << 32` for `u32` ids.
+    /// The first `m` hashes only depend on `n` and `key`, so signatures of different lengths
+    /// generated from the same key can be compared on their common prefix.
+    ///
+    /// Each permutation comes from `KWisePermutation::with_key`, so like there, the min-wise
+    /// independence is only heuristic. Returns `None` if `n == 0` or `num_hashes == 0`.
+    #[must_use]
+    pub fn with_key(n: u64, num_hashes: usize, key: u64) -> Option<Self> {
+        let perms = (0..num_hashes as u64)
+            .map(|i| {
+                let key = SplitMix64::mix(SplitMix64::mix(key ^ n) ^ i);
+                KWisePermutation::with_key(n, DEFAULT_K, key)
+            })
+            .collect::<Option<_>>()?;
+        Self::from_permutations(perms)
+    }
+}
+
+impl<P: Permutation> MinHasher<P> {
+    /// Returns `None` if `perms` is empty, or if the permutations have different numbers of
+    /// points.
+    #[must_use]
+    pub fn from_permutations(perms: Vec<P>) -> Option<Self> {
+        let n = perms.first()?.num_points();
+        if perms.iter().any(|p| p.num_points() != n) {
+            return None;
+        }
+
+        Some(Self { perms })
+    }
+
+    /// The size of the universe of ids.
+    #[must_use]
+    pub fn num_points(&self) -> u64 {
+        self.perms[0].num_points()
+    }
+
+    /// The length `m` of each signature.
+    #[must_use]
+    pub fn num_hashes(&self) -> usize {
+        self.perms.len()
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> Vec<P> {
+        self.perms
+    }
+
+    /// Returns the signature of a set of ids, or `None` if the set is empty. Repeated ids are
+    /// allowed, and have no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any id is out of range.
+    pub fn min_hash<I: IntoIterator<Item = u64>>(&self, ids: I) -> Option<Vec<u64>> {
+        let mut signature = vec![u64::MAX; self.perms.len()];
+        let mut empty = true;
+
+        for id in ids {
+            for (min, perm) in signature.iter_mut().zip(&self.perms) {
+                let a = perm.nth(id).unwrap_or_else(|| {
+                    panic!("id {id} is out of range for {} points", perm.num_points())
+                });
+                *min = (*min).min(a);
+            }
+            empty = false;
+        }
+
+        (!empty).then_some(signature)
+    }
+}
+
+/// Estimates the Jaccard similarity of two sets from their signatures, as the fraction of hashes
+/// that are equal. If the signatures have different lengths, only their common prefix is used.
+///
+/// # Panics
+///
+/// Panics if either signature is empty.
+#[must_use]
+pub fn estimate_similarity(a: &[u64], b: &[u64]) -> f64 {
+    let len = a.len().min(b.len());
+    assert_ne!(len, 0, "signatures must not be empty");
+
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / len as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::PhiloxPermutation;
+
+    #[test]
+    fn test_min_hash() {
+        let hasher = MinHasher::with_key(1000, 4, 0).unwrap();
+        assert_eq!(hasher.num_points(), 1000);
+        assert_eq!(hasher.num_hashes(), 4);
+        assert_eq!(hasher.min_hash([]), None);
+
+        let signature = hasher.min_hash([5, 17, 999, 17]).unwrap();
+        for (&min, perm) in signature.iter().zip(&hasher.clone().into_inner()) {
+            assert_eq!(
+                min,
+                [5, 17, 999]
+                    .map(|i| perm.nth(i).unwrap())
+                    .into_iter()
+                    .min()
+                    .unwrap()
+            );
+        }
+        assert_eq!(hasher.min_hash([999, 17, 5]), Some(signature));
+    }
+
+    #[test]
+    fn test_with_rng() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut other = rng.clone();
+
+        let hasher = MinHasher::with_rng(1000, 3, &mut rng).unwrap();
+        for perm in hasher.into_inner() {
+            assert_eq!(
+                perm,
+                KWisePermutation::with_rng(1000, 8, &mut other).unwrap()
+            );
+        }
+
+        assert_eq!(MinHasher::with_rng(0, 3, &mut rng), None);
+        assert_eq!(MinHasher::with_rng(1000, 0, &mut rng), None);
+        assert_eq!(MinHasher::with_key(1000, 0, 0), None);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let short = MinHasher::with_key(1 << 32, 8, 7).unwrap();
+        let long = MinHasher::with_key(1 << 32, 32, 7).unwrap();
+        let ids = [1, 2, 3, 1 << 31];
+        assert_eq!(
+            short.min_hash(ids).unwrap(),
+            long.min_hash(ids).unwrap()[..8]
+        );
+    }
+
+    #[test]
+    fn test_similarity() {
+        let hasher = MinHasher::with_key(1 << 20, 400, 1).unwrap();
+
+        // Jaccard similarity 1/3.
+        let a = hasher.min_hash(0..2000).unwrap();
+        let b = hasher.min_hash(1000..3000).unwrap();
+        assert_eq!(estimate_similarity(&a, &a), 1.0);
+        assert!((estimate_similarity(&a, &b) - 1.0 / 3.0).abs() < 0.1);
+
+        let c = hasher.min_hash(5000..6000).unwrap();
+        assert!(estimate_similarity(&a, &c) < 0.05);
+    }
+
+    #[test]
+    fn test_from_permutations() {
+        let perms = (0..3)
+            .map(|key| PhiloxPermutation::with_key(100, key).unwrap())
+            .collect::<Vec<_>>();
+        let hasher = MinHasher::from_permutations(perms.clone()).unwrap();
+        assert_eq!(
+            hasher.min_hash([42]),
+            Some(perms.iter().map(|p| p.nth(42).unwrap()).collect())
+        );
+
+        assert_eq!(
+            MinHasher::<PhiloxPermutation>::from_permutations(Vec::new()),
+            None
+        );
+        let mixed = vec![perms[0], PhiloxPermutation::with_key(101, 0).unwrap()];
+        assert_eq!(MinHasher::from_permutations(mixed), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_out_of_range() {
+        let hasher = MinHasher::with_key(1000, 4, 0).unwrap();
+        let _ = hasher.min_hash([1000]);
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    os::fd::AsRawFd,
+    path::Path,
+    ptr::{self, NonNull},
+    slice,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    bytes::{self, FromBytesError, Reader, VERSION},
+    round::SubPerms,
+    RandomPermutation,
+};
+
+const MAGIC: &[u8; 8] = b"RPCRMMAP";
+
+// The header is the magic, the `to_bytes` version used for the rounds, and the number of table
+// entries, each padded to 8 bytes so that the tables are aligned. The tables follow the header,
+// and the rounds (in the `to_bytes` format, without the table entries) follow the tables.
+const HEADER_WORDS: usize = 3;
+
+// Distinguishes the temporary files of concurrent calls to `write_mappable` in one process.
+static WRITE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A read-only shared mapping of a whole file.
+#[derive(Debug)]
+pub(crate) struct MappedFile {
+    ptr: NonNull<libc::c_void>,
+    len: usize,
+}
+
+// The mapping is read-only, so it can be shared between threads like a `&[u8]`.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped.
+    unsafe fn map(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file is too large to map"))?;
+        if len < HEADER_WORDS * 8 {
+            return Err(FromBytesError::UnexpectedEnd.into());
+        }
+
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: NonNull::new(ptr).ok_or_else(io::Error::last_os_error)?,
+            len,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr().cast(), self.len) }
+    }
+
+    /// The file as little-endian words, ignoring any partial word at the end. The mapping is
+    /// page-aligned, so this doesn't need a copy.
+    pub(crate) fn words(&self) -> &[u64] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr().cast(), self.len / 8) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr(), self.len);
+        }
+    }
+}
+
+impl RandomPermutation {
+    /// Writes the permutation to a file that `map_file` can use without copying the tables into
+    /// memory. The table entries are stored as 8-byte little-endian integers, so the file is
+    /// usually larger than the output of `to_bytes`.
+    ///
+    /// As with `save_to`, the file is written to a temporary file next to `path`, which then
+    /// replaces `path`. A process that has mapped the old file keeps its mapping of the old
+    /// contents, instead of seeing the file change or shrink under it.
+    pub fn write_mappable<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let id = WRITE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut temp = OsString::from(path);
+        temp.push(format!(".{}-{id}.tmp", std::process::id()));
+
+        let file = File::options().write(true).create_new(true).open(&temp)?;
+        let written = self
+            .write_mappable_to(BufWriter::new(file))
+            .and_then(|file| file.sync_all());
+
+        let result = written.and_then(|()| fs::rename(&temp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    // Writes the contents of a mappable file, and returns the file once it has been flushed.
+    fn write_mappable_to(&self, mut out: BufWriter<File>) -> io::Result<File> {
+        let num_words = self
+            .rounds
+            .iter()
+            .map(|round| round.sub_perms.buffer().len() as u64)
+            .sum::<u64>();
+        out.write_all(MAGIC)?;
+        out.write_all(&(VERSION as u64).to_le_bytes())?;
+        out.write_all(&num_words.to_le_bytes())?;
+
+        for round in &self.rounds {
+            for &a in round.sub_perms.buffer() {
+                out.write_all(&a.to_le_bytes())?;
+            }
+        }
+
+        let mut rounds = Vec::new();
+        bytes::write_varint(&mut rounds, self.num_points);
+        bytes::write_varint(&mut rounds, self.rounds.len() as u64);
+        for round in &self.rounds {
+            bytes::write_round(&mut rounds, round, |_, _| {});
+        }
+        out.write_all(&rounds)?;
+
+        out.into_inner().map_err(io::IntoInnerError::into_error)
+    }
+
+    /// Maps a file written by `write_mappable`, so that the tables are read from the page cache
+    /// instead of being copied into memory. Any number of processes can map the same file and
+    /// share a single copy of the tables. Methods that modify the tables, such as
+    /// `invert_in_place`, copy them into memory first.
+    ///
+    /// Only the structure of the file is checked, since checking the tables would read all of
+    /// them. Call `verify` to check the tables too.
+    ///
+    /// Returns an error with `ErrorKind::InvalidData` if the file isn't a valid permutation, and
+    /// always fails with `ErrorKind::Unsupported` on big-endian targets.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the permutation, or any clone of it,
+    /// exists. Files written by `write_mappable` are never modified in place, since it replaces
+    /// the file instead.
+    ///
+    /// Each table in the file must also be a permutation, as in a file written by
+    /// `write_mappable`. Otherwise, methods of the permutation may panic, loop forever or return
+    /// wrong results. If the file may be corrupted, call `verify` before using it.
+    pub unsafe fn map_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if cfg!(target_endian = "big") {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+
+        let file = Arc::new(MappedFile::map(&File::open(path)?)?);
+        let words = file.words();
+
+        if file.bytes()[..8] != *MAGIC {
+            return Err(FromBytesError::InvalidMagic.into());
+        }
+
+        if words[1] != VERSION as u64 {
+            return Err(
+                FromBytesError::UnsupportedVersion(words[1].min(u8::MAX as u64) as u8).into(),
+            );
+        }
+
+        let tables_end = usize::try_from(words[2])
+            .ok()
+            .and_then(|num_words| HEADER_WORDS.checked_add(num_words))
+            .filter(|&end| end <= words.len())
+            .ok_or(FromBytesError::UnexpectedEnd)?;
+
+        let mut reader = Reader {
+            bytes: &file.bytes()[tables_end * 8..],
+        };
+        let num_points = reader.varint()?;
+        let num_rounds = reader.varint()?;
+
+        let read_table: bytes::ReadTable = |_, sub_perms, pk| {
+            usize::try_from(pk)
+                .ok()
+                .and_then(|pk| sub_perms.push_mapped(pk))
+                .ok_or(FromBytesError::UnexpectedEnd)
+        };
+
+        let mut rounds = Vec::new();
+        let mut start = HEADER_WORDS;
+        for _ in 0..num_rounds {
+            let sub_perms = SubPerms::mapped(file.clone(), start);
+            let round = reader.round(sub_perms, read_table)?;
+            start += round.sub_perms.buffer().len();
+            rounds.push(round);
+        }
+
+        if start != tables_end {
+            return Err(FromBytesError::InvalidTables.into());
+        }
+        if !reader.bytes.is_empty() {
+            return Err(FromBytesError::TrailingBytes.into());
+        }
+        if !rounds.iter().all(|round| round.verify_moduli(num_points)) {
+            return Err(FromBytesError::InvalidTables.into());
+        }
+
+        Ok(Self { num_points, rounds })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{InvertiblePermutation, Permutation};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("randperm-crt-{}-{name}", std::process::id()))
+    }
+
+    fn round_trip(p: &RandomPermutation, name: &str) -> RandomPermutation {
+        let path = temp_path(name);
+        p.write_mappable(&path).unwrap();
+        let q = unsafe { RandomPermutation::map_file(&path) }.unwrap();
+        fs::remove_file(&path).unwrap();
+        q
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 360, 62208, 1 << 20, 1000000] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            let q = round_trip(&p, "round-trip");
+            assert!(q.verify());
+            assert!(p.iter().eq(q.iter()));
+            assert_eq!(q.to_bytes(), p.to_bytes());
+        }
+    }
+
+    #[test]
+    fn test_inverted_and_implicit() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        let p = RandomPermutation::with_rng(62208, &mut rng)
+            .unwrap()
+            .into_inverted();
+        let q = round_trip(&p, "inverted");
+        assert!(p.iter().eq(q.iter()));
+
+        let p = RandomPermutation::builder(3600)
+            .implicit_threshold(10)
+            .rng(rng)
+            .build()
+            .unwrap();
+        let q = round_trip(&p, "implicit");
+        assert!(q.verify());
+        assert!(p.iter().eq(q.iter()));
+    }
+
+    #[test]
+    fn test_modify_copies() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let mut q = round_trip(&p, "modify");
+
+        q.invert_in_place();
+        assert!(q.verify());
+        for n in 0..360 {
+            assert_eq!(q.nth(n), p.preimage(n));
+        }
+    }
+
+    #[test]
+    fn test_overwrite_mapped() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let p = RandomPermutation::with_rng(1 << 20, &mut rng).unwrap();
+        let path = temp_path("overwrite");
+        p.write_mappable(&path).unwrap();
+        let q = unsafe { RandomPermutation::map_file(&path) }.unwrap();
+
+        // Replacing the file leaves the existing mapping of the old one intact.
+        let r = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        r.write_mappable(&path).unwrap();
+        assert!(q.verify());
+        assert!(p.iter().eq(q.iter()));
+
+        let s = unsafe { RandomPermutation::map_file(&path) }.unwrap();
+        assert!(r.iter().eq(s.iter()));
+        fs::remove_file(&path).unwrap();
+
+        let temp = env::temp_dir()
+            .read_dir()
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| {
+                name.starts_with(&format!("randperm-crt-{}-overwrite.", std::process::id()))
+            })
+            .count();
+        assert_eq!(temp, 0);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        let path = temp_path("invalid");
+        p.write_mappable(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let map = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            unsafe { RandomPermutation::map_file(&path) }
+                .unwrap_err()
+                .kind()
+        };
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] ^= 1;
+        assert_eq!(map(&bad_magic), io::ErrorKind::InvalidData);
+
+        let mut bad_version = bytes.clone();
+        bad_version[8] = 2;
+        assert_eq!(map(&bad_version), io::ErrorKind::InvalidData);
+
+        let mut bad_len = bytes.clone();
+        bad_len[16] += 1;
+        assert_eq!(map(&bad_len), io::ErrorKind::InvalidData);
+
+        assert_eq!(map(&bytes[..bytes.len() - 1]), io::ErrorKind::InvalidData);
+        assert_eq!(
+            map(&[bytes.as_slice(), &[0]].concat()),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(map(&bytes[..16]), io::ErrorKind::InvalidData);
+        assert_eq!(map(&[]), io::ErrorKind::InvalidData);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
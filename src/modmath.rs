@@ -0,0 +1,232 @@
+//! Modular arithmetic on `u64` and `u128`.
+
+use crate::divisor::Divisor;
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u64 {}
+    impl Sealed for u128 {}
+}
+
+/// An unsigned integer type supported by the functions in this module.
+pub trait ModInt: private::Sealed + Copy + Eq {
+    fn extended_gcd(a: Self, b: Self) -> (Self, i128, i128);
+    fn mod_inverse(a: Self, m: Self) -> Option<Self>;
+    fn mul_mod(a: Self, b: Self, m: Self) -> Option<Self>;
+    fn mod_pow(base: Self, exp: Self, m: Self) -> Option<Self>;
+}
+
+/// Returns `(g, x, y)` where `g = gcd(a, b)` and `a * x + b * y = g`.
+///
+/// The coefficients satisfy `|x| <= max(b / g, 1)` and `|y| <= max(a / g, 1)`, so they always fit
+/// in an `i128`. `extended_gcd(0, 0)` is `(0, 1, 0)`.
+#[must_use]
+pub fn extended_gcd<T: ModInt>(a: T, b: T) -> (T, i128, i128) {
+    T::extended_gcd(a, b)
+}
+
+/// Returns the `x` in `0..m` with `a * x % m == 1`, or `None` if there is no such `x` (`a` and `m`
+/// are not coprime, or `m == 0`). Every `a` has inverse 0 modulo 1.
+#[must_use]
+pub fn mod_inverse<T: ModInt>(a: T, m: T) -> Option<T> {
+    T::mod_inverse(a, m)
+}
+
+/// Returns `a * b % m` without overflowing, or `None` if `m == 0`.
+#[must_use]
+pub fn mul_mod<T: ModInt>(a: T, b: T, m: T) -> Option<T> {
+    T::mul_mod(a, b, m)
+}
+
+/// Returns `base.pow(exp) % m` without overflowing, or `None` if `m == 0`.
+#[must_use]
+pub fn mod_pow<T: ModInt>(base: T, exp: T, m: T) -> Option<T> {
+    T::mod_pow(base, exp, m)
+}
+
+// Returns `(a - b) % m` for `a, b < m`.
+pub(crate) fn sub_mod(a: u64, b: u64, m: u64) -> u64 {
+    if a >= b {
+        a - b
+    } else {
+        a.wrapping_sub(b).wrapping_add(m)
+    }
+}
+
+// Same as `mul_mod`, but uses the precomputed reciprocal of `m` when `a * b` fits in a `u64`.
+pub(crate) fn mul_mod_divisor(a: u64, b: u64, m: &Divisor) -> u64 {
+    match a.checked_mul(b) {
+        Some(product) => m.rem(product),
+        None => u64::mul_mod(a, b, m.get()).unwrap(),
+    }
+}
+
+macro_rules! impl_mod_int {
+    ($t:ty, |$a:ident, $b:ident, $m:ident| $mul_mod:expr) => {
+        impl ModInt for $t {
+            fn extended_gcd(a: Self, b: Self) -> (Self, i128, i128) {
+                let (mut r0, mut r1) = (a, b);
+                let (mut s0, mut s1) = (1i128, 0i128);
+                let (mut t0, mut t1) = (0i128, 1i128);
+
+                // The final values of `s1` and `t1` can be too large for an `i128`, but all the
+                // values we return are exact because they fit and wrapping is exact mod 2^128.
+                while r1 != 0 {
+                    let q = r0 / r1;
+                    (r0, r1) = (r1, r0 - q * r1);
+                    (s0, s1) = (s1, s0.wrapping_sub((q as i128).wrapping_mul(s1)));
+                    (t0, t1) = (t1, t0.wrapping_sub((q as i128).wrapping_mul(t1)));
+                }
+
+                (r0, s0, t0)
+            }
+
+            fn mod_inverse(a: Self, m: Self) -> Option<Self> {
+                if m == 0 {
+                    return None;
+                }
+
+                let (g, x, _) = Self::extended_gcd(a % m, m);
+                if g != 1 {
+                    None
+                } else if x < 0 {
+                    Some(m - x.unsigned_abs() as Self)
+                } else {
+                    Some(x as Self % m)
+                }
+            }
+
+            fn mul_mod($a: Self, $b: Self, $m: Self) -> Option<Self> {
+                if $m == 0 {
+                    None
+                } else {
+                    let ($a, $b) = ($a % $m, $b % $m);
+                    Some($mul_mod)
+                }
+            }
+
+            fn mod_pow(base: Self, mut exp: Self, m: Self) -> Option<Self> {
+                let mut base = Self::mul_mod(base, 1, m)?;
+                let mut result = 1 % m;
+
+                while exp > 0 {
+                    if exp & 1 == 1 {
+                        result = Self::mul_mod(result, base, m)?;
+                    }
+                    base = Self::mul_mod(base, base, m)?;
+                    exp >>= 1;
+                }
+
+                Some(result)
+            }
+        }
+    };
+}
+
+impl_mod_int!(u64, |a, b, m| (a as u128 * b as u128 % m as u128) as u64);
+
+impl_mod_int!(u128, |a, b, m| {
+    let add_mod = |x: u128, y: u128| {
+        let (sum, overflow) = x.overflowing_add(y);
+        if overflow || sum >= m {
+            sum.wrapping_sub(m)
+        } else {
+            sum
+        }
+    };
+
+    let (mut a, mut b) = (a, b);
+    let mut result = 0;
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add_mod(result, a);
+        }
+        a = add_mod(a, a);
+        b >>= 1;
+    }
+
+    result
+});
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_extended_gcd() {
+        assert_eq!(extended_gcd(240u64, 46), (2, -9, 47));
+        assert_eq!(extended_gcd(0u64, 0), (0, 1, 0));
+        assert_eq!(extended_gcd(0u64, 5), (5, 0, 1));
+        assert_eq!(extended_gcd(5u64, 0), (5, 1, 0));
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        for _ in 0..1000 {
+            let (a, b) = (rng.gen::<u64>(), rng.gen::<u64>());
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(a as i128 * x + b as i128 * y, g as i128);
+
+            let (a, b) = (rng.gen::<u128>(), rng.gen::<u128>());
+            let (g, x, y) = extended_gcd(a, b);
+            let lhs = (a as i128)
+                .wrapping_mul(x)
+                .wrapping_add((b as i128).wrapping_mul(y));
+            assert_eq!(lhs, g as i128);
+        }
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(mod_inverse(3u64, 7), Some(5));
+        assert_eq!(mod_inverse(4u64, 7), Some(2));
+        assert_eq!(mod_inverse(2u64, 5), Some(3));
+        assert_eq!(mod_inverse(10u64, 7), Some(5));
+        assert_eq!(mod_inverse(3u64, 6), None);
+        assert_eq!(mod_inverse(3u64, 0), None);
+        assert_eq!(mod_inverse(3u64, 1), Some(0));
+
+        assert_eq!(mod_inverse(2u64, u64::MAX), Some(1 << 63));
+        assert_eq!(mod_inverse(2u128, u128::MAX), Some(1 << 127));
+        assert_eq!(mod_inverse(u128::MAX - 1, u128::MAX), Some(u128::MAX - 1));
+    }
+
+    #[test]
+    fn test_mul_mod() {
+        assert_eq!(mul_mod(u64::MAX, u64::MAX, u64::MAX - 1), Some(1));
+        assert_eq!(mul_mod(u128::MAX, u128::MAX, u128::MAX - 1), Some(1));
+        assert_eq!(mul_mod(u128::MAX - 1, 2, u128::MAX), Some(u128::MAX - 2));
+        assert_eq!(mul_mod(3u64, 4, 0), None);
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        for _ in 0..1000 {
+            let m = rng.gen_range(1..=u64::MAX);
+            let (a, b) = (rng.gen_range(0..m), rng.gen_range(0..m));
+
+            assert_eq!(
+                mul_mod_divisor(a, b, &Divisor::new(m)),
+                (a as u128 * b as u128 % m as u128) as u64
+            );
+            assert_eq!(
+                sub_mod(a, b, m),
+                ((a as i128 - b as i128).rem_euclid(m as i128)) as u64
+            );
+        }
+    }
+
+    #[test]
+    fn test_mod_pow() {
+        assert_eq!(mod_pow(4u64, 13, 497), Some(445));
+        assert_eq!(mod_pow(0u64, 0, 7), Some(1));
+        assert_eq!(mod_pow(5u64, 0, 1), Some(0));
+        assert_eq!(mod_pow(5u64, 3, 0), None);
+
+        // Fermat's little theorem, with the largest primes below 2^64 and 2^128.
+        let p = u64::MAX - 58;
+        assert_eq!(mod_pow(123456789u64, p - 1, p), Some(1));
+        let p = u128::MAX - 158;
+        assert_eq!(mod_pow(123456789u128, p - 1, p), Some(1));
+    }
+}
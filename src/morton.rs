@@ -0,0 +1,158 @@
+use crate::{Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+/// Maps the row-major index of a point in a grid with `2^bits` points along each of `dims` axes to
+/// its Z-order (Morton) index, which interleaves the bits of the coordinates. Points that are
+/// close in the grid tend to be close in Z-order, so this can be composed with other permutations
+/// to get a locality-preserving order.
+///
+/// Bit `j` of coordinate `k` becomes bit `j * dims + (dims - 1 - k)` of the Morton index, so the
+/// last (fastest-changing) coordinate is in the lowest bit of each group, as in row-major order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MortonPermutation {
+    dims: u32,
+    bits: u32,
+}
+
+impl MortonPermutation {
+    /// Returns `None` if `dims` is zero or the grid has `2^64` or more points.
+    #[must_use]
+    pub fn new(dims: u32, bits: u32) -> Option<Self> {
+        (dims != 0 && dims.checked_mul(bits)? < 64).then_some(Self { dims, bits })
+    }
+
+    #[must_use]
+    pub fn dims(&self) -> u32 {
+        self.dims
+    }
+
+    /// Returns the number of points along each axis.
+    #[must_use]
+    pub fn side(&self) -> u64 {
+        1 << self.bits
+    }
+
+    // Moves bit `j` of field `k` of `x` (where the fields are `bits` wide and field 0 is the most
+    // significant) to bit `j * dims + (dims - 1 - k)`.
+    fn interleave(&self, x: u64) -> u64 {
+        let mut out = 0;
+        for k in 0..self.dims {
+            let coord = x >> ((self.dims - 1 - k) * self.bits);
+            for j in 0..self.bits {
+                out |= (coord >> j & 1) << (j * self.dims + (self.dims - 1 - k));
+            }
+        }
+        out
+    }
+
+    fn deinterleave(&self, x: u64) -> u64 {
+        let mut out = 0;
+        for k in 0..self.dims {
+            for j in 0..self.bits {
+                let bit = x >> (j * self.dims + (self.dims - 1 - k)) & 1;
+                out |= bit << ((self.dims - 1 - k) * self.bits + j);
+            }
+        }
+        out
+    }
+}
+
+impl Permutation for MortonPermutation {
+    fn num_points(&self) -> u64 {
+        1 << (self.dims * self.bits)
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        (n < self.num_points()).then(|| self.interleave(n))
+    }
+}
+
+impl InvertiblePermutation for MortonPermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        (x < self.num_points()).then(|| self.deinterleave(x))
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{PermutationExt, RandomPermutation, Shape};
+
+    #[test]
+    fn test_new() {
+        assert_eq!(MortonPermutation::new(0, 4), None);
+        assert_eq!(MortonPermutation::new(2, 32), None);
+        assert_eq!(MortonPermutation::new(3, 21).unwrap().num_points(), 1 << 63);
+        assert_eq!(MortonPermutation::new(2, 0).unwrap().num_points(), 1);
+    }
+
+    #[test]
+    fn test_2d() {
+        let p = MortonPermutation::new(2, 2).unwrap();
+        let shape = Shape::new([4, 4]).unwrap();
+
+        // Row `y`, column `x`.
+        let z = |y: u64, x: u64| p.nth(shape.flatten(&[y, x]).unwrap()).unwrap();
+        assert_eq!([z(0, 0), z(0, 1), z(1, 0), z(1, 1)], [0, 1, 2, 3]);
+        assert_eq!([z(0, 2), z(0, 3), z(1, 2), z(1, 3)], [4, 5, 6, 7]);
+        assert_eq!([z(2, 0), z(3, 3)], [8, 15]);
+        assert_eq!(p.nth(16), None);
+    }
+
+    #[test]
+    fn test_3d() {
+        let p = MortonPermutation::new(3, 3).unwrap();
+        let shape = Shape::new([8, 8, 8]).unwrap();
+
+        for i in 0..p.num_points() {
+            let coords = shape.unflatten(i).unwrap();
+            let z = p.nth(i).unwrap();
+            for (k, &c) in coords.iter().enumerate() {
+                let field = (0..3).map(|j| (z >> (3 * j + 2 - k) & 1) << j).sum::<u64>();
+                assert_eq!(field, c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bijection_and_preimage() {
+        for (dims, bits) in [(1, 5), (2, 3), (3, 2), (4, 1)] {
+            let p = MortonPermutation::new(dims, bits).unwrap();
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort_unstable();
+            assert!(values.into_iter().eq(0..p.num_points()));
+
+            for i in 0..p.num_points() {
+                assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+            }
+            assert_eq!(p.preimage(p.num_points()), None);
+        }
+
+        let p = MortonPermutation::new(3, 21).unwrap();
+        assert_eq!(
+            p.preimage(p.nth(u64::MAX >> 1).unwrap()),
+            Some(u64::MAX >> 1)
+        );
+    }
+
+    #[test]
+    fn test_then_shuffle() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = MortonPermutation::new(2, 4).unwrap();
+        let shuffle = RandomPermutation::with_rng(256, &mut rng).unwrap();
+        let q = p.then(&shuffle).unwrap();
+
+        for i in 0..256 {
+            assert_eq!(q.nth(i), shuffle.nth(p.nth(i).unwrap()));
+            assert_eq!(q.preimage(q.nth(i).unwrap()), Some(i));
+        }
+    }
+}
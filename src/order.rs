@@ -0,0 +1,225 @@
+use rand::Rng;
+
+use crate::{
+    crt::CrtBasis, rng, CycleStats, FactoredInteger, Permutation, DEFAULT_IMPLICIT_THRESHOLD,
+    DEFAULT_UNIFORM_THRESHOLD,
+};
+
+/// A random permutation of `0..n` whose order divides `m`, i.e. all of its cycle lengths divide
+/// `m`, so applying it `m` times gives the identity.
+///
+/// When `n < DEFAULT_UNIFORM_THRESHOLD`, this is stored as a single table that is uniformly
+/// distributed over all such permutations. Otherwise, `n` must be a product of small prime powers,
+/// and `x` is mapped by applying a uniformly random table (with order dividing `m`) to `x` modulo
+/// each prime power factor, and combining the results with the CRT. Each table is generated and
+/// stored explicitly, so no prime power factor can be larger than `DEFAULT_IMPLICIT_THRESHOLD`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderDividingPermutation {
+    tables: Vec<Vec<u64>>,
+    basis: CrtBasis,
+}
+
+impl OrderDividingPermutation {
+    #[must_use]
+    pub fn new(n: u64, m: u64) -> Option<Self> {
+        Self::with_rng(n, m, &mut rand::thread_rng())
+    }
+
+    /// Returns `None` if `n` or `m` is zero, if `n` isn't supported, or if a prime power factor of
+    /// `n` is larger than `DEFAULT_IMPLICIT_THRESHOLD`, since its table would be too large.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, m: u64, rng: &mut R) -> Option<Self> {
+        if n == 0 || m == 0 {
+            return None;
+        }
+
+        let moduli = if n < DEFAULT_UNIFORM_THRESHOLD {
+            vec![n]
+        } else {
+            FactoredInteger::new(n)?
+                .factors
+                .iter()
+                .map(|&(p, k)| (p as u64).pow(k as u32))
+                .collect()
+        };
+        if moduli.iter().any(|&pk| pk > DEFAULT_IMPLICIT_THRESHOLD) {
+            return None;
+        }
+
+        Some(Self {
+            tables: moduli.iter().map(|&k| table(k, m, rng)).collect(),
+            basis: CrtBasis::new(&moduli)?,
+        })
+    }
+}
+
+impl Permutation for OrderDividingPermutation {
+    fn num_points(&self) -> u64 {
+        self.basis.product()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points() {
+            return None;
+        }
+
+        let remainders = self
+            .tables
+            .iter()
+            .zip(self.basis.divisors())
+            .map(|(table, pk)| table[pk.rem(n) as usize]);
+
+        Some(self.basis.combine_iter(remainders))
+    }
+
+    /// The permutation acts on each residue independently, so its cycle type is the product of
+    /// the cycle types of the tables, which only takes time proportional to their total size.
+    fn cycle_stats(&self) -> CycleStats {
+        self.tables
+            .iter()
+            .map(|table| CycleStats::from_table(table))
+            .reduce(|a, b| a.product(&b))
+            .unwrap()
+    }
+}
+
+// Generates a uniformly random permutation of `0..len` with all cycle lengths dividing `m`.
+fn table<R: Rng + ?Sized>(len: u64, m: u64, rng: &mut R) -> Vec<u64> {
+    let divisors = (1..=m.min(len))
+        .filter(|&d| m.is_multiple_of(d))
+        .collect::<Vec<_>>();
+
+    // `log_counts[j]` is `ln(a(j) / j!)`, where `a(j)` is the number of such permutations of `j`
+    // points. Conditioning on the length `d` of the cycle containing a fixed point gives
+    // `a(j) = Σ (j - 1)! / (j - d)! * a(j - d)`.
+    let mut log_counts = vec![0.0; len as usize + 1];
+    for j in 1..=len as usize {
+        let terms = divisors.iter().take_while(|&&d| d as usize <= j);
+        let max = terms
+            .clone()
+            .map(|&d| log_counts[j - d as usize])
+            .fold(f64::MIN, f64::max);
+        let sum = terms
+            .map(|&d| (log_counts[j - d as usize] - max).exp())
+            .sum::<f64>();
+        log_counts[j] = max + sum.ln() - (j as f64).ln();
+    }
+
+    // Cut a uniformly random ordering of the points into cycles, where the cycle containing the
+    // first remaining point has length `d` with probability `(j - 1)! / (j - d)! * a(j - d) / a(j)`.
+    let mut points = (0..len).collect::<Vec<_>>();
+    rng::shuffle(rng, &mut points);
+
+    let mut table = vec![0; len as usize];
+    let mut start = 0;
+    while start < points.len() {
+        let j = points.len() - start;
+        let weights = divisors
+            .iter()
+            .take_while(|&&d| d as usize <= j)
+            .map(|&d| (log_counts[j - d as usize] - log_counts[j]).exp() / j as f64)
+            .collect::<Vec<_>>();
+
+        // The weights sum to 1, up to rounding errors.
+        let mut u =
+            (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * weights.iter().sum::<f64>();
+        let mut d = divisors[weights.len() - 1] as usize;
+        for (&w, &divisor) in weights.iter().zip(&divisors) {
+            if u < w {
+                d = divisor as usize;
+                break;
+            }
+            u -= w;
+        }
+
+        let cycle = &points[start..start + d];
+        for (i, &a) in cycle.iter().enumerate() {
+            table[a as usize] = cycle[(i + 1) % d];
+        }
+        start += d;
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    fn power_is_identity(p: &OrderDividingPermutation, m: u64, samples: u64) -> bool {
+        let n = p.num_points();
+        (0..samples.min(n))
+            .map(|i| i * (n / samples.min(n)))
+            .all(|x| {
+                let y = (0..m).fold(x, |y, _| p.nth(y).unwrap());
+                y == x
+            })
+    }
+
+    #[test]
+    fn test_order() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 10, 1009, 30030] {
+            for m in [1, 2, 3, 6, 12, 1000] {
+                let p = OrderDividingPermutation::with_rng(n, m, &mut rng).unwrap();
+                assert_eq!(p.num_points(), n);
+                assert!(power_is_identity(&p, m, 1000));
+
+                let mut values = p.iter().collect::<Vec<_>>();
+                values.sort();
+                assert!(values.into_iter().eq(0..n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_crt() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let n = DEFAULT_UNIFORM_THRESHOLD * 3 * 25;
+        let p = OrderDividingPermutation::with_rng(n, 4, &mut rng).unwrap();
+
+        assert_eq!(p.num_points(), n);
+        assert!(power_is_identity(&p, 4, 10000));
+        assert!(!power_is_identity(&p, 2, 10000));
+    }
+
+    #[test]
+    fn test_too_large() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        for n in [1 << 40, 3u64.pow(21), (1 << 33) * 3 * 5] {
+            assert_eq!(OrderDividingPermutation::with_rng(n, 2, &mut rng), None);
+        }
+    }
+
+    #[test]
+    fn test_uniform() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+
+        // There are 10 involutions of 4 points, and 9 permutations of 4 points with order 1 or 3.
+        for (m, count) in [(1, 1), (2, 10), (3, 9), (4, 10 + 6), (12, 24)] {
+            let perms = (0..2000)
+                .map(|_| {
+                    let p = OrderDividingPermutation::with_rng(4, m, &mut rng).unwrap();
+                    p.iter().collect::<Vec<_>>()
+                })
+                .collect::<HashSet<_>>();
+            assert_eq!(perms.len(), count);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        assert_eq!(OrderDividingPermutation::with_rng(0, 2, &mut rng), None);
+        assert_eq!(OrderDividingPermutation::with_rng(10, 0, &mut rng), None);
+        assert_eq!(
+            OrderDividingPermutation::with_rng(1009 * DEFAULT_UNIFORM_THRESHOLD, 2, &mut rng),
+            None
+        );
+    }
+}
@@ -0,0 +1,278 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{Permutation, RandomPermutation, SmallPermutation, UniformPermutation};
+
+/// Evaluates a permutation over whole ranges of indices at once.
+///
+/// `fill_range` only takes `&self`, so any number of threads can fill disjoint parts of the same
+/// buffer concurrently, e.g. with `chunks_mut` and scoped threads.
+pub trait ParallelPermutation: Permutation + Sync {
+    /// Writes `self.nth(start + i)` to `out[i]` for each `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start + out.len() > self.num_points()`.
+    fn fill_range(&self, start: u64, out: &mut [u64]) {
+        check_range(self.num_points(), start, out.len());
+
+        for (i, a) in (start..).zip(out) {
+            *a = self.nth(i).unwrap();
+        }
+    }
+
+    /// Calls `f(start, values)` for consecutive chunks of `σ(0), σ(1), ..., σ(n - 1)`, where
+    /// `values` is `σ(start), ..., σ(start + values.len() - 1)`. Every chunk has `chunk_len`
+    /// values, except possibly the last. The chunks are computed with `fill_range` into a single
+    /// buffer, which is reused for each chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len == 0`.
+    fn for_each_chunk<F: FnMut(u64, &[u64])>(&self, chunk_len: usize, mut f: F) {
+        assert!(chunk_len > 0, "chunk length must be nonzero");
+
+        let n = self.num_points();
+        let mut buf = vec![0; (chunk_len as u64).min(n) as usize];
+        for start in (0..n).step_by(chunk_len) {
+            let len = (n - start).min(chunk_len as u64) as usize;
+            self.fill_range(start, &mut buf[..len]);
+            f(start, &buf[..len]);
+        }
+    }
+
+    /// Permutes the bits of a packed buffer, where bit `i` is bit `i % 64` of `bits[i / 64]`, so
+    /// that bit `i` of the result is bit `σ(i)` of `bits`. Any padding bits at the end of the last
+    /// word of the result are zero.
+    ///
+    /// The indices are computed a block at a time with `fill_range`, rather than calling `nth` for
+    /// each bit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits.len()` isn't `num_points` divided by 64, rounded up.
+    fn apply_to_bits(&self, bits: &[u64]) -> Vec<u64> {
+        const BLOCK_WORDS: usize = 16;
+
+        let n = self.num_points();
+        assert_eq!(
+            bits.len() as u64,
+            n.div_ceil(64),
+            "bit buffer has the wrong length for permutation of {n} points"
+        );
+
+        let mut out = vec![0; bits.len()];
+        let mut indices = [0; BLOCK_WORDS * 64];
+        for (i, words) in out.chunks_mut(BLOCK_WORDS).enumerate() {
+            let start = (i * BLOCK_WORDS * 64) as u64;
+            let len = (n - start).min(indices.len() as u64) as usize;
+            self.fill_range(start, &mut indices[..len]);
+
+            for (word, indices) in words.iter_mut().zip(indices[..len].chunks(64)) {
+                *word = indices.iter().enumerate().fold(0, |word, (j, &k)| {
+                    word | (bits[k as usize / 64] >> (k % 64) & 1) << j
+                });
+            }
+        }
+        out
+    }
+
+    /// Collects `σ(0), σ(1), ..., σ(n - 1)` into a `Vec`, filling chunks of it on the rayon thread
+    /// pool.
+    #[cfg(feature = "rayon")]
+    fn to_vec_parallel(&self) -> Vec<u64> {
+        const CHUNK_LEN: usize = 1 << 16;
+
+        let mut vec = vec![0; self.num_points() as usize];
+        vec.par_chunks_mut(CHUNK_LEN)
+            .enumerate()
+            .for_each(|(i, chunk)| self.fill_range((i * CHUNK_LEN) as u64, chunk));
+        vec
+    }
+}
+
+fn check_range(num_points: u64, start: u64, len: usize) {
+    let end = start.checked_add(len as u64);
+    assert!(
+        end.is_some_and(|end| end <= num_points),
+        "range {start}..{start}+{len} out of bounds for permutation of {num_points} points"
+    );
+}
+
+impl ParallelPermutation for RandomPermutation {
+    fn fill_range(&self, start: u64, out: &mut [u64]) {
+        check_range(self.num_points, start, out.len());
+
+        let Some((first, rest)) = self.rounds.split_first() else {
+            return;
+        };
+
+        first.fill_range(start, out);
+        for round in rest {
+            for a in out.iter_mut() {
+                *a = round.nth(*a);
+            }
+        }
+    }
+}
+
+impl ParallelPermutation for UniformPermutation {
+    fn fill_range(&self, start: u64, out: &mut [u64]) {
+        check_range(self.num_points(), start, out.len());
+
+        let start = start as usize;
+        out.copy_from_slice(&self.as_slice()[start..start + out.len()]);
+    }
+}
+
+impl<const N: usize> ParallelPermutation for SmallPermutation<N> {}
+
+#[cfg(test)]
+mod tests {
+    use rand::{RngCore, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    fn check<P: ParallelPermutation>(p: &P) {
+        let n = p.num_points();
+        let expected = p.iter().collect::<Vec<_>>();
+
+        let mut all = vec![0; n as usize];
+        p.fill_range(0, &mut all);
+        assert_eq!(all, expected);
+
+        let ranges = [(0, 0), (1, 5), (17, 100), (n - 1, 1), (n, 0)];
+        for (start, len) in ranges
+            .into_iter()
+            .filter(|&(start, len)| start + len as u64 <= n)
+        {
+            let mut out = vec![0; len];
+            p.fill_range(start, &mut out);
+            assert_eq!(out, expected[start as usize..start as usize + len]);
+        }
+    }
+
+    #[test]
+    fn test_fill_range() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 360, 1024, 2 * 3 * 5 * 7 * 11 * 13] {
+            check(&RandomPermutation::with_rng(n, &mut rng).unwrap());
+        }
+
+        check(&RandomPermutation::with_rng_rounds(3600, 3, &mut rng).unwrap());
+        check(&RandomPermutation::from(UniformPermutation::with_rng(
+            1009, &mut rng,
+        )));
+        check(&UniformPermutation::with_rng(1009, &mut rng));
+        check(&SmallPermutation::<10>::with_rng(&mut rng));
+    }
+
+    #[test]
+    fn test_fill_range_large() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let n = (1..=20).product();
+        let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+
+        let mut out = vec![0; 10000];
+        p.fill_range(n - 10000, &mut out);
+        assert!(out
+            .iter()
+            .zip(n - 10000..)
+            .all(|(&a, i)| p.nth(i) == Some(a)));
+    }
+
+    #[test]
+    fn test_fill_range_threads() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng(1 << 16, &mut rng).unwrap();
+
+        let mut out = vec![0; 1 << 16];
+        std::thread::scope(|s| {
+            for (i, chunk) in out.chunks_mut(10000).enumerate() {
+                let p = &p;
+                s.spawn(move || p.fill_range(i as u64 * 10000, chunk));
+            }
+        });
+
+        assert!(out.into_iter().eq(p.iter()));
+    }
+
+    #[test]
+    fn test_for_each_chunk() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::with_rng(3600, &mut rng).unwrap();
+
+        for chunk_len in [1, 7, 1000, 3600, 10000] {
+            let mut all = Vec::new();
+            let mut starts = Vec::new();
+            p.for_each_chunk(chunk_len, |start, values| {
+                assert_eq!(start, all.len() as u64);
+                assert!(values.len() == chunk_len || start + values.len() as u64 == 3600);
+                starts.push(start);
+                all.extend_from_slice(values);
+            });
+
+            assert!(all.into_iter().eq(p.iter()));
+            assert_eq!(starts.len(), 3600usize.div_ceil(chunk_len));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_for_each_chunk_zero() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        p.for_each_chunk(0, |_, _| {});
+    }
+
+    #[test]
+    fn test_apply_to_bits() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+
+        for n in [1, 64, 360, 2 * 3 * 5 * 7 * 11 * 13] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            let bits = (0..n.div_ceil(64))
+                .map(|_| rng.next_u64())
+                .collect::<Vec<_>>();
+            let out = p.apply_to_bits(&bits);
+
+            let bit = |words: &[u64], i: u64| words[i as usize / 64] >> (i % 64) & 1;
+            assert!(p.pairs().all(|(i, a)| bit(&out, i) == bit(&bits, a)));
+            if n % 64 != 0 {
+                assert_eq!(out.last().unwrap() >> (n % 64), 0);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apply_to_bits_wrong_length() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(6);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        p.apply_to_bits(&[0; 5]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_to_vec_parallel() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+
+        for n in [1, 360, 1 << 16, 3 << 16, 3628800] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            assert!(p.to_vec_parallel().into_iter().eq(p.iter()));
+        }
+
+        let p = UniformPermutation::with_rng(100003, &mut rng);
+        assert_eq!(p.to_vec_parallel(), p.as_slice());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fill_range_out_of_bounds() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+        p.fill_range(300, &mut [0; 61]);
+    }
+}
@@ -0,0 +1,214 @@
+use rand::Rng;
+
+use crate::{
+    assert_explicit, Permutation, RandomPermutation, DEFAULT_UNIFORM_THRESHOLD, MAX_EXPLICIT_POINTS,
+};
+
+/// Whether a permutation is a product of an even or odd number of transpositions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parity {
+    Even,
+    Odd,
+}
+
+pub fn parity<P: Permutation>(perm: &P) -> Parity {
+    let n = perm.num_points();
+    assert_explicit(n, "parity");
+    let mut seen = vec![0u64; n.div_ceil(64) as usize];
+
+    // A cycle of length `k` is a product of `k - 1` transpositions, so the parity is that of `n`
+    // minus the number of cycles.
+    let mut cycles = 0u64;
+    for i in 0..n {
+        if seen[(i / 64) as usize] >> (i % 64) & 1 == 1 {
+            continue;
+        }
+
+        cycles += 1;
+        let mut j = i;
+        while seen[(j / 64) as usize] >> (j % 64) & 1 == 0 {
+            seen[(j / 64) as usize] |= 1 << (j % 64);
+            j = perm.nth(j).unwrap();
+        }
+    }
+
+    if (n - cycles).is_multiple_of(2) {
+        Parity::Even
+    } else {
+        Parity::Odd
+    }
+}
+
+/// A random permutation with a given parity.
+///
+/// This is a `RandomPermutation` with the images of 0 and 1 swapped if it has the wrong parity.
+/// Swapping is a bijection between the even and odd permutations, so if the underlying permutation
+/// is uniformly random, so is the result. `with_rng` uses `RandomPermutation::hybrid_with_rng`,
+/// which is uniformly random for `n < DEFAULT_UNIFORM_THRESHOLD`.
+///
+/// Finding the parity takes O(n) time, so this is only suitable for moderate `n`, and `n` can't be
+/// larger than `MAX_EXPLICIT_POINTS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParityPermutation {
+    perm: RandomPermutation,
+    parity: Parity,
+    swapped: bool,
+}
+
+impl ParityPermutation {
+    #[must_use]
+    pub fn new(n: u64, parity: Parity) -> Option<Self> {
+        Self::with_rng(n, parity, &mut rand::thread_rng())
+    }
+
+    /// Returns `None` if `n` isn't supported by `RandomPermutation::hybrid_with_rng`, or for the
+    /// same reasons as `from_permutation`.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, parity: Parity, rng: &mut R) -> Option<Self> {
+        if n > MAX_EXPLICIT_POINTS {
+            return None;
+        }
+
+        let perm = RandomPermutation::hybrid_with_rng(n, DEFAULT_UNIFORM_THRESHOLD, rng)?;
+        Self::from_permutation(perm, parity)
+    }
+
+    /// Returns `None` if there is no permutation of `0..n` with the given parity, i.e. if `n < 2`
+    /// and `parity` is `Odd`, or if `n > MAX_EXPLICIT_POINTS`, since finding the parity of `perm`
+    /// would take too long.
+    #[must_use]
+    pub fn from_permutation(perm: RandomPermutation, parity: Parity) -> Option<Self> {
+        if perm.num_points() > MAX_EXPLICIT_POINTS {
+            return None;
+        }
+
+        let swapped = perm.parity() != parity;
+        if swapped && perm.num_points() < 2 {
+            return None;
+        }
+
+        Some(Self {
+            perm,
+            parity,
+            swapped,
+        })
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> RandomPermutation {
+        self.perm
+    }
+}
+
+impl Permutation for ParityPermutation {
+    fn num_points(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        match n {
+            0 | 1 if self.swapped => self.perm.nth(1 - n),
+            _ => self.perm.nth(n),
+        }
+    }
+
+    fn parity(&self) -> Parity {
+        self.parity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{PhiloxPermutation, UniformPermutation};
+
+    struct Table(Vec<u64>);
+
+    impl Permutation for Table {
+        fn num_points(&self) -> u64 {
+            self.0.len() as u64
+        }
+
+        fn nth(&self, n: u64) -> Option<u64> {
+            self.0.get(n as usize).copied()
+        }
+    }
+
+    #[test]
+    fn test_parity() {
+        assert_eq!(Table(vec![]).parity(), Parity::Even);
+        assert_eq!(Table(vec![0, 1, 2, 3]).parity(), Parity::Even);
+        assert_eq!(Table(vec![0, 2, 1, 3]).parity(), Parity::Odd);
+        assert_eq!(Table(vec![1, 2, 0, 3]).parity(), Parity::Even);
+        assert_eq!(Table(vec![1, 2, 3, 0]).parity(), Parity::Odd);
+        assert_eq!(Table(vec![1, 0, 3, 2]).parity(), Parity::Even);
+    }
+
+    #[test]
+    fn test_parity_lehmer() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // The parity is also the parity of the number of inversions.
+        for n in [1, 2, 10, 360, 1000] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            let inversions = p.lehmer_code().iter().sum::<u64>();
+            let expected = match inversions % 2 {
+                0 => Parity::Even,
+                _ => Parity::Odd,
+            };
+            assert_eq!(p.parity(), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_parity_too_large() {
+        let p = PhiloxPermutation::with_key(MAX_EXPLICIT_POINTS + 1, 0).unwrap();
+        let _ = p.parity();
+    }
+
+    #[test]
+    fn test_parity_permutation() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        for n in [2, 3, 360, 1000] {
+            for parity in [Parity::Even, Parity::Odd] {
+                let p = ParityPermutation::with_rng(n, parity, &mut rng).unwrap();
+                assert_eq!(p.parity(), parity);
+                assert_eq!(crate::parity::parity(&p), parity);
+            }
+        }
+
+        assert!(ParityPermutation::with_rng(1, Parity::Even, &mut rng).is_some());
+        assert_eq!(ParityPermutation::with_rng(1, Parity::Odd, &mut rng), None);
+    }
+
+    #[test]
+    fn test_parity_permutation_too_large() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let n = 2 * MAX_EXPLICIT_POINTS;
+        assert_eq!(ParityPermutation::with_rng(n, Parity::Even, &mut rng), None);
+
+        let perm = RandomPermutation::with_rng(n, &mut rng).unwrap();
+        assert_eq!(ParityPermutation::from_permutation(perm, Parity::Odd), None);
+    }
+
+    #[test]
+    fn test_alternating_group() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+
+        // There are 4! / 2 = 12 even permutations of 4 points.
+        let perms = (0..1000)
+            .map(|_| {
+                let perm = UniformPermutation::with_rng(4, &mut rng).into();
+                let p = ParityPermutation::from_permutation(perm, Parity::Even).unwrap();
+                p.iter().collect::<Vec<_>>()
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(perms.len(), 12);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{rng::SplitMix64, FromBytesError, RandomPermutation};
+
+static SAVE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A checksum of `bytes`, mixing in one little-endian word at a time and then the length.
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut chunks = bytes.chunks_exact(8);
+    let mut h = 0;
+    for chunk in chunks.by_ref() {
+        h = SplitMix64::mix(h ^ u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    let mut tail = [0; 8];
+    tail[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    h = SplitMix64::mix(h ^ u64::from_le_bytes(tail));
+    SplitMix64::mix(h ^ bytes.len() as u64)
+}
+
+impl RandomPermutation {
+    /// Saves the permutation to a file that can be read by `load_from`. The file contains the
+    /// output of `to_bytes` followed by an 8-byte checksum.
+    ///
+    /// The permutation is written to a temporary file next to `path`, which then replaces `path`,
+    /// so an existing file is never left half-written. The temporary file has a name that is
+    /// unique to this call, so concurrent saves don't write to the same one.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut bytes = self.to_bytes();
+        bytes.extend_from_slice(&checksum(&bytes).to_le_bytes());
+
+        let id = SAVE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut temp = OsString::from(path);
+        temp.push(format!(".{}-{id}.tmp", std::process::id()));
+
+        let mut file = File::options().write(true).create_new(true).open(&temp)?;
+        let written = file.write_all(&bytes).and_then(|()| file.sync_all());
+        drop(file);
+
+        let result = written.and_then(|()| fs::rename(&temp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Loads a permutation saved by `save_to`.
+    ///
+    /// Returns an error with `ErrorKind::InvalidData`, wrapping a `FromBytesError`, if the
+    /// checksum doesn't match or the file isn't a valid permutation.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+
+        let (bytes, sum) = bytes
+            .split_last_chunk::<8>()
+            .ok_or(FromBytesError::UnexpectedEnd)?;
+        if checksum(bytes) != u64::from_le_bytes(*sum) {
+            return Err(FromBytesError::ChecksumMismatch.into());
+        }
+
+        Ok(Self::from_bytes(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf};
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::Permutation;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("randperm-crt-{}-{name}", std::process::id()))
+    }
+
+    fn error(err: io::Error) -> FromBytesError {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        *err.into_inner().unwrap().downcast().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let path = temp_path("save");
+
+        for n in [1, 360, 62208, 1000000] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            p.save_to(&path).unwrap();
+            let q = RandomPermutation::load_from(&path).unwrap();
+            assert!(p.iter().eq(q.iter()));
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        let dir = temp_path("concurrent");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("perm");
+
+        let perms = (0..8)
+            .map(|i| {
+                let mut rng = Xoshiro256StarStar::seed_from_u64(i);
+                RandomPermutation::with_rng(62208, &mut rng).unwrap()
+            })
+            .collect::<Vec<_>>();
+        std::thread::scope(|s| {
+            for p in &perms {
+                s.spawn(|| p.save_to(&path).unwrap());
+            }
+        });
+
+        // The file is one of the permutations, and no temporary files are left behind.
+        let q = RandomPermutation::load_from(&path).unwrap();
+        assert!(perms.contains(&q));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let path = temp_path("corrupted");
+        RandomPermutation::with_rng(360, &mut rng)
+            .unwrap()
+            .save_to(&path)
+            .unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let load = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            RandomPermutation::load_from(&path)
+        };
+
+        for i in [0, 5, bytes.len() / 2, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 1;
+            assert_eq!(
+                error(load(&corrupted).unwrap_err()),
+                FromBytesError::ChecksumMismatch
+            );
+        }
+
+        assert_eq!(
+            error(load(&bytes[..bytes.len() - 1]).unwrap_err()),
+            FromBytesError::ChecksumMismatch
+        );
+        assert_eq!(
+            error(load(&bytes[..7]).unwrap_err()),
+            FromBytesError::UnexpectedEnd
+        );
+
+        // A valid checksum of an invalid permutation.
+        let mut invalid = bytes[..bytes.len() - 8].to_vec();
+        invalid[4] = u8::MAX;
+        invalid.extend_from_slice(&checksum(&invalid).to_le_bytes());
+        assert_eq!(
+            error(load(&invalid).unwrap_err()),
+            FromBytesError::UnsupportedVersion(u8::MAX)
+        );
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            RandomPermutation::load_from(&path).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
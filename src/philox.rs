@@ -0,0 +1,173 @@
+use rand::Rng;
+
+use crate::{Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+const ROUNDS: u64 = 10;
+
+// The multiplier and key increment from the Philox-2x32 generator.
+const MULTIPLIER: u64 = 0xD251_1F53;
+const WEYL: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// A stateless keyed permutation of `0..n` that uses no tables.
+///
+/// Indices are encrypted with a balanced Feistel network whose round function is a Philox-style
+/// multiply and xor, over the smallest even number of bits that can hold `n - 1`. Outputs that
+/// land outside `0..n` are encrypted again (cycle walking), which takes fewer than 4 rounds of
+/// encryption on average. The result only depends on `n` and the key, so it is easy to reproduce
+/// on other platforms.
+///
+/// Unlike `RandomPermutation`, `n` can be any positive integer, but this is much slower to
+/// evaluate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhiloxPermutation {
+    num_points: u64,
+    key: u64,
+    half_bits: u32,
+}
+
+impl PhiloxPermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::with_key(n, rng.next_u64())
+    }
+
+    /// Returns `None` if `n == 0`.
+    #[must_use]
+    pub fn with_key(n: u64, key: u64) -> Option<Self> {
+        let bits = u64::BITS - n.checked_sub(1)?.leading_zeros();
+
+        Some(Self {
+            num_points: n,
+            key,
+            half_bits: bits.div_ceil(2).max(1),
+        })
+    }
+
+    #[must_use]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.half_bits) - 1
+    }
+
+    fn round_function(&self, x: u64, round: u64) -> u64 {
+        let key = self.key.wrapping_add(round.wrapping_mul(WEYL));
+        let product = ((x ^ key) & 0xFFFF_FFFF) * MULTIPLIER;
+        ((product >> 32) ^ product ^ (key >> 32)) & self.mask()
+    }
+
+    fn encrypt(&self, x: u64) -> u64 {
+        let (mut left, mut right) = (x >> self.half_bits, x & self.mask());
+        for round in 0..ROUNDS {
+            (left, right) = (right, left ^ self.round_function(right, round));
+        }
+        left << self.half_bits | right
+    }
+
+    fn decrypt(&self, x: u64) -> u64 {
+        let (mut left, mut right) = (x >> self.half_bits, x & self.mask());
+        for round in (0..ROUNDS).rev() {
+            (left, right) = (right ^ self.round_function(left, round), left);
+        }
+        left << self.half_bits | right
+    }
+}
+
+/// `PhiloxPermutation` is `Copy`, so it can't be zeroized on drop.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for PhiloxPermutation {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl Permutation for PhiloxPermutation {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points {
+            return None;
+        }
+
+        let mut x = self.encrypt(n);
+        while x >= self.num_points {
+            x = self.encrypt(x);
+        }
+        Some(x)
+    }
+}
+
+impl InvertiblePermutation for PhiloxPermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        if x >= self.num_points {
+            return None;
+        }
+
+        let mut n = self.decrypt(x);
+        while n >= self.num_points {
+            n = self.decrypt(n);
+        }
+        Some(n)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 3, 4, 5, 1000, 1009, 65536, 65537] {
+            let p = PhiloxPermutation::with_rng(n, &mut rng).unwrap();
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort();
+
+            assert!(values.into_iter().eq(0..n));
+            assert_eq!(p.nth(n), None);
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        for n in [1, 1009, 1 << 20, u64::MAX] {
+            let p = PhiloxPermutation::with_rng(n, &mut rng).unwrap();
+            for i in (0..1000).map(|i| i * (n / 1000)) {
+                let a = p.nth(i).unwrap();
+                assert_eq!(p.preimage(a), Some(i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let p = PhiloxPermutation::with_key(1000, 42).unwrap();
+        assert_eq!(p, PhiloxPermutation::with_key(1000, 42).unwrap());
+        assert_eq!(p.key(), 42);
+
+        let q = PhiloxPermutation::with_key(1000, 43).unwrap();
+        assert!(!p.eq_mapping(&q));
+
+        assert_eq!(PhiloxPermutation::with_key(0, 42), None);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{modmath, smooth, FactoredInteger};
+
+/// The primes that the number of points is allowed to be a product of.
+///
+/// The default is every prime below 256, which is what `RandomPermutation::new` uses. Smaller sets
+/// guarantee smaller tables, and larger sets accept more sizes, at the cost of tables with up to
+/// `p^k` entries for each prime power factor `p^k` of `n`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrimeSet {
+    // Sorted, with no duplicates.
+    primes: Vec<u32>,
+    // Whether `primes` contains every prime up to the largest one, in which case trial division can
+    // stop as soon as the cofactor is less than the square of the next prime.
+    complete: bool,
+}
+
+impl PrimeSet {
+    /// The largest prime that a set can contain.
+    pub const MAX_BOUND: u64 = 1 << 24;
+
+    /// Every prime up to `bound`. Bounds above `MAX_BOUND` are treated as `MAX_BOUND`.
+    #[must_use]
+    pub fn up_to(bound: u64) -> Self {
+        Self {
+            primes: sieve(bound.min(Self::MAX_BOUND) as u32),
+            complete: true,
+        }
+    }
+
+    /// The set of primes in `primes`, e.g. `[2, 3, 5, 7]` to guarantee tiny tables. Returns `None`
+    /// if any element isn't a prime, or is larger than `MAX_BOUND`.
+    pub fn from_primes<I: IntoIterator<Item = u64>>(primes: I) -> Option<Self> {
+        let mut primes = primes
+            .into_iter()
+            .map(|p| {
+                u32::try_from(p)
+                    .ok()
+                    .filter(|&p| p as u64 <= Self::MAX_BOUND)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        primes.sort_unstable();
+        primes.dedup();
+
+        let all = sieve(primes.last().copied().unwrap_or(0));
+        if !primes.iter().all(|p| all.binary_search(p).is_ok()) {
+            return None;
+        }
+
+        Some(Self {
+            complete: primes.len() == all.len(),
+            primes,
+        })
+    }
+
+    /// Returns the primes in increasing order.
+    #[must_use]
+    pub fn primes(&self) -> &[u32] {
+        &self.primes
+    }
+
+    #[must_use]
+    pub fn contains(&self, p: u64) -> bool {
+        u32::try_from(p).is_ok_and(|p| self.primes.binary_search(&p).is_ok())
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Returns whether `n` is a nonzero product of primes in the set.
+    #[must_use]
+    pub fn is_smooth(&self, n: u64) -> bool {
+        FactoredInteger::with_primes(n, self).is_some()
+    }
+
+    /// Returns the smallest product of primes in the set that is at least `n`, or `None` if there
+    /// is no such integer that fits in a `u64`.
+    #[must_use]
+    pub fn next_smooth(&self, n: u64) -> Option<u64> {
+        smooth::next_smooth(n, self)
+    }
+}
+
+impl Default for PrimeSet {
+    fn default() -> Self {
+        Self::up_to(u8::MAX as u64)
+    }
+}
+
+/// The sieve of Eratosthenes.
+fn sieve(bound: u32) -> Vec<u32> {
+    let bound = bound as usize;
+    let mut is_prime = vec![true; bound + 1];
+    let mut primes = Vec::new();
+
+    for p in 2..=bound {
+        if is_prime[p] {
+            primes.push(p as u32);
+            for q in (p * p..=bound).step_by(p) {
+                is_prime[q] = false;
+            }
+        }
+    }
+
+    primes
+}
+
+/// A deterministic Miller-Rabin test. These bases are enough for every `n < 3.3 * 10^24`.
+pub(crate) fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    if let Some(&p) = BASES.iter().find(|&&p| n.is_multiple_of(p)) {
+        return n == p;
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    BASES.iter().all(|&a| {
+        let mut x = modmath::mod_pow(a, d, n).unwrap();
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1..s).any(|_| {
+            x = modmath::mul_mod(x, x, n).unwrap();
+            x == n - 1
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_prime() {
+        let primes = sieve(10000);
+        assert!((0..10000).all(|n| is_prime(n) == primes.contains(&(n as u32))));
+
+        assert!(is_prime((1 << 61) - 1));
+        assert!(is_prime(18446744073709551557));
+        assert!(!is_prime(u64::MAX));
+        // A strong pseudoprime to the bases 2 through 11.
+        assert!(!is_prime(3215031751));
+        assert!(!is_prime(4294967291 * 4294967279));
+    }
+
+    #[test]
+    fn test_up_to() {
+        assert!(PrimeSet::up_to(0).primes().is_empty());
+        assert_eq!(PrimeSet::up_to(2).primes(), [2]);
+        assert_eq!(PrimeSet::up_to(20).primes(), [2, 3, 5, 7, 11, 13, 17, 19]);
+        assert_eq!(PrimeSet::default().primes().len(), 54);
+        assert_eq!(PrimeSet::default().primes().last(), Some(&251));
+        assert_eq!(PrimeSet::up_to(1_000_000).primes().len(), 78498);
+    }
+
+    #[test]
+    fn test_from_primes() {
+        let set = PrimeSet::from_primes([7, 2, 5, 3, 5]).unwrap();
+        assert_eq!(set.primes(), [2, 3, 5, 7]);
+        assert!(set.is_complete());
+        assert_eq!(set, PrimeSet::up_to(10));
+
+        let set = PrimeSet::from_primes([3, 7]).unwrap();
+        assert!(!set.is_complete());
+        assert!(set.contains(7));
+        assert!(!set.contains(5));
+        assert!(!set.contains(u64::MAX));
+
+        assert_eq!(PrimeSet::from_primes([]), Some(PrimeSet::up_to(1)));
+        assert_eq!(PrimeSet::from_primes([2, 9]), None);
+        assert_eq!(PrimeSet::from_primes([1]), None);
+        assert_eq!(PrimeSet::from_primes([0]), None);
+        assert_eq!(PrimeSet::from_primes([(1 << 31) - 1]), None);
+    }
+
+    #[test]
+    fn test_is_smooth() {
+        let set = PrimeSet::from_primes([3, 7]).unwrap();
+        assert!(set.is_smooth(1));
+        assert!(set.is_smooth(3 * 3 * 7));
+        assert!(!set.is_smooth(0));
+        assert!(!set.is_smooth(2 * 3));
+        assert!(!set.is_smooth(5));
+        assert!(!set.is_smooth(7 * 11));
+
+        let set = PrimeSet::up_to(1_000_000);
+        assert!(set.is_smooth(999983 * 999979));
+        assert!(set.is_smooth(999983 * 2u64.pow(40)));
+        assert!(!set.is_smooth(1000003));
+        assert!(!set.is_smooth(1000003 * 999983));
+
+        for n in 0..10000 {
+            assert_eq!(
+                PrimeSet::default().is_smooth(n),
+                FactoredInteger::new(n).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_smooth() {
+        let set = PrimeSet::from_primes([3, 5]).unwrap();
+        assert_eq!(set.next_smooth(0), Some(1));
+        assert_eq!(set.next_smooth(2), Some(3));
+        assert_eq!(set.next_smooth(28), Some(45));
+
+        assert_eq!(
+            PrimeSet::up_to(1_000_000).next_smooth(1000003),
+            Some(1000004)
+        );
+    }
+}
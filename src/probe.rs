@@ -0,0 +1,124 @@
+use std::iter::FusedIterator;
+
+use crate::{rng::SplitMix64, Permutation, PhiloxPermutation};
+
+/// The order in which to visit the `n` slots of an open-addressing hash table when looking up a
+/// key, as a permutation of `0..n` derived from a hash of the key.
+///
+/// This is a `PhiloxPermutation` and a position, so it needs no tables, takes O(1) space, and is
+/// cheap enough to create for every lookup. Every slot is visited exactly once, so a lookup can
+/// stop after `n` probes knowing that the whole table has been searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProbeSequence {
+    perm: PhiloxPermutation,
+    position: u64,
+}
+
+impl ProbeSequence {
+    /// The probe sequence for a key with hash `seed` in a table with `n` slots. Returns `None` if
+    /// `n == 0`.
+    ///
+    /// The seed is mixed before it is used, so hashes of low quality (e.g. identity hashes of
+    /// integers) give unrelated sequences.
+    #[must_use]
+    pub fn new(n: u64, seed: u64) -> Option<Self> {
+        Some(Self {
+            perm: PhiloxPermutation::with_key(n, SplitMix64::mix(seed))?,
+            position: 0,
+        })
+    }
+
+    /// The number of slots in the table.
+    #[must_use]
+    pub fn num_slots(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    /// The number of slots that have been probed so far.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the slot probed at step `i`, without advancing the sequence, or `None` if `i >= n`.
+    #[must_use]
+    pub fn probe(&self, i: u64) -> Option<u64> {
+        self.perm.nth(i)
+    }
+}
+
+impl Iterator for ProbeSequence {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.perm.nth(self.position)?;
+        self.position += 1;
+        Some(slot)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.num_slots() - self.position;
+        (
+            usize::try_from(remaining).unwrap_or(usize::MAX),
+            usize::try_from(remaining).ok(),
+        )
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.position = self.position.saturating_add(n as u64).min(self.num_slots());
+        self.next()
+    }
+}
+
+impl FusedIterator for ProbeSequence {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visits_every_slot() {
+        for n in [1, 2, 7, 64, 1000, 1009] {
+            for seed in 0..10 {
+                let mut slots = ProbeSequence::new(n, seed).unwrap().collect::<Vec<_>>();
+                slots.sort();
+                assert!(slots.into_iter().eq(0..n));
+            }
+        }
+
+        assert_eq!(ProbeSequence::new(0, 0), None);
+    }
+
+    #[test]
+    fn test_seeds() {
+        let seq = |seed| ProbeSequence::new(1000, seed).unwrap();
+        assert!(seq(1).eq(seq(1)));
+        assert!(!seq(1).eq(seq(2)));
+
+        // Consecutive seeds should start at unrelated slots.
+        let mut first = (0..100)
+            .map(|seed| seq(seed).next().unwrap())
+            .collect::<Vec<_>>();
+        first.sort();
+        first.dedup();
+        assert!(first.len() > 90);
+    }
+
+    #[test]
+    fn test_position() {
+        let mut seq = ProbeSequence::new(1009, 42).unwrap();
+        let expected = seq.collect::<Vec<_>>();
+        assert_eq!(seq.size_hint(), (1009, Some(1009)));
+
+        assert_eq!(seq.next(), Some(expected[0]));
+        assert_eq!(seq.nth(9), Some(expected[10]));
+        assert_eq!(seq.position(), 11);
+        assert_eq!(seq.probe(500), Some(expected[500]));
+        assert_eq!(seq.size_hint(), (998, Some(998)));
+
+        assert_eq!(seq.nth(2000), None);
+        assert_eq!(seq.position(), 1009);
+        assert_eq!(seq.next(), None);
+        assert_eq!(seq.probe(1009), None);
+    }
+}
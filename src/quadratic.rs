@@ -0,0 +1,204 @@
+use rand::Rng;
+
+use crate::{modmath, primes, Inversed, InvertiblePermutation, Permutation, PermutationExt};
+
+/// A keyed permutation of `0..n` built from the quadratic residue map on a prime `p ≡ 3 mod 4`,
+/// as used for turning sequential integer IDs into unique non-sequential ones.
+///
+/// For such a prime, `-1` isn't a square mod `p`, so the map `f(x) = x^2 mod p` for `x <= p / 2`
+/// and `f(x) = p - (x^2 mod p)` otherwise is a permutation of `0..p`: the first half maps onto the
+/// squares, and the second half onto their negatives. This computes `f((f(i) + key) mod p)`, with
+/// `p` the smallest suitable prime that is at least `n`. If `p > n`, outputs outside `0..n` are
+/// mapped again (cycle walking), which takes about `p / n` steps on average.
+///
+/// Each evaluation of `f` is a modular squaring, and each evaluation of its inverse is a modular
+/// square root, i.e. an exponentiation. The result is a well-scrambled sequence, but with only
+/// `p` keys, it is far from a uniformly random permutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuadraticResiduePermutation {
+    num_points: u64,
+    prime: u64,
+    key: u64,
+}
+
+impl QuadraticResiduePermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        Self::with_key(n, rng.next_u64())
+    }
+
+    /// Returns `None` if `n == 0` or there is no prime `p ≡ 3 mod 4` with `n <= p < 2^64`. Keys
+    /// are reduced mod `p`.
+    #[must_use]
+    pub fn with_key(n: u64, key: u64) -> Option<Self> {
+        if n == 0 {
+            return None;
+        }
+
+        let mut prime = n.checked_add(3 - n % 4)?;
+        while !primes::is_prime(prime) {
+            prime = prime.checked_add(4)?;
+        }
+
+        Some(Self {
+            num_points: n,
+            prime,
+            key: key % prime,
+        })
+    }
+
+    /// Returns the prime `p` that the map is defined on.
+    #[must_use]
+    pub fn prime(&self) -> u64 {
+        self.prime
+    }
+
+    #[must_use]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn residue(&self, x: u64) -> u64 {
+        let r = modmath::mul_mod(x, x, self.prime).unwrap();
+        if x <= self.prime / 2 {
+            r
+        } else {
+            (self.prime - r) % self.prime
+        }
+    }
+
+    fn residue_inverse(&self, y: u64) -> u64 {
+        // The square roots of `a` are `±a^((p + 1) / 4)` if `a` is a square.
+        let sqrt = |a| modmath::mod_pow(a, (self.prime + 1) / 4, self.prime).unwrap();
+
+        let r = sqrt(y);
+        if modmath::mul_mod(r, r, self.prime).unwrap() == y {
+            r.min(self.prime - r)
+        } else {
+            let r = sqrt(self.prime - y);
+            r.max(self.prime - r)
+        }
+    }
+
+    // Returns `(x + y) mod p` for `x, y < p`, without overflowing.
+    fn add(&self, x: u64, y: u64) -> u64 {
+        let (sum, overflow) = x.overflowing_add(y);
+        if overflow || sum >= self.prime {
+            sum.wrapping_sub(self.prime)
+        } else {
+            sum
+        }
+    }
+
+    fn encrypt(&self, x: u64) -> u64 {
+        self.residue(self.add(self.residue(x), self.key))
+    }
+
+    fn decrypt(&self, y: u64) -> u64 {
+        let y = self.residue_inverse(y);
+        self.residue_inverse(self.add(y, (self.prime - self.key) % self.prime))
+    }
+}
+
+impl Permutation for QuadraticResiduePermutation {
+    fn num_points(&self) -> u64 {
+        self.num_points
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        if n >= self.num_points {
+            return None;
+        }
+
+        let mut x = self.encrypt(n);
+        while x >= self.num_points {
+            x = self.encrypt(x);
+        }
+        Some(x)
+    }
+}
+
+impl InvertiblePermutation for QuadraticResiduePermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        if x >= self.num_points {
+            return None;
+        }
+
+        let mut n = self.decrypt(x);
+        while n >= self.num_points {
+            n = self.decrypt(n);
+        }
+        Some(n)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_with_key() {
+        assert_eq!(QuadraticResiduePermutation::with_key(0, 0), None);
+        assert_eq!(QuadraticResiduePermutation::with_key(u64::MAX, 0), None);
+
+        let primes = [(1, 3), (3, 3), (4, 7), (12, 19), (1000, 1019)];
+        for (n, p) in primes {
+            let perm = QuadraticResiduePermutation::with_key(n, 5).unwrap();
+            assert_eq!(perm.prime(), p);
+            assert_eq!(perm.key(), 5 % p);
+        }
+
+        let perm = QuadraticResiduePermutation::with_key(u64::MAX - 1000, u64::MAX).unwrap();
+        assert_eq!(perm.prime() % 4, 3);
+        for i in [0, 1, 1234567898765, u64::MAX - 1001] {
+            assert_eq!(perm.preimage(perm.nth(i).unwrap()), Some(i));
+        }
+
+        let perm = QuadraticResiduePermutation::with_key(1 << 63, 0).unwrap();
+        for i in [0, 1, 1234567898765, (1 << 63) - 1] {
+            assert_eq!(perm.preimage(perm.nth(i).unwrap()), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_residue() {
+        // The map on its own is a permutation of `0..p`.
+        let perm = QuadraticResiduePermutation::with_key(11, 0).unwrap();
+        let values = (0..11).map(|x| perm.residue(x)).collect::<Vec<_>>();
+        assert_eq!(values, [0, 1, 4, 9, 5, 3, 8, 6, 2, 7, 10]);
+        for x in 0..11 {
+            assert_eq!(perm.residue_inverse(perm.residue(x)), x);
+        }
+    }
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        for n in [1, 2, 3, 10, 11, 100, 1000, 4096] {
+            let perm = QuadraticResiduePermutation::with_rng(n, &mut rng).unwrap();
+
+            let mut values = perm.iter().collect::<Vec<_>>();
+            values.sort_unstable();
+            assert!(values.into_iter().eq(0..n));
+
+            for i in 0..n {
+                assert_eq!(perm.preimage(perm.nth(i).unwrap()), Some(i));
+            }
+            assert_eq!(perm.nth(n), None);
+            assert_eq!(perm.preimage(n), None);
+        }
+    }
+}
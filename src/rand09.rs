@@ -0,0 +1,72 @@
+use rand::RngCore;
+
+/// Adapts an RNG from `rand_core` 0.9 (used by `rand` 0.9) to the `rand_core` 0.6 traits that the
+/// `with_rng` constructors take, e.g. `RandomPermutation::with_rng(n, &mut Rng09(&mut rng))`.
+///
+/// The output of this crate only depends on `next_u64`, so an adapted RNG generates the same
+/// permutations as an RNG from `rand` 0.8 with the same `next_u64` output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng09<R>(pub R);
+
+impl<R: rand_core_09::RngCore> RngCore for Rng09<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{Permutation, RandomPermutation};
+
+    // A `rand_core` 0.9 RNG with the same output as a `rand` 0.8 one.
+    struct Wrapper(Xoshiro256StarStar);
+
+    impl rand_core_09::RngCore for Wrapper {
+        fn next_u32(&mut self) -> u32 {
+            self.0.next_u32()
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0.next_u64()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.0.fill_bytes(dest);
+        }
+    }
+
+    #[test]
+    fn test_same_permutation() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut rng_09 = Wrapper(Xoshiro256StarStar::seed_from_u64(0));
+
+        for n in [1, 360, 62208] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            let q = RandomPermutation::with_rng(n, &mut Rng09(&mut rng_09)).unwrap();
+            assert!(p.eq_mapping(&q));
+        }
+
+        let mut bytes = [0; 13];
+        Rng09(&mut rng_09).fill_bytes(&mut bytes);
+        let mut expected = [0; 13];
+        rng.fill_bytes(&mut expected);
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,303 @@
+use std::ops::Range;
+
+use rand::Rng;
+
+use crate::{InvertiblePermutation, Permutation, RandomPermutation};
+
+/// A random permutation of the values in `start..end`.
+///
+/// `nth(i)` is the `i`th value in the permuted order, so `nth` takes an index in `0..len` and
+/// returns a value in `start..end`. Use `get` to map a value in the range to another value in the
+/// range.
+///
+/// This doesn't implement `Permutation`, whose methods (`verify`, `cycle_stats`, composition, ...)
+/// assume that the values are in `0..num_points`, like the indices. Use `permutation` to get the
+/// underlying permutation of `0..len` when you need one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PermutedRange {
+    start: u64,
+    perm: RandomPermutation,
+}
+
+impl PermutedRange {
+    #[must_use]
+    pub fn new(range: Range<u64>) -> Option<Self> {
+        Self::with_rng(range, &mut rand::thread_rng())
+    }
+
+    /// Returns `None` if the range is empty or `RandomPermutation` doesn't support its length.
+    pub fn with_rng<R: Rng + ?Sized>(range: Range<u64>, rng: &mut R) -> Option<Self> {
+        let perm = RandomPermutation::with_rng(range.end.checked_sub(range.start)?, rng)?;
+        Self::from_permutation(range.start, perm)
+    }
+
+    /// Shifts `perm` to permute `start..start + perm.num_points()`. Returns `None` if the end of
+    /// the range overflows.
+    #[must_use]
+    pub fn from_permutation(start: u64, perm: RandomPermutation) -> Option<Self> {
+        start.checked_add(perm.num_points())?;
+        Some(Self { start, perm })
+    }
+
+    #[must_use]
+    pub fn range(&self) -> Range<u64> {
+        self.start..self.start + self.perm.num_points()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `i`th value in the permuted order, or `None` if `i >= len`.
+    #[must_use]
+    pub fn nth(&self, i: u64) -> Option<u64> {
+        Some(self.start + self.perm.nth(i)?)
+    }
+
+    /// Returns the image of `x`, or `None` if `x` is not in the range.
+    #[must_use]
+    pub fn get(&self, x: u64) -> Option<u64> {
+        self.nth(x.checked_sub(self.start)?)
+    }
+
+    /// Returns the inverse of `get`, i.e. the `x` with `get(x) == Some(y)`.
+    #[must_use]
+    pub fn preimage(&self, y: u64) -> Option<u64> {
+        let inverse = self.perm.inverse();
+        Some(self.start + inverse.nth(y.checked_sub(self.start)?)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.perm.iter().map(|a| self.start + a)
+    }
+
+    /// Returns the underlying permutation of `0..len`.
+    #[must_use]
+    pub fn permutation(&self) -> &RandomPermutation {
+        &self.perm
+    }
+}
+
+/// A random bijection from `domain` onto `codomain`, two ranges of the same length.
+///
+/// `get(x)` is `codomain.start + σ(x - domain.start)` for a random permutation `σ` of `0..len`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RangeBijection {
+    domain_start: u64,
+    codomain_start: u64,
+    perm: RandomPermutation,
+}
+
+impl RangeBijection {
+    #[must_use]
+    pub fn new(domain: Range<u64>, codomain: Range<u64>) -> Option<Self> {
+        Self::with_rng(domain, codomain, &mut rand::thread_rng())
+    }
+
+    /// Uses a uniformly random table for lengths below `DEFAULT_UNIFORM_THRESHOLD`, as in
+    /// `RandomPermutation::hybrid_with_rng`. Larger lengths use the CRT construction if it
+    /// supports them, and a single `PhiloxPermutation` component otherwise.
+    ///
+    /// Returns `None` if the ranges have different lengths or are empty.
+    pub fn with_rng<R: Rng + ?Sized>(
+        domain: Range<u64>,
+        codomain: Range<u64>,
+        rng: &mut R,
+    ) -> Option<Self> {
+        let len = domain.end.checked_sub(domain.start)?;
+        if codomain.end.checked_sub(codomain.start)? != len {
+            return None;
+        }
+
+        let perm = RandomPermutation::any_size_with_rng(len, rng)?;
+        Self::from_permutation(domain.start, codomain.start, perm)
+    }
+
+    /// Shifts `perm` to map `domain_start..domain_start + len` onto
+    /// `codomain_start..codomain_start + len`, where `len = perm.num_points()`. Returns `None` if
+    /// the end of either range overflows.
+    #[must_use]
+    pub fn from_permutation(
+        domain_start: u64,
+        codomain_start: u64,
+        perm: RandomPermutation,
+    ) -> Option<Self> {
+        domain_start.checked_add(perm.num_points())?;
+        codomain_start.checked_add(perm.num_points())?;
+        Some(Self {
+            domain_start,
+            codomain_start,
+            perm,
+        })
+    }
+
+    #[must_use]
+    pub fn domain(&self) -> Range<u64> {
+        self.domain_start..self.domain_start + self.perm.num_points()
+    }
+
+    #[must_use]
+    pub fn codomain(&self) -> Range<u64> {
+        self.codomain_start..self.codomain_start + self.perm.num_points()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the image of `x`, or `None` if `x` is not in the domain.
+    #[must_use]
+    pub fn get(&self, x: u64) -> Option<u64> {
+        Some(self.codomain_start + self.perm.nth(x.checked_sub(self.domain_start)?)?)
+    }
+
+    /// Returns the `x` in the domain with `get(x) == Some(y)`, or `None` if `y` is not in the
+    /// codomain.
+    #[must_use]
+    pub fn preimage(&self, y: u64) -> Option<u64> {
+        Some(self.domain_start + self.perm.preimage(y.checked_sub(self.codomain_start)?)?)
+    }
+
+    /// Returns an iterator over `(x, get(x))` for each `x` in the domain.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.perm
+            .pairs()
+            .map(|(i, a)| (self.domain_start + i, self.codomain_start + a))
+    }
+
+    /// Returns the underlying permutation of `0..len`.
+    #[must_use]
+    pub fn permutation(&self) -> &RandomPermutation {
+        &self.perm
+    }
+
+    #[must_use]
+    pub fn into_permutation(self) -> RandomPermutation {
+        self.perm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_with_rng() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = PermutedRange::with_rng(1000..1360, &mut rng).unwrap();
+
+        assert_eq!(p.range(), 1000..1360);
+        assert_eq!(p.len(), 360);
+
+        let mut vec = p.iter().collect::<Vec<_>>();
+        vec.sort();
+        assert!(vec.into_iter().eq(1000..1360));
+
+        assert_eq!(p.nth(360), None);
+        assert_eq!(p.get(999), None);
+        assert_eq!(p.get(1360), None);
+        assert_eq!(p.preimage(1360), None);
+
+        for x in 1000..1360 {
+            assert_eq!(p.get(x), p.nth(x - 1000));
+            assert_eq!(p.preimage(p.get(x).unwrap()), Some(x));
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        assert_eq!(PermutedRange::with_rng(10..10, &mut rng), None);
+        assert_eq!(PermutedRange::with_rng(0..1009, &mut rng), None);
+
+        let perm = RandomPermutation::with_rng(10, &mut rng).unwrap();
+        assert_eq!(
+            PermutedRange::from_permutation(u64::MAX - 9, perm.clone()),
+            None
+        );
+        assert!(PermutedRange::from_permutation(u64::MAX - 10, perm).is_some());
+    }
+
+    #[test]
+    fn test_end_of_range() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = PermutedRange::with_rng(u64::MAX - 360..u64::MAX, &mut rng).unwrap();
+
+        let mut vec = p.iter().collect::<Vec<_>>();
+        vec.sort();
+        assert!(vec.into_iter().eq(u64::MAX - 360..u64::MAX));
+    }
+
+    #[test]
+    fn test_bijection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RangeBijection::with_rng(1000..1360, 50..410, &mut rng).unwrap();
+
+        assert_eq!(p.domain(), 1000..1360);
+        assert_eq!(p.codomain(), 50..410);
+        assert_eq!(p.len(), 360);
+
+        let mut images = p.iter().map(|(_, y)| y).collect::<Vec<_>>();
+        images.sort();
+        assert!(images.into_iter().eq(50..410));
+
+        for (x, y) in p.iter() {
+            assert_eq!(p.get(x), Some(y));
+            assert_eq!(p.preimage(y), Some(x));
+        }
+
+        assert_eq!(p.get(999), None);
+        assert_eq!(p.get(1360), None);
+        assert_eq!(p.preimage(49), None);
+        assert_eq!(p.preimage(410), None);
+    }
+
+    #[test]
+    fn test_bijection_any_length() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+
+        // 1009 and 1048583 are prime, and 1048583 is above the uniform threshold.
+        for len in [1009, 1048583] {
+            let p = RangeBijection::with_rng(10..10 + len, 0..len, &mut rng).unwrap();
+            for x in [10, 11, 500, 9 + len] {
+                assert_eq!(p.preimage(p.get(x).unwrap()), Some(x));
+            }
+            assert_eq!(p.get(10 + len), None);
+        }
+
+        let p = RangeBijection::with_rng(0..1009, 1..1010, &mut rng).unwrap();
+        let mut images = p.iter().map(|(_, y)| y).collect::<Vec<_>>();
+        images.sort();
+        assert!(images.into_iter().eq(1..1010));
+    }
+
+    #[test]
+    fn test_bijection_invalid() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        assert_eq!(RangeBijection::with_rng(0..10, 5..14, &mut rng), None);
+        assert_eq!(RangeBijection::with_rng(0..0, 5..5, &mut rng), None);
+
+        let perm = RandomPermutation::with_rng(10, &mut rng).unwrap();
+        assert_eq!(
+            RangeBijection::from_permutation(0, u64::MAX - 9, perm.clone()),
+            None
+        );
+        let p = RangeBijection::from_permutation(0, u64::MAX - 10, perm).unwrap();
+        assert_eq!(p.codomain(), u64::MAX - 10..u64::MAX);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{
+    lehmer,
+    round::{Round, SubPerm},
+    FactoredInteger, RandomPermutation, DEFAULT_IMPLICIT_THRESHOLD,
+};
+
+// Ranks are stored as little-endian 64-bit limbs.
+
+// The largest total length of the tables that `from_rank` builds. Decoding takes time quadratic in
+// the length of the rank, which is already impractical well below this.
+const MAX_RANK_TABLE_LEN: u64 = 1 << 20;
+
+fn mul_add(rank: &mut Vec<u64>, base: u64, digit: u64) {
+    let mut carry = digit as u128;
+    for limb in rank.iter_mut() {
+        let x = *limb as u128 * base as u128 + carry;
+        *limb = x as u64;
+        carry = x >> 64;
+    }
+    if carry != 0 {
+        rank.push(carry as u64);
+    }
+}
+
+fn div_rem(rank: &mut [u64], base: u64) -> u64 {
+    let mut rem = 0u128;
+    for limb in rank.iter_mut().rev() {
+        let x = rem << 64 | *limb as u128;
+        *limb = (x / base as u128) as u64;
+        rem = x % base as u128;
+    }
+    rem as u64
+}
+
+/// The prime power factors of `n`, in the order that `FactoredInteger` lists them.
+fn moduli(n: u64) -> Option<Vec<u64>> {
+    let factors = FactoredInteger::new(n)?.factors;
+    Some(
+        factors
+            .iter()
+            .map(|&(p, k)| (p as u64).pow(k as u32))
+            .collect(),
+    )
+}
+
+/// The bases of the digits of a rank: the Lehmer code of the component order, followed by the
+/// Lehmer code of the table for each modulus.
+fn bases(moduli: &[u64]) -> impl DoubleEndedIterator<Item = u64> + '_ {
+    let lehmer_bases = |len: u64| (1..=len).rev();
+    lehmer_bases(moduli.len() as u64).chain(moduli.iter().flat_map(move |&m| lehmer_bases(m)))
+}
+
+/// `log2(m!)`, summed directly for small `m` and from Stirling's series otherwise, which is
+/// accurate to about 1e-12 relative error in that range.
+fn log2_factorial(m: u64) -> f64 {
+    if m < 1024 {
+        return (2..=m).map(|i| (i as f64).log2()).sum();
+    }
+
+    let m = m as f64;
+    let ln = m * m.ln() - m + 0.5 * (std::f64::consts::TAU * m).ln() + 1.0 / (12.0 * m)
+        - 1.0 / (360.0 * m * m * m);
+    ln / std::f64::consts::LN_2
+}
+
+impl RandomPermutation {
+    /// Returns `log2` of the number of different permutations of `0..n` that `with_rng` can
+    /// generate, i.e. `log2(c! * ∏ (p^k)!)` for the `c` prime power factors `p^k` of `n`, or
+    /// `None` if `n` isn't supported. Compare this with `uniform_entropy_bits(n)` to see how
+    /// small a fraction of all permutations the family is.
+    ///
+    /// Factors larger than `DEFAULT_IMPLICIT_THRESHOLD` are generated from a 64-bit key instead
+    /// of a table, so they contribute at most 64 bits.
+    #[must_use]
+    pub fn entropy_bits(n: u64) -> Option<f64> {
+        let moduli = moduli(n)?;
+        let tables = moduli
+            .iter()
+            .map(|&m| {
+                if m > DEFAULT_IMPLICIT_THRESHOLD {
+                    64.0
+                } else {
+                    log2_factorial(m)
+                }
+            })
+            .sum::<f64>();
+        Some(log2_factorial(moduli.len() as u64) + tables)
+    }
+
+    /// Returns `log2(n!)`, the number of bits of entropy in a uniformly random permutation of
+    /// `0..n`.
+    #[must_use]
+    pub fn uniform_entropy_bits(n: u64) -> f64 {
+        log2_factorial(n)
+    }
+
+    /// Returns the index of the permutation among all the permutations that
+    /// `RandomPermutation::with_rng(n, _)` can generate, as little-endian 64-bit limbs with no
+    /// trailing zeros. `from_rank` is the inverse.
+    ///
+    /// The members of the family are determined by the order of the prime power factors of `n`
+    /// and a table for each factor `p^k`, so there are `c! * ∏ (p^k)!` of them for `c` factors,
+    /// and the rank has about `Σ p^k log2(p^k)` bits. Computing it takes time quadratic in its
+    /// length, so this is only practical when the tables are small.
+    ///
+    /// Returns `None` if the permutation isn't in the family, e.g. if it has more than one round
+    /// or implicit components.
+    #[must_use]
+    pub fn rank(&self) -> Option<Vec<u64>> {
+        let [round] = self.rounds.as_slice() else {
+            return None;
+        };
+        if round.inverted && round.sub_perms.len() > 1 {
+            return None;
+        }
+
+        let moduli = moduli(self.num_points)?;
+        let tables = round
+            .sub_perms
+            .iter()
+            .map(SubPerm::table)
+            .collect::<Option<Vec<_>>>()?;
+
+        // `order[j]` is the index in `moduli` of component `j`.
+        let order = tables
+            .iter()
+            .map(|t| Some(moduli.iter().position(|&m| m == t.len() as u64)? as u64))
+            .collect::<Option<Vec<_>>>()?;
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        if !sorted.iter().copied().eq(0..moduli.len() as u64) {
+            return None;
+        }
+
+        let mut digits = lehmer::table_lehmer_code(&order);
+        for i in 0..moduli.len() as u64 {
+            let j = order.iter().position(|&o| o == i).unwrap();
+            digits.extend(lehmer::table_lehmer_code(tables[j]));
+        }
+
+        let mut rank = Vec::new();
+        for (base, digit) in bases(&moduli).zip(digits) {
+            mul_add(&mut rank, base, digit);
+        }
+        while rank.last() == Some(&0) {
+            rank.pop();
+        }
+        Some(rank)
+    }
+
+    /// Reconstructs the permutation of `0..n` with the given `rank`, which may have trailing
+    /// zero limbs. Returns `None` if `n` isn't supported by `new`, if `n` has a factor larger
+    /// than `DEFAULT_IMPLICIT_THRESHOLD` (which `with_rng` doesn't store as a table), or if
+    /// `rank` is at least the size of the family.
+    ///
+    /// Also returns `None`, before allocating anything, if the prime power factors of `n` add up
+    /// to more than `2^20`, or if `rank` has far more limbs than any rank in the family.
+    #[must_use]
+    pub fn from_rank(n: u64, rank: &[u64]) -> Option<Self> {
+        let moduli = moduli(n)?;
+        if moduli.iter().any(|&m| m > DEFAULT_IMPLICIT_THRESHOLD)
+            || moduli.iter().sum::<u64>() > MAX_RANK_TABLE_LEN
+        {
+            return None;
+        }
+
+        let len = rank
+            .iter()
+            .rposition(|&limb| limb != 0)
+            .map_or(0, |i| i + 1);
+        if len as f64 > Self::entropy_bits(n)? / 64.0 + 1.0 {
+            return None;
+        }
+
+        let mut rank = rank[..len].to_vec();
+        let mut digits = bases(&moduli)
+            .rev()
+            .map(|base| div_rem(&mut rank, base))
+            .collect::<Vec<_>>();
+        if rank.iter().any(|&limb| limb != 0) {
+            return None;
+        }
+        digits.reverse();
+
+        let (order_digits, mut rest) = digits.split_at(moduli.len());
+        let order = lehmer::from_lehmer_code(order_digits)?;
+        let mut tables = moduli
+            .iter()
+            .map(|&m| {
+                let (code, tail) = rest.split_at(m as usize);
+                rest = tail;
+                lehmer::from_lehmer_code(code)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let tables = order
+            .iter()
+            .map(|&i| std::mem::take(&mut tables[i as usize]))
+            .collect::<Vec<_>>();
+
+        Some(Self {
+            num_points: n,
+            rounds: vec![Round::new(tables)?],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::Permutation;
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 12, 360, 30030, 62208] {
+            for _ in 0..5 {
+                let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+                let rank = p.rank().unwrap();
+                assert_ne!(rank.last(), Some(&0));
+                assert_eq!(RandomPermutation::from_rank(n, &rank), Some(p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_family() {
+        // For n = 6, there are 2! * 2! * 3! = 24 permutations in the family, all different.
+        let perms = (0..24)
+            .map(|r| RandomPermutation::from_rank(6, &[r]).unwrap())
+            .collect::<Vec<_>>();
+        for (r, p) in perms.iter().enumerate() {
+            assert_eq!(p.rank(), Some(if r == 0 { vec![] } else { vec![r as u64] }));
+            assert!(perms[..r].iter().all(|q| !q.eq_mapping(p)));
+        }
+
+        assert_eq!(RandomPermutation::from_rank(6, &[24]), None);
+        assert_eq!(RandomPermutation::from_rank(6, &[0, 1]), None);
+        assert!(RandomPermutation::from_rank(6, &[23, 0, 0]).is_some());
+        assert_eq!(RandomPermutation::from_rank(1009, &[]), None);
+        assert_eq!(RandomPermutation::from_rank(3 << 40, &[]), None);
+    }
+
+    #[test]
+    fn test_from_rank_limits() {
+        assert_eq!(RandomPermutation::from_rank(1 << 31, &[]), None);
+        assert_eq!(RandomPermutation::from_rank(1 << 31, &[1; 1000]), None);
+        assert_eq!(RandomPermutation::from_rank(360, &[1; 1 << 20]), None);
+
+        let n = 1 << 20;
+        assert_eq!(
+            RandomPermutation::from_rank(n, &[1]).unwrap().num_points(),
+            n
+        );
+        assert_eq!(RandomPermutation::from_rank(n * 3, &[1]), None);
+    }
+
+    #[test]
+    fn test_large_rank() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::with_rng(256 * 243, &mut rng).unwrap();
+        let rank = p.rank().unwrap();
+        assert!(rank.len() > 30);
+        assert_eq!(RandomPermutation::from_rank(256 * 243, &rank), Some(p));
+    }
+
+    #[test]
+    fn test_not_in_family() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = RandomPermutation::with_rng_rounds(360, 2, &mut rng).unwrap();
+        assert_eq!(p.rank(), None);
+
+        let p = RandomPermutation::hybrid_with_rng(1009, u64::MAX, &mut rng).unwrap();
+        assert_eq!(p.rank(), None);
+
+        let p = RandomPermutation::builder(1 << 20)
+            .implicit_threshold(1 << 10)
+            .rng(rng)
+            .build()
+            .unwrap();
+        assert_eq!(p.rank(), None);
+    }
+
+    #[test]
+    fn test_entropy_bits() {
+        // 2! * 2! * 3! = 24
+        assert_eq!(RandomPermutation::entropy_bits(6), Some(24f64.log2()));
+        assert_eq!(RandomPermutation::entropy_bits(1), Some(0.0));
+        assert_eq!(RandomPermutation::entropy_bits(1009), None);
+        assert_eq!(RandomPermutation::uniform_entropy_bits(6), 720f64.log2());
+
+        // Agrees with the length of the largest rank.
+        let n = 256 * 243;
+        let bits = RandomPermutation::entropy_bits(n).unwrap();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let rank = RandomPermutation::with_rng(n, &mut rng)
+            .unwrap()
+            .rank()
+            .unwrap();
+        assert!(rank.len() as f64 * 64.0 - 64.0 < bits);
+        assert!(bits < 1e4);
+
+        // Stirling's series matches the direct sum near the cutoff.
+        let direct = (2..=2000u64).map(|i| (i as f64).log2()).sum::<f64>();
+        assert!((log2_factorial(2000) - direct).abs() < 1e-8 * direct);
+
+        // The factor 2^40 is implicit.
+        let bits = RandomPermutation::entropy_bits(3 << 40).unwrap();
+        assert!((bits - (1.0 + 6f64.log2() + 64.0)).abs() < 1e-9);
+        assert!(bits < 1e-10 * RandomPermutation::uniform_entropy_bits(3 << 40));
+    }
+}
@@ -0,0 +1,112 @@
+//! Bounded sampling and shuffling, implemented here rather than using `rand`'s `gen_range` and
+//! `shuffle`, whose algorithms may change between releases. The output of this crate for a given
+//! seeded RNG must only depend on `RngCore::next_u64`, so these algorithms must never change.
+
+use rand::RngCore;
+
+/// Returns a uniformly random integer in `0..range`, where `range > 0`.
+///
+/// This uses Lemire's widening multiplication method, with the same rejection zone that `rand` 0.8
+/// uses for `gen_range` on `u64`.
+pub fn gen_below<R: RngCore + ?Sized>(rng: &mut R, range: u64) -> u64 {
+    debug_assert_ne!(range, 0);
+
+    let zone = (range << range.leading_zeros()).wrapping_sub(1);
+
+    loop {
+        let v = rng.next_u64() as u128 * range as u128;
+        let (hi, lo) = ((v >> 64) as u64, v as u64);
+        if lo <= zone {
+            return hi;
+        }
+    }
+}
+
+/// Fisher-Yates shuffle, swapping each position (including the last) with a uniformly random
+/// position at or after it.
+pub fn shuffle<T, R: RngCore + ?Sized>(rng: &mut R, slice: &mut [T]) {
+    let len = slice.len();
+    for a in 0..len {
+        let b = a + gen_below(rng, (len - a) as u64) as usize;
+        slice.swap(a, b);
+    }
+}
+
+/// The SplitMix64 generator, for deriving reproducible permutations from a 64-bit seed without
+/// depending on an RNG from another crate.
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    /// The SplitMix64 output function, a bijective mixer of 64-bit integers.
+    pub fn mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        Self::mix(self.0)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_gen_below() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let values = [1, 2, 3, 10, 1000, 1 << 40, u64::MAX].map(|range| gen_below(&mut rng, range));
+
+        assert_eq!(
+            values,
+            [0, 1, 1, 5, 855, 1010295069246, 2108416074180405843]
+        );
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut values = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        shuffle(&mut rng, &mut values);
+
+        assert_eq!(values, [6, 9, 7, 0, 8, 5, 2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn test_splitmix64() {
+        // The reference output for seed 1234567.
+        let mut rng = SplitMix64(1234567);
+        let values = [(); 3].map(|()| rng.next_u64());
+
+        assert_eq!(
+            values,
+            [
+                6457827717110365317,
+                3203168211198807973,
+                9817491932198370423
+            ]
+        );
+    }
+}
@@ -0,0 +1,756 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, Index, IndexMut},
+};
+#[cfg(feature = "mmap")]
+use std::{ops::Range, sync::Arc};
+
+use rand::Rng;
+use smallvec::SmallVec;
+
+#[cfg(feature = "mmap")]
+use crate::mmap::MappedFile;
+use crate::{
+    crt::{self, CrtBasis},
+    modmath, rng, InvertiblePermutation, Permutation, PhiloxPermutation,
+};
+
+/// The buffer holding the tables of a `SubPerms`. With the `mmap` feature, this can also be a
+/// range of a memory-mapped file, which is copied into a `Vec` the first time it is modified.
+#[derive(Clone)]
+enum Tables {
+    Owned(Vec<u64>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<MappedFile>, Range<usize>),
+}
+
+impl Tables {
+    fn to_mut(&mut self) -> &mut Vec<u64> {
+        match self {
+            Self::Owned(tables) => tables,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(..) => {
+                *self = Self::Owned(self.to_vec());
+                self.to_mut()
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Self::Owned(tables) => tables.clear(),
+            #[cfg(feature = "mmap")]
+            Self::Mapped(..) => *self = Self::Owned(Vec::new()),
+        }
+    }
+}
+
+impl Deref for Tables {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        match self {
+            Self::Owned(tables) => tables,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(file, range) => &file.words()[range.clone()],
+        }
+    }
+}
+
+impl fmt::Debug for Tables {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl PartialEq for Tables {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Tables {}
+
+/// The tables of a `Round`, stored contiguously in a single buffer so that evaluating the round
+/// doesn't chase a pointer per table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubPerms {
+    tables: Tables,
+    // Table `i` is `tables[offsets[i]..offsets[i + 1]]`. Implicit components have empty tables.
+    offsets: Vec<usize>,
+    // The components that are too large to tabulate, with their indices.
+    implicit: SmallVec<[(usize, ImplicitPerm); 1]>,
+}
+
+/// A table-free bijection or its inverse, for components too large to tabulate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImplicitPerm {
+    pub perm: PhiloxPermutation,
+    pub inverted: bool,
+}
+
+impl From<PhiloxPermutation> for ImplicitPerm {
+    fn from(perm: PhiloxPermutation) -> Self {
+        Self {
+            perm,
+            inverted: false,
+        }
+    }
+}
+
+/// One component of a `Round`, which permutes the residues modulo one prime power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubPerm<'a> {
+    Table(&'a [u64]),
+    Implicit(&'a ImplicitPerm),
+}
+
+impl<'a> SubPerm<'a> {
+    pub fn len(self) -> u64 {
+        match self {
+            Self::Table(table) => table.len() as u64,
+            Self::Implicit(perm) => perm.perm.num_points(),
+        }
+    }
+
+    pub fn get(self, r: u64) -> u64 {
+        match self {
+            Self::Table(table) => table[r as usize],
+            Self::Implicit(&ImplicitPerm {
+                perm,
+                inverted: false,
+            }) => perm.nth(r).unwrap(),
+            Self::Implicit(&ImplicitPerm {
+                perm,
+                inverted: true,
+            }) => perm.preimage(r).unwrap(),
+        }
+    }
+
+    pub fn position(self, a: u64) -> u64 {
+        match self {
+            Self::Table(table) => table.iter().position(|&b| b == a).unwrap() as u64,
+            Self::Implicit(&ImplicitPerm {
+                perm,
+                inverted: false,
+            }) => perm.preimage(a).unwrap(),
+            Self::Implicit(&ImplicitPerm {
+                perm,
+                inverted: true,
+            }) => perm.nth(a).unwrap(),
+        }
+    }
+
+    pub fn table(self) -> Option<&'a [u64]> {
+        match self {
+            Self::Table(table) => Some(table),
+            Self::Implicit(_) => None,
+        }
+    }
+}
+
+impl SubPerms {
+    pub fn new() -> Self {
+        Self {
+            tables: Tables::Owned(Vec::new()),
+            offsets: vec![0],
+            implicit: SmallVec::new(),
+        }
+    }
+
+    /// An empty `SubPerms` whose tables will be read from `file`, starting at word `start`.
+    #[cfg(feature = "mmap")]
+    pub fn mapped(file: Arc<MappedFile>, start: usize) -> Self {
+        Self {
+            tables: Tables::Mapped(file, start..start),
+            ..Self::new()
+        }
+    }
+
+    /// Appends the next `len` words of the mapped file as a table. Returns `None` if the file is
+    /// too short.
+    #[cfg(feature = "mmap")]
+    pub fn push_mapped(&mut self, len: usize) -> Option<()> {
+        let Tables::Mapped(file, range) = &mut self.tables else {
+            panic!("tables aren't mapped");
+        };
+
+        let end = range.end.checked_add(len)?;
+        if end > file.words().len() {
+            return None;
+        }
+
+        range.end = end;
+        self.offsets.push(self.tables.len());
+        Some(())
+    }
+
+    #[cfg(feature = "mmap")]
+    /// Returns all of the tables, in order.
+    pub fn buffer(&self) -> &[u64] {
+        &self.tables
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn get(&self, i: usize) -> SubPerm<'_> {
+        match self.implicit.iter().find(|(j, _)| *j == i) {
+            Some((_, perm)) => SubPerm::Implicit(perm),
+            None => SubPerm::Table(&self[i]),
+        }
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = SubPerm<'_>> + ExactSizeIterator + Clone {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// Makes room for `additional` more table entries. With the `zeroize` feature, an outgrown
+    /// buffer is wiped before it is freed, so that no copies of the tables are left behind.
+    fn reserve(&mut self, additional: usize) {
+        let tables = self.tables.to_mut();
+        if tables.capacity() - tables.len() >= additional {
+            return;
+        }
+
+        #[cfg(feature = "zeroize")]
+        {
+            let capacity = (tables.len() + additional).max(2 * tables.capacity());
+            let mut grown = Vec::with_capacity(capacity);
+            grown.extend_from_slice(tables);
+            zeroize::Zeroize::zeroize(tables);
+            *tables = grown;
+        }
+        #[cfg(not(feature = "zeroize"))]
+        tables.reserve(additional);
+    }
+
+    /// Appends a table `[0, 1, ..., len - 1]` and returns it.
+    fn push_identity(&mut self, len: u64) -> &mut [u64] {
+        self.reserve(len as usize);
+        let tables = self.tables.to_mut();
+        let start = tables.len();
+        tables.extend(0..len);
+        self.offsets.push(tables.len());
+        &mut tables[start..]
+    }
+
+    pub fn push_implicit(&mut self, perm: ImplicitPerm) {
+        self.implicit.push((self.len(), perm));
+        self.offsets.push(self.tables.len());
+    }
+
+    pub fn push(&mut self, perm: SubPerm<'_>) {
+        match perm {
+            SubPerm::Table(table) => {
+                self.reserve(table.len());
+                self.tables.to_mut().extend_from_slice(table);
+                self.offsets.push(self.tables.len());
+            }
+            SubPerm::Implicit(&perm) => self.push_implicit(perm),
+        }
+    }
+
+    /// Replaces each component with its inverse.
+    fn invert(&mut self) {
+        for i in 0..self.len() {
+            invert_table(&mut self[i]);
+        }
+        for (_, perm) in &mut self.implicit {
+            perm.inverted = !perm.inverted;
+        }
+    }
+
+    /// Removes every component, keeping the allocations.
+    fn clear(&mut self) {
+        self.tables.clear();
+        self.offsets.truncate(1);
+        self.implicit.clear();
+    }
+
+    /// Removes the components for which `f` returns `false`, moving the remaining tables down
+    /// within the same buffer.
+    fn retain<F: FnMut(SubPerm<'_>) -> bool>(&mut self, mut f: F) {
+        let keep = self.iter().map(&mut f).collect::<SmallVec<[bool; 15]>>();
+
+        let tables = self.tables.to_mut();
+        let mut offsets = vec![0];
+        let mut implicit = SmallVec::new();
+        for i in (0..keep.len()).filter(|&i| keep[i]) {
+            if let Some(&(_, perm)) = self.implicit.iter().find(|(j, _)| *j == i) {
+                implicit.push((offsets.len() - 1, perm));
+            }
+
+            let end = offsets[offsets.len() - 1];
+            tables.copy_within(self.offsets[i]..self.offsets[i + 1], end);
+            offsets.push(end + self.offsets[i + 1] - self.offsets[i]);
+        }
+
+        tables.truncate(offsets[offsets.len() - 1]);
+        self.offsets = offsets;
+        self.implicit = implicit;
+    }
+}
+
+impl From<Vec<Vec<u64>>> for SubPerms {
+    fn from(tables: Vec<Vec<u64>>) -> Self {
+        let mut offsets = vec![0];
+        offsets.extend(tables.iter().scan(0, |end, table| {
+            *end += table.len();
+            Some(*end)
+        }));
+
+        // A single table can be used as the buffer without copying it. Otherwise, the buffer is
+        // allocated once, and with the `zeroize` feature, the original tables are wiped.
+        let tables = match <[Vec<u64>; 1]>::try_from(tables) {
+            Ok([table]) => table,
+            Err(mut tables) => {
+                let mut buffer = Vec::with_capacity(offsets[offsets.len() - 1]);
+                for table in &mut tables {
+                    buffer.extend_from_slice(table);
+                    #[cfg(feature = "zeroize")]
+                    zeroize::Zeroize::zeroize(table);
+                }
+                buffer
+            }
+        };
+
+        Self {
+            tables: Tables::Owned(tables),
+            offsets,
+            implicit: SmallVec::new(),
+        }
+    }
+}
+
+impl Index<usize> for SubPerms {
+    type Output = [u64];
+
+    fn index(&self, i: usize) -> &[u64] {
+        &self.tables[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+impl IndexMut<usize> for SubPerms {
+    fn index_mut(&mut self, i: usize) -> &mut [u64] {
+        &mut self.tables.to_mut()[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+/// One CRT permutation: the index is split into mixed-radix digits with the moduli as the bases,
+/// each digit is mapped through its table, and the results are combined with the CRT.
+///
+/// If `inverted` is set, the round is evaluated the other way around: the index is split into its
+/// residues, each residue is mapped through its table, and the results are combined as mixed-radix
+/// digits. With inverted tables, this is the inverse of the original round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    pub sub_perms: SubPerms,
+    pub basis: CrtBasis,
+    pub inverted: bool,
+}
+
+impl Round {
+    /// Returns `None` if the table sizes aren't valid moduli for a `CrtBasis`.
+    pub fn new<T: Into<SubPerms>>(sub_perms: T) -> Option<Self> {
+        let sub_perms = sub_perms.into();
+        let moduli = sub_perms.iter().map(SubPerm::len).collect::<Vec<_>>();
+        let basis = CrtBasis::new(&moduli)?;
+
+        Some(Self {
+            sub_perms,
+            basis,
+            inverted: false,
+        })
+    }
+
+    /// Components with more than `max_table_len` points are represented by a `PhiloxPermutation`
+    /// instead of a table.
+    pub fn with_rng<R: Rng + ?Sized>(
+        factors: &[(u32, u8)],
+        max_table_len: u64,
+        rng: &mut R,
+    ) -> Self {
+        let components = factors
+            .iter()
+            .map(|&(p, k)| {
+                let pk = (p as u64).pow(k as u32);
+                (pk, pk > max_table_len)
+            })
+            .collect();
+
+        let mut sub_perms = SubPerms::new();
+        let basis = Self::generate(&mut sub_perms, components, rng);
+        Self {
+            sub_perms,
+            basis,
+            inverted: false,
+        }
+    }
+
+    /// Generates new tables and keys for the same components, reusing the allocations. This
+    /// consumes the RNG in the same way as `with_rng` with the factors in increasing order.
+    pub fn regenerate<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut components = self
+            .sub_perms
+            .iter()
+            .map(|perm| (perm.len(), matches!(perm, SubPerm::Implicit(_))))
+            .collect::<SmallVec<[_; 15]>>();
+        components.sort_by_cached_key(|&(pk, _)| smallest_factor(pk));
+
+        self.basis = Self::generate(&mut self.sub_perms, components, rng);
+        self.inverted = false;
+    }
+
+    // Fills `sub_perms` with the components `(p^k, implicit)` in a random order, and returns the
+    // matching basis.
+    fn generate<R: Rng + ?Sized>(
+        sub_perms: &mut SubPerms,
+        mut components: SmallVec<[(u64, bool); 15]>,
+        rng: &mut R,
+    ) -> CrtBasis {
+        rng::shuffle(rng, &mut components);
+
+        // This consumes the RNG in the same way as `shuffled_table` on each table.
+        sub_perms.clear();
+        sub_perms.reserve(
+            components
+                .iter()
+                .filter(|&&(_, implicit)| !implicit)
+                .map(|&(pk, _)| pk as usize)
+                .sum(),
+        );
+        for &(pk, implicit) in &components {
+            if implicit {
+                let perm = PhiloxPermutation::with_key(pk, rng.next_u64()).unwrap();
+                sub_perms.push_implicit(perm.into());
+            } else {
+                rng::shuffle(rng, sub_perms.push_identity(pk));
+            }
+        }
+
+        let moduli = components.iter().map(|&(pk, _)| pk).collect::<Vec<_>>();
+        CrtBasis::new(&moduli).unwrap()
+    }
+
+    /// Returns the table if the round consists of a single table, in which case the index is its
+    /// own digit and residue, so the round is just a lookup in that table.
+    pub fn single_table(&self) -> Option<&[u64]> {
+        match self.sub_perms.len() {
+            1 => self.sub_perms.get(0).table(),
+            _ => None,
+        }
+    }
+
+    pub fn single_table_mut(&mut self) -> Option<&mut [u64]> {
+        self.single_table()?;
+        Some(&mut self.sub_perms[0])
+    }
+
+    pub fn num_nontrivial(&self) -> usize {
+        self.sub_perms.iter().filter(|perm| perm.len() > 1).count()
+    }
+
+    /// Replaces the round with its inverse, without allocating.
+    pub fn invert(&mut self) {
+        self.sub_perms.invert();
+        self.inverted = !self.inverted;
+    }
+
+    pub fn nth(&self, n: u64) -> u64 {
+        if self.inverted {
+            self.residues_to_digits(n, SubPerm::get)
+        } else {
+            self.digits_to_residues(n, SubPerm::get)
+        }
+    }
+
+    // Splits `n` into mixed-radix digits, maps each digit with `f`, and combines the results with
+    // the CRT.
+    fn digits_to_residues<'a, F: Fn(SubPerm<'a>, u64) -> u64>(&'a self, mut n: u64, f: F) -> u64 {
+        let remainders = self
+            .sub_perms
+            .iter()
+            .zip(self.basis.divisors())
+            .map(|(perm, pk)| {
+                let (q, r) = pk.div_rem(n);
+                n = q;
+                f(perm, r)
+            });
+
+        self.basis.combine_iter(remainders)
+    }
+
+    // Splits `n` into residues, maps each residue with `f`, and combines the results as
+    // mixed-radix digits.
+    fn residues_to_digits<'a, F: Fn(SubPerm<'a>, u64) -> u64>(&'a self, n: u64, f: F) -> u64 {
+        let sub_perms = self.sub_perms.iter().zip(self.basis.divisors());
+        sub_perms
+            .rev()
+            .fold(0, |idx, (perm, pk)| idx * pk.get() + f(perm, pk.rem(n)))
+    }
+
+    /// Writes `self.nth(start + i)` to `out[i]`. Consecutive indices usually differ only in the
+    /// first digit, so this keeps the CRT sum `Σ perm_i[d_i] * e_i mod N` (where `e_i` is `1 mod
+    /// m_i` and `0 mod m_j` for `j != i`) and only updates the terms whose digit changed.
+    ///
+    /// Inverted rounds change every residue at each step, so they are evaluated one index at a
+    /// time.
+    pub fn fill_range(&self, start: u64, out: &mut [u64]) {
+        if self.inverted {
+            for (i, a) in (start..).zip(out) {
+                *a = self.nth(i);
+            }
+            return;
+        }
+
+        let n = self.basis.product();
+        let add = |a: u64, b: u64| if a >= n - b { a - (n - b) } else { a + b };
+        let sub = |a: u64, b: u64| if a >= b { a - b } else { a + (n - b) };
+
+        let idempotents = self
+            .basis
+            .divisors()
+            .iter()
+            .map(|pk| {
+                let cofactor = n / pk.get();
+                let inverse = modmath::mod_inverse(cofactor, pk.get()).unwrap();
+                modmath::mul_mod(cofactor, inverse, n).unwrap()
+            })
+            .collect::<SmallVec<[u64; 15]>>();
+        let term = |i: usize, digit: u64| {
+            modmath::mul_mod(self.sub_perms.get(i).get(digit), idempotents[i], n).unwrap()
+        };
+
+        let mut rest = start;
+        let mut digits = self
+            .basis
+            .divisors()
+            .iter()
+            .map(|pk| {
+                let (q, r) = pk.div_rem(rest);
+                rest = q;
+                r
+            })
+            .collect::<SmallVec<[u64; 15]>>();
+        let mut terms = (0..digits.len())
+            .map(|i| term(i, digits[i]))
+            .collect::<SmallVec<[u64; 15]>>();
+        let mut x = terms.iter().fold(0, |x, &t| add(x, t));
+
+        for a in out {
+            *a = x;
+
+            for (i, pk) in self.basis.divisors().iter().enumerate() {
+                digits[i] += 1;
+                let carry = digits[i] == pk.get();
+                if carry {
+                    digits[i] = 0;
+                }
+
+                let t = term(i, digits[i]);
+                x = add(sub(x, terms[i]), t);
+                terms[i] = t;
+
+                if !carry {
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn preimage(&self, n: u64) -> u64 {
+        if self.inverted {
+            self.digits_to_residues(n, SubPerm::position)
+        } else {
+            self.residues_to_digits(n, SubPerm::position)
+        }
+    }
+
+    pub fn canonicalize(&mut self) {
+        self.sub_perms.retain(|perm| perm.len() > 1);
+        self.basis = CrtBasis::new(&self.moduli()).unwrap();
+
+        // With at most one component, both directions split and combine the index in the same way.
+        if self.sub_perms.len() <= 1 {
+            self.inverted = false;
+        }
+    }
+
+    fn moduli(&self) -> Vec<u64> {
+        self.sub_perms.iter().map(SubPerm::len).collect()
+    }
+
+    pub fn verify(&self, num_points: u64) -> bool {
+        self.verify_moduli(num_points)
+            && self.sub_perms.iter().all(|perm| {
+                // Implicit components are bijections by construction.
+                perm.table().is_none_or(|table| {
+                    let mut seen = vec![false; table.len()];
+                    table.iter().all(|&a| {
+                        let a = a as usize;
+                        a < seen.len() && !std::mem::replace(&mut seen[a], true)
+                    })
+                })
+            })
+    }
+
+    pub fn verify_moduli(&self, num_points: u64) -> bool {
+        let moduli = self.sub_perms.iter().map(SubPerm::len);
+        let product = moduli
+            .clone()
+            .try_fold(1u64, |product, pk| product.checked_mul(pk));
+
+        product == Some(num_points)
+            && self.basis.moduli() == self.moduli()
+            && moduli
+                .clone()
+                .enumerate()
+                .all(|(i, a)| moduli.clone().skip(i + 1).all(|b| crt::gcd(a, b) == 1))
+    }
+
+    /// Evaluates the round at `i`, checking that each table entry used is in range and that the
+    /// CRT reconstruction is consistent with it. Assumes that `verify_moduli` passed.
+    pub fn nth_checked(&self, i: u64) -> Option<u64> {
+        if self.inverted {
+            // The digits are combined directly, so they only need to be in range.
+            let digits_ok = self
+                .sub_perms
+                .iter()
+                .zip(self.basis.divisors())
+                .all(|(perm, pk)| perm.get(pk.rem(i)) < pk.get());
+            return digits_ok.then(|| self.nth(i));
+        }
+
+        let mut n = i;
+        let digits_ok = self
+            .sub_perms
+            .iter()
+            .zip(self.basis.divisors())
+            .all(|(perm, pk)| {
+                let (q, r) = pk.div_rem(n);
+                n = q;
+                perm.get(r) < pk.get()
+            });
+
+        if !digits_ok {
+            return None;
+        }
+
+        let x = self.nth(i);
+        let mut n = i;
+        let consistent = self
+            .sub_perms
+            .iter()
+            .zip(self.basis.divisors())
+            .all(|(perm, pk)| {
+                let (q, r) = pk.div_rem(n);
+                n = q;
+                pk.rem(x) == perm.get(r)
+            });
+
+        consistent.then_some(x)
+    }
+}
+
+/// Inverts a permutation table in place, by following each cycle and marking the entries that
+/// have been written with the top bit.
+fn invert_table(table: &mut [u64]) {
+    const MARK: u64 = 1 << 63;
+
+    for i in 0..table.len() {
+        if table[i] & MARK != 0 {
+            continue;
+        }
+
+        let (mut prev, mut cur) = (i as u64, table[i]);
+        while cur != i as u64 {
+            let next = table[cur as usize];
+            table[cur as usize] = prev | MARK;
+            (prev, cur) = (cur, next);
+        }
+        table[i] = prev | MARK;
+    }
+
+    for a in table {
+        *a &= !MARK;
+    }
+}
+
+fn smallest_factor(n: u64) -> u64 {
+    (2..)
+        .take_while(|&d| d <= n / d)
+        .find(|&d| n.is_multiple_of(d))
+        .unwrap_or(n)
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Round {
+    fn zeroize(&mut self) {
+        // A mapped file can't be zeroized, but it isn't a private copy anyway.
+        match &mut self.sub_perms.tables {
+            Tables::Owned(tables) => tables.zeroize(),
+            #[cfg(feature = "mmap")]
+            Tables::Mapped(..) => {}
+        }
+        self.sub_perms.tables.clear();
+        self.sub_perms.offsets.truncate(1);
+        for (_, perm) in &mut self.sub_perms.implicit {
+            perm.perm.zeroize();
+        }
+        self.sub_perms.implicit.clear();
+    }
+}
+
+impl Hash for Round {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Skip trivial components, so that the hash only depends on the canonical form.
+        self.num_nontrivial().hash(state);
+        (self.inverted && self.num_nontrivial() > 1).hash(state);
+        for perm in self.sub_perms.iter().filter(|perm| perm.len() > 1) {
+            match perm {
+                SubPerm::Table(table) => table.hash(state),
+                SubPerm::Implicit(perm) => perm.hash(state),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_generate_capacity() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // The tables are allocated once, up front, so no partial copies are freed along the way.
+        let mut round = Round::with_rng(&[(2, 3), (3, 2), (5, 1), (7, 1), (11, 1)], 8, &mut rng);
+        let tables = round.sub_perms.tables.to_mut();
+        let (ptr, capacity) = (tables.as_ptr(), tables.capacity());
+        assert_eq!(capacity, 8 + 5 + 7);
+
+        round.regenerate(&mut rng);
+        let tables = round.sub_perms.tables.to_mut();
+        assert_eq!((tables.as_ptr(), tables.capacity()), (ptr, capacity));
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut sub_perms = SubPerms::from(vec![vec![0], vec![1, 0], vec![0], vec![2, 0, 1]]);
+        sub_perms.push_implicit(PhiloxPermutation::with_key(5, 0).unwrap().into());
+        let ptr = sub_perms.tables.as_ptr();
+
+        sub_perms.retain(|perm| perm.len() > 1);
+        assert_eq!(sub_perms.len(), 3);
+        assert_eq!(sub_perms.get(0).table(), Some(&[1, 0][..]));
+        assert_eq!(sub_perms.get(1).table(), Some(&[2, 0, 1][..]));
+        assert_eq!(sub_perms.get(2).len(), 5);
+        assert_eq!(sub_perms.tables.as_ptr(), ptr);
+    }
+}
@@ -0,0 +1,349 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::Rng;
+
+use crate::{Permutation, PhiloxPermutation, RandomPermutation, DEFAULT_UNIFORM_THRESHOLD};
+
+/// Yields the values `0..n` in a random order, each exactly once.
+///
+/// The order comes from a uniformly random table if `n < DEFAULT_UNIFORM_THRESHOLD`, and from a
+/// `PhiloxPermutation` otherwise, so that any prefix of it is well mixed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithoutReplacement {
+    perm: RandomPermutation,
+    idx: u64,
+}
+
+impl WithoutReplacement {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        RandomPermutation::new(n).map(Self::from)
+    }
+
+    /// Returns `None` if `n == 0`.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        mixed_permutation(n, rng).map(Self::from)
+    }
+
+    #[must_use]
+    pub fn remaining(&self) -> u64 {
+        self.perm.num_points() - self.idx
+    }
+}
+
+/// Yields the values in the order of `perm`. A prefix of a CRT permutation from `with_rng` only
+/// varies in one component, i.e. all but one of the residues of the values are the same, so this
+/// should be a permutation with several rounds or a `PhiloxPermutation` unless all the values are
+/// needed.
+impl From<RandomPermutation> for WithoutReplacement {
+    fn from(perm: RandomPermutation) -> Self {
+        Self { perm, idx: 0 }
+    }
+}
+
+impl Iterator for WithoutReplacement {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let a = self.perm.nth(self.idx)?;
+        self.idx += 1;
+        Some(a)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining()) {
+            Ok(n) => (n, Some(n)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+/// Hands out the values `0..n` in a random order, each at most once, until they run out.
+///
+/// Unlike `WithoutReplacement`, `next_value` only needs `&self`, so a single sampler can be shared
+/// between threads (e.g. in an `Arc`) and every value is still handed out at most once. The order
+/// is generated in the same way as for `WithoutReplacement`.
+#[derive(Debug)]
+pub struct UniqueSampler {
+    perm: RandomPermutation,
+    idx: AtomicU64,
+}
+
+impl UniqueSampler {
+    #[must_use]
+    pub fn new(n: u64) -> Option<Self> {
+        RandomPermutation::new(n).map(Self::from)
+    }
+
+    /// Returns `None` if `n == 0`.
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<Self> {
+        mixed_permutation(n, rng).map(Self::from)
+    }
+
+    /// Returns the next unused value, or `None` if all `n` values have been handed out.
+    pub fn next_value(&self) -> Option<u64> {
+        let n = self.perm.num_points();
+        let idx = self
+            .idx
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |i| {
+                (i < n).then_some(i + 1)
+            })
+            .ok()?;
+
+        self.perm.nth(idx)
+    }
+
+    #[must_use]
+    pub fn remaining(&self) -> u64 {
+        self.perm.num_points() - self.idx.load(Ordering::Relaxed)
+    }
+
+    #[must_use]
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+impl Clone for UniqueSampler {
+    fn clone(&self) -> Self {
+        Self {
+            perm: self.perm.clone(),
+            idx: AtomicU64::new(self.idx.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Hands out the values in the order of `perm`. See `From<RandomPermutation>` for
+/// `WithoutReplacement`.
+impl From<RandomPermutation> for UniqueSampler {
+    fn from(perm: RandomPermutation) -> Self {
+        Self {
+            perm,
+            idx: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Randomly samples `amount` distinct values from `0..length`, in random order.
+///
+/// This has the same shape as `rand::seq::index::sample`, but the memory usage doesn't grow with
+/// `length`: the values are a prefix of a `PhiloxPermutation` of `0..length`.
+///
+/// # Panics
+///
+/// Panics if `amount > length`.
+pub fn sample<R: Rng + ?Sized>(rng: &mut R, length: u64, amount: usize) -> Vec<u64> {
+    assert!(
+        amount as u64 <= length,
+        "`amount` of samples must be less than or equal to `length`"
+    );
+
+    // A prefix of a CRT permutation would only vary in one residue, so this uses a Feistel network.
+    match PhiloxPermutation::with_rng(length, rng) {
+        Some(perm) => perm.iter().take(amount).collect(),
+        None => Vec::new(),
+    }
+}
+
+// A permutation of `0..n` whose prefixes are well mixed: a uniformly random table if it is small
+// enough, and a `PhiloxPermutation` otherwise.
+fn mixed_permutation<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Option<RandomPermutation> {
+    if n < DEFAULT_UNIFORM_THRESHOLD {
+        RandomPermutation::hybrid_with_rng(n, DEFAULT_UNIFORM_THRESHOLD, rng)
+    } else {
+        PhiloxPermutation::with_rng(n, rng).map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    // Checks that the values don't all have the same residue mod each of `moduli`, as they would
+    // for a prefix of a CRT permutation.
+    fn assert_mixed(values: &[u64], moduli: &[u64]) {
+        for &m in moduli {
+            assert!(
+                values.iter().any(|&v| v % m != values[0] % m),
+                "all residues mod {m} are equal"
+            );
+        }
+    }
+
+    const SMALL: u64 = 8 * 9 * 25 * 49;
+    const LARGE: u64 = 9 * 25 * 49 * 11 * 13;
+    const MODULI: [u64; 7] = [8, 9, 25, 49, 11, 13, 100];
+
+    mod without_replacement {
+        use super::*;
+
+        #[test]
+        fn test_unique() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let sampler = WithoutReplacement::with_rng(1000, &mut rng).unwrap();
+
+            let values = sampler.collect::<HashSet<_>>();
+            assert_eq!(values.len(), 1000);
+            assert!(values.iter().all(|&v| v < 1000));
+        }
+
+        #[test]
+        fn test_remaining() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut sampler = WithoutReplacement::with_rng(10, &mut rng).unwrap();
+
+            assert_eq!(sampler.remaining(), 10);
+            assert_eq!(sampler.size_hint(), (10, Some(10)));
+            sampler.by_ref().take(4).for_each(drop);
+            assert_eq!(sampler.remaining(), 6);
+            assert_eq!(sampler.by_ref().count(), 6);
+            assert_eq!(sampler.next(), None);
+        }
+
+        #[test]
+        fn test_mixed() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+            for n in [SMALL, LARGE, 1 << 40] {
+                for _ in 0..10 {
+                    let values = WithoutReplacement::with_rng(n, &mut rng)
+                        .unwrap()
+                        .take(20)
+                        .collect::<Vec<_>>();
+                    assert_mixed(&values, &MODULI);
+                }
+            }
+            assert!(WithoutReplacement::with_rng(0, &mut rng).is_none());
+            assert_eq!(
+                WithoutReplacement::with_rng(1009, &mut rng)
+                    .unwrap()
+                    .count(),
+                1009
+            );
+        }
+
+        #[test]
+        fn test_matches_permutation() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let p = RandomPermutation::with_rng(300, &mut rng).unwrap();
+            let sampler = WithoutReplacement::from(p.clone());
+
+            assert!(sampler.eq(p.iter()));
+        }
+    }
+
+    mod unique_sampler {
+        use super::*;
+
+        #[test]
+        fn test_next_value() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let p = RandomPermutation::with_rng(100, &mut rng).unwrap();
+            let sampler = UniqueSampler::from(p.clone());
+
+            assert_eq!(sampler.remaining(), 100);
+            let values = std::iter::from_fn(|| sampler.next_value()).collect::<Vec<_>>();
+            assert!(values.into_iter().eq(p.iter()));
+
+            assert!(sampler.is_exhausted());
+            assert_eq!(sampler.next_value(), None);
+            assert_eq!(sampler.remaining(), 0);
+        }
+
+        #[test]
+        fn test_clone() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let sampler = UniqueSampler::with_rng(100, &mut rng).unwrap();
+            sampler.next_value();
+
+            let clone = sampler.clone();
+            assert_eq!(clone.remaining(), 99);
+            assert_eq!(clone.next_value(), sampler.next_value());
+        }
+
+        #[test]
+        fn test_mixed() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+            for n in [SMALL, LARGE, 1 << 40] {
+                for _ in 0..10 {
+                    let sampler = UniqueSampler::with_rng(n, &mut rng).unwrap();
+                    let values = (0..20)
+                        .map(|_| sampler.next_value().unwrap())
+                        .collect::<Vec<_>>();
+                    assert_mixed(&values, &MODULI);
+                }
+            }
+        }
+
+        #[test]
+        fn test_threads() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+            let sampler = UniqueSampler::with_rng(10000, &mut rng).unwrap();
+
+            let values = std::thread::scope(|s| {
+                let handles = (0..4)
+                    .map(|_| {
+                        s.spawn(|| std::iter::from_fn(|| sampler.next_value()).collect::<Vec<_>>())
+                    })
+                    .collect::<Vec<_>>();
+
+                handles
+                    .into_iter()
+                    .flat_map(|h| h.join().unwrap())
+                    .collect::<Vec<_>>()
+            });
+
+            assert_eq!(values.len(), 10000);
+            assert_eq!(values.into_iter().collect::<HashSet<_>>().len(), 10000);
+        }
+    }
+
+    mod sample {
+        use super::*;
+
+        #[test]
+        fn test_sample() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+            for length in [1, 10, 257, 1000003, 1 << 40, u64::MAX / 3, u64::MAX] {
+                let amount = length.min(100) as usize;
+                let values = sample(&mut rng, length, amount);
+
+                assert_eq!(values.len(), amount);
+                assert!(values.iter().all(|&v| v < length));
+                assert_eq!(values.iter().collect::<HashSet<_>>().len(), amount);
+            }
+        }
+
+        #[test]
+        fn test_mixed() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+            for length in [SMALL, LARGE, 1 << 40] {
+                for _ in 0..10 {
+                    assert_mixed(&sample(&mut rng, length, 10), &MODULI);
+                }
+            }
+            assert_eq!(sample(&mut rng, 0, 0), [] as [u64; 0]);
+        }
+
+        #[test]
+        fn test_sample_all() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+            let mut values = sample(&mut rng, 1009, 1009);
+            values.sort();
+
+            assert!(values.into_iter().eq(0..1009));
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_sample_too_many() {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+            sample(&mut rng, 10, 11);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use rand::Rng;
+
+use crate::{
+    Inversed, InvertiblePermutation, Permutation, PermutationExt, RandomPermutation,
+    DEFAULT_UNIFORM_THRESHOLD,
+};
+
+/// The dimensions of a multi-dimensional grid, whose points are numbered in row-major order (the
+/// last coordinate changes fastest).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Shape {
+    dims: Vec<u64>,
+    len: u64,
+}
+
+impl Shape {
+    /// Returns `None` if the number of points doesn't fit in a `u64`.
+    #[must_use]
+    pub fn new<T: Into<Vec<u64>>>(dims: T) -> Option<Self> {
+        let dims = dims.into();
+        let len = dims.iter().try_fold(1u64, |len, &d| len.checked_mul(d))?;
+        Some(Self { dims, len })
+    }
+
+    #[must_use]
+    pub fn dims(&self) -> &[u64] {
+        &self.dims
+    }
+
+    /// Returns the number of points in the grid.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the row-major index of `coords`, or `None` if it has the wrong number of
+    /// coordinates or any of them is out of range.
+    #[must_use]
+    pub fn flatten(&self, coords: &[u64]) -> Option<u64> {
+        if coords.len() != self.dims.len() {
+            return None;
+        }
+
+        coords
+            .iter()
+            .zip(&self.dims)
+            .try_fold(0, |idx, (&c, &d)| (c < d).then_some(idx * d + c))
+    }
+
+    /// Returns the coordinates of the point with row-major index `idx`, or `None` if
+    /// `idx >= len`.
+    #[must_use]
+    pub fn unflatten(&self, mut idx: u64) -> Option<Vec<u64>> {
+        if idx >= self.len {
+            return None;
+        }
+
+        let mut coords = vec![0; self.dims.len()];
+        for (c, &d) in coords.iter_mut().zip(&self.dims).rev() {
+            *c = idx % d;
+            idx /= d;
+        }
+        Some(coords)
+    }
+}
+
+/// A permutation of the points of a grid. See `PermutationExt::with_shape`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shaped<P> {
+    perm: P,
+    shape: Shape,
+}
+
+impl<P: Permutation> Shaped<P> {
+    /// Returns `None` if `shape` doesn't have `perm.num_points()` points.
+    #[must_use]
+    pub fn new(perm: P, shape: Shape) -> Option<Self> {
+        (shape.len() == perm.num_points()).then_some(Self { perm, shape })
+    }
+
+    #[must_use]
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> P {
+        self.perm
+    }
+
+    /// Returns the coordinates of the image of the point at `coords`, or `None` if `coords` isn't
+    /// a point of the grid.
+    #[must_use]
+    pub fn nth_multi(&self, coords: &[u64]) -> Option<Vec<u64>> {
+        self.shape
+            .unflatten(self.perm.nth(self.shape.flatten(coords)?)?)
+    }
+}
+
+impl<P: InvertiblePermutation> Shaped<P> {
+    /// The inverse of `nth_multi`.
+    #[must_use]
+    pub fn preimage_multi(&self, coords: &[u64]) -> Option<Vec<u64>> {
+        self.shape
+            .unflatten(self.perm.preimage(self.shape.flatten(coords)?)?)
+    }
+}
+
+impl<P: Permutation> Permutation for Shaped<P> {
+    fn num_points(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.perm.nth(n)
+    }
+}
+
+/// A permutation of the points of a grid that permutes each axis independently, and then
+/// optionally reorders the axes.
+///
+/// The point at `coords` maps to the point whose `j`th coordinate is `σ_a(coords[a])`, where
+/// `a = axis_order[j]` and `σ_a` is the permutation of axis `a`. The output grid has dimensions
+/// `dims[axis_order[j]]`, so it only has the same shape as the input grid if the axis order is the
+/// identity or only reorders axes of equal length. As a `Permutation`, points of the input grid
+/// are numbered in row-major order, and so are points of the output grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AxesPermutation<P = RandomPermutation> {
+    axes: Vec<P>,
+    axis_order: Vec<usize>,
+    input_shape: Shape,
+    output_shape: Shape,
+}
+
+impl AxesPermutation {
+    #[must_use]
+    pub fn new(shape: &Shape) -> Option<Self> {
+        Self::with_rng(shape, &mut rand::thread_rng())
+    }
+
+    /// Generates an independent permutation of each axis, as in `RandomPermutation::new_hybrid`.
+    /// Returns `None` if any dimension is zero or isn't supported.
+    pub fn with_rng<R: Rng + ?Sized>(shape: &Shape, rng: &mut R) -> Option<Self> {
+        let axes = shape
+            .dims()
+            .iter()
+            .map(|&d| RandomPermutation::hybrid_with_rng(d, DEFAULT_UNIFORM_THRESHOLD, rng))
+            .collect::<Option<_>>()?;
+        Self::from_axes(axes)
+    }
+}
+
+impl<P: Permutation> AxesPermutation<P> {
+    /// Permutes axis `i` of a grid with dimensions `axes[i].num_points()` by `axes[i]`. Returns
+    /// `None` if the number of points doesn't fit in a `u64`.
+    #[must_use]
+    pub fn from_axes(axes: Vec<P>) -> Option<Self> {
+        let shape = Shape::new(axes.iter().map(P::num_points).collect::<Vec<_>>())?;
+        Some(Self {
+            axis_order: (0..axes.len()).collect(),
+            axes,
+            input_shape: shape.clone(),
+            output_shape: shape,
+        })
+    }
+
+    /// Reorders the axes of the output, so that output axis `j` is input axis `axis_order[j]`.
+    /// Returns `None` if `axis_order` isn't a permutation of the axes.
+    #[must_use]
+    pub fn with_axis_order<T: Into<Vec<usize>>>(mut self, axis_order: T) -> Option<Self> {
+        let axis_order = axis_order.into();
+        let mut seen = vec![false; self.axes.len()];
+        if axis_order.len() != self.axes.len()
+            || !axis_order
+                .iter()
+                .all(|&a| a < seen.len() && !std::mem::replace(&mut seen[a], true))
+        {
+            return None;
+        }
+
+        let dims = self.input_shape.dims();
+        self.output_shape = Shape::new(axis_order.iter().map(|&a| dims[a]).collect::<Vec<_>>())?;
+        self.axis_order = axis_order;
+        Some(self)
+    }
+
+    #[must_use]
+    pub fn input_shape(&self) -> &Shape {
+        &self.input_shape
+    }
+
+    #[must_use]
+    pub fn output_shape(&self) -> &Shape {
+        &self.output_shape
+    }
+
+    /// Returns the permutation of each input axis.
+    #[must_use]
+    pub fn axes(&self) -> &[P] {
+        &self.axes
+    }
+
+    #[must_use]
+    pub fn axis_order(&self) -> &[usize] {
+        &self.axis_order
+    }
+
+    #[must_use]
+    pub fn into_axes(self) -> Vec<P> {
+        self.axes
+    }
+
+    /// Returns the coordinates in the output grid of the image of the point at `coords`, or
+    /// `None` if `coords` isn't a point of the input grid.
+    #[must_use]
+    pub fn nth_multi(&self, coords: &[u64]) -> Option<Vec<u64>> {
+        if coords.len() != self.axes.len() {
+            return None;
+        }
+
+        self.axis_order
+            .iter()
+            .map(|&a| self.axes[a].nth(coords[a]))
+            .collect()
+    }
+}
+
+impl<P: InvertiblePermutation> AxesPermutation<P> {
+    /// The inverse of `nth_multi`, taking coordinates in the output grid.
+    #[must_use]
+    pub fn preimage_multi(&self, coords: &[u64]) -> Option<Vec<u64>> {
+        if coords.len() != self.axes.len() {
+            return None;
+        }
+
+        let mut preimage = vec![0; coords.len()];
+        for (&c, &a) in coords.iter().zip(&self.axis_order) {
+            preimage[a] = self.axes[a].preimage(c)?;
+        }
+        Some(preimage)
+    }
+}
+
+impl<P: Permutation> Permutation for AxesPermutation<P> {
+    fn num_points(&self) -> u64 {
+        self.input_shape.len()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        let coords = self.nth_multi(&self.input_shape.unflatten(n)?)?;
+        self.output_shape.flatten(&coords)
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for AxesPermutation<P> {
+    type Inverse<'a>
+        = Inversed<&'a Self>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        let coords = self.preimage_multi(&self.output_shape.unflatten(x)?)?;
+        self.input_shape.flatten(&coords)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{PermutationExt, RandomPermutation};
+
+    #[test]
+    fn test_shape() {
+        let shape = Shape::new([3, 4, 5]).unwrap();
+        assert_eq!(shape.len(), 60);
+        assert_eq!(shape.flatten(&[1, 2, 3]), Some(33));
+        assert_eq!(shape.unflatten(33), Some(vec![1, 2, 3]));
+        assert_eq!(shape.flatten(&[3, 0, 0]), None);
+        assert_eq!(shape.flatten(&[1, 2]), None);
+        assert_eq!(shape.unflatten(60), None);
+
+        assert!((0..60).all(|i| shape.flatten(&shape.unflatten(i).unwrap()) == Some(i)));
+
+        assert_eq!(Shape::new([]).unwrap().len(), 1);
+        assert!(Shape::new([5, 0]).unwrap().is_empty());
+        assert_eq!(Shape::new([1 << 32, 1 << 32]), None);
+    }
+
+    #[test]
+    fn test_nth_multi() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = RandomPermutation::with_rng(60, &mut rng).unwrap();
+        let shape = Shape::new([3, 4, 5]).unwrap();
+        let q = (&p).with_shape(shape.clone()).unwrap();
+
+        for i in 0..60 {
+            let coords = shape.unflatten(i).unwrap();
+            let image = q.nth_multi(&coords).unwrap();
+            assert_eq!(shape.flatten(&image), p.nth(i));
+            assert_eq!(q.preimage_multi(&image), Some(coords));
+        }
+
+        assert_eq!(q.nth_multi(&[0, 4, 0]), None);
+        assert!((&p).with_shape(Shape::new([7, 8]).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_axes() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let shape = Shape::new([3, 4, 1009]).unwrap();
+        let p = AxesPermutation::with_rng(&shape, &mut rng).unwrap();
+        assert_eq!(p.num_points(), shape.len());
+        assert_eq!(p.output_shape(), &shape);
+
+        let mut values = p.iter().collect::<Vec<_>>();
+        values.sort();
+        assert!(values.into_iter().eq(0..shape.len()));
+
+        for i in (0..shape.len()).step_by(37) {
+            let coords = shape.unflatten(i).unwrap();
+            let image = p.nth_multi(&coords).unwrap();
+            for (a, (&c, &d)) in coords.iter().zip(&image).enumerate() {
+                assert_eq!(p.axes()[a].nth(c), Some(d));
+            }
+            assert_eq!(shape.flatten(&image), p.nth(i));
+            assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+        }
+
+        // Points in the same row along the last axis stay in the same row.
+        let row = |i| p.nth(i).unwrap() / 1009;
+        assert!((0..1009).all(|i| row(i) == row(0)));
+    }
+
+    #[test]
+    fn test_axis_order() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = AxesPermutation::with_rng(&Shape::new([2, 3, 5]).unwrap(), &mut rng)
+            .unwrap()
+            .with_axis_order([2, 0, 1])
+            .unwrap();
+        assert_eq!(p.output_shape().dims(), [5, 2, 3]);
+        assert_eq!(p.axis_order(), [2, 0, 1]);
+
+        let mut values = p.iter().collect::<Vec<_>>();
+        values.sort();
+        assert!(values.into_iter().eq(0..30));
+
+        let image = p.nth_multi(&[1, 2, 4]).unwrap();
+        let axes = p.axes();
+        assert_eq!(
+            image,
+            [axes[2].nth(4), axes[0].nth(1), axes[1].nth(2)].map(Option::unwrap)
+        );
+        assert_eq!(p.preimage_multi(&image), Some(vec![1, 2, 4]));
+        for i in 0..30 {
+            assert_eq!(p.preimage(p.nth(i).unwrap()), Some(i));
+        }
+
+        let p = AxesPermutation::with_rng(&Shape::new([2, 3]).unwrap(), &mut rng).unwrap();
+        assert!(p.clone().with_axis_order([0]).is_none());
+        assert!(p.clone().with_axis_order([1, 1]).is_none());
+        assert!(p.with_axis_order([0, 2]).is_none());
+    }
+
+    #[test]
+    fn test_axes_invalid() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        assert!(AxesPermutation::with_rng(&Shape::new([3, 0]).unwrap(), &mut rng).is_none());
+
+        let p = AxesPermutation::with_rng(&Shape::new([3, 4]).unwrap(), &mut rng).unwrap();
+        assert_eq!(p.nth_multi(&[3, 0]), None);
+        assert_eq!(p.nth_multi(&[0]), None);
+        assert_eq!(p.preimage_multi(&[0, 4]), None);
+        assert_eq!(p.nth(12), None);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::Permutation;
+
+/// Rearranges `slice` so that `slice[i]` becomes the old value of `slice[perm.nth(i)]`.
+///
+/// Unlike `SliceRandom::shuffle`, the reordering only depends on `perm`, so it can be replayed
+/// later by regenerating the same permutation, e.g. from `n` and a seed. This follows the cycles
+/// of `perm` with swaps, so it takes O(n) time and uses n bits of extra memory.
+///
+/// # Panics
+///
+/// Panics if `slice.len()` isn't `perm.num_points()`.
+pub fn shuffle_slice<T, P: Permutation>(slice: &mut [T], perm: &P) {
+    let n = slice.len();
+    assert_eq!(
+        n as u64,
+        perm.num_points(),
+        "slice of length {n} can't be shuffled by a permutation of {} points",
+        perm.num_points()
+    );
+
+    let mut seen = vec![0u64; n.div_ceil(64)];
+    for i in 0..n {
+        if seen[i / 64] >> (i % 64) & 1 == 1 {
+            continue;
+        }
+
+        // After swapping `j` with `σ(j)`, `slice[j]` is correct and `slice[σ(j)]` holds the old
+        // value of `slice[i]`, which belongs at the last point of the cycle.
+        let mut j = i;
+        loop {
+            seen[j / 64] |= 1 << (j % 64);
+            let k = perm.nth_usize(j).unwrap();
+            if k == i {
+                break;
+            }
+            slice.swap(j, k);
+            j = k;
+        }
+    }
+}
+
+/// Treats `bytes` as consecutive blocks of `block_size` bytes, and rearranges them like
+/// `shuffle_slice`, so that block `i` becomes the old block `perm.nth(i)`. Each cycle of `perm` is
+/// followed by moving blocks along it, so apart from the visited bits this only needs a single
+/// block of scratch space.
+///
+/// # Panics
+///
+/// Panics if `block_size` is zero, or if `bytes` isn't exactly `perm.num_points()` blocks long.
+pub fn shuffle_blocks<P: Permutation>(bytes: &mut [u8], block_size: usize, perm: &P) {
+    assert!(block_size != 0, "block size is zero");
+    assert!(
+        bytes.len().is_multiple_of(block_size)
+            && (bytes.len() / block_size) as u64 == perm.num_points(),
+        "{} bytes can't be shuffled as {} blocks of {block_size} bytes",
+        bytes.len(),
+        perm.num_points()
+    );
+
+    let n = bytes.len() / block_size;
+    let block = |i: usize| i * block_size..(i + 1) * block_size;
+
+    let mut scratch = vec![0u8; block_size];
+    let mut seen = vec![0u64; n.div_ceil(64)];
+    for i in 0..n {
+        if seen[i / 64] >> (i % 64) & 1 == 1 {
+            continue;
+        }
+
+        // Save block `i`, then move each block back along the cycle, so that the saved block
+        // ends up at the last point of the cycle.
+        scratch.copy_from_slice(&bytes[block(i)]);
+        let mut j = i;
+        loop {
+            seen[j / 64] |= 1 << (j % 64);
+            let k = perm.nth_usize(j).unwrap();
+            if k == i {
+                bytes[block(j)].copy_from_slice(&scratch);
+                break;
+            }
+            bytes.copy_within(block(k), j * block_size);
+            j = k;
+        }
+    }
+}
+
+/// Extension trait mirroring `SliceRandom::shuffle`, but driven by a permutation instead of an RNG.
+pub trait SliceShuffle {
+    /// See `shuffle_slice`.
+    fn shuffle_by<P: Permutation>(&mut self, perm: &P);
+}
+
+impl<T> SliceShuffle for [T] {
+    fn shuffle_by<P: Permutation>(&mut self, perm: &P) {
+        shuffle_slice(self, perm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{RandomPermutation, UniformPermutation};
+
+    #[test]
+    fn test_shuffle_slice() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 2, 1009, 3600] {
+            let p = RandomPermutation::hybrid_with_rng(n, 1024, &mut rng).unwrap();
+            let mut slice = (0..n).map(|i| i.to_string()).collect::<Vec<_>>();
+            slice.shuffle_by(&p);
+
+            assert!(slice.iter().zip(p.iter()).all(|(s, a)| *s == a.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let shuffled = |seed| {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+            let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+            let mut slice = (0..360).collect::<Vec<_>>();
+            shuffle_slice(&mut slice, &p);
+            slice
+        };
+
+        assert_eq!(shuffled(1), shuffled(1));
+        assert_ne!(shuffled(1), shuffled(2));
+    }
+
+    #[test]
+    fn test_shuffle_blocks() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for (n, block_size) in [(1, 3), (2, 1), (360, 7), (1009, 16)] {
+            let p = RandomPermutation::hybrid_with_rng(n, 1024, &mut rng).unwrap();
+            // Each block starts with the bytes of its index, so the blocks are all distinct.
+            let mut bytes = (0..n)
+                .flat_map(|b| b.to_le_bytes().into_iter().cycle().take(block_size))
+                .collect::<Vec<_>>();
+            let mut blocks = bytes
+                .chunks(block_size)
+                .map(<[u8]>::to_vec)
+                .collect::<Vec<_>>();
+            assert_eq!(blocks.iter().collect::<HashSet<_>>().len(), n as usize);
+
+            shuffle_blocks(&mut bytes, block_size, &p);
+            blocks.shuffle_by(&p);
+            assert_eq!(bytes, blocks.concat());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_shuffle_blocks_wrong_length() {
+        let p = UniformPermutation::try_from(vec![1, 0]).unwrap();
+        shuffle_blocks(&mut [0; 5], 2, &p);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_shuffle_blocks_zero_size() {
+        let p = UniformPermutation::try_from(vec![]).unwrap();
+        shuffle_blocks(&mut [], 0, &p);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut slice: [u8; 0] = [];
+        slice.shuffle_by(&UniformPermutation::try_from(vec![]).unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wrong_length() {
+        let p = UniformPermutation::try_from(vec![1, 0]).unwrap();
+        shuffle_slice(&mut [0, 1, 2], &p);
+    }
+}
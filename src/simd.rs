@@ -0,0 +1,105 @@
+use std::simd::{cmp::SimdPartialOrd, num::SimdUint, Simd};
+
+use smallvec::SmallVec;
+
+use crate::{
+    round::{Round, SubPerm},
+    RandomPermutation,
+};
+
+impl Round {
+    pub fn nth_simd<const L: usize>(&self, mut n: Simd<u64, L>) -> Simd<u64, L> {
+        let lookup = |perm: SubPerm<'_>, r: Simd<u64, L>| match perm {
+            SubPerm::Table(table) => Simd::gather_or_default(table, r.cast()),
+            SubPerm::Implicit(_) => Simd::from_array(r.to_array().map(|r| perm.get(r))),
+        };
+
+        if self.inverted {
+            let digits = self
+                .sub_perms
+                .iter()
+                .zip(self.basis.divisors())
+                .map(|(perm, pk)| lookup(perm, pk.div_rem_simd(n).1))
+                .collect::<SmallVec<[_; 15]>>();
+
+            return digits
+                .into_iter()
+                .zip(self.basis.divisors())
+                .rev()
+                .fold(Simd::splat(0), |idx, (digit, pk)| {
+                    idx * Simd::splat(pk.get()) + digit
+                });
+        }
+
+        let remainders = self
+            .sub_perms
+            .iter()
+            .zip(self.basis.divisors())
+            .map(|(perm, pk)| {
+                let (q, r) = pk.div_rem_simd(n);
+                n = q;
+                lookup(perm, r)
+            })
+            .collect::<SmallVec<[_; 15]>>();
+
+        self.basis.combine_simd(remainders)
+    }
+}
+
+impl RandomPermutation {
+    /// Evaluates the permutation at `L` indices at once, or returns `None` if any of them is out of
+    /// range. This gives the same results as calling `nth` on each lane.
+    #[must_use]
+    pub fn nth_simd<const L: usize>(&self, n: Simd<u64, L>) -> Option<Simd<u64, L>> {
+        if n.simd_ge(Simd::splat(self.num_points)).any() {
+            return None;
+        }
+
+        Some(self.rounds.iter().fold(n, |n, round| round.nth_simd(n)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::simd::{u64x4, u64x8};
+
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{Permutation, UniformPermutation};
+
+    fn check(p: &RandomPermutation, rng: &mut Xoshiro256StarStar) {
+        let n = p.num_points();
+        for _ in 0..100 {
+            let idx = u64x8::from_array([(); 8].map(|()| rng.gen_range(0..n)));
+            let expected = idx.to_array().map(|i| p.nth(i).unwrap());
+            assert_eq!(p.nth_simd(idx), Some(u64x8::from_array(expected)));
+        }
+    }
+
+    #[test]
+    fn test_nth_simd() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 360, 3113510400, (1..=20).product()] {
+            let p = RandomPermutation::with_rng(n, &mut rng).unwrap();
+            check(&p, &mut rng);
+        }
+
+        let p = RandomPermutation::with_rng_rounds(3628800, 3, &mut rng).unwrap();
+        check(&p, &mut rng);
+
+        let p = RandomPermutation::from(UniformPermutation::with_rng(1009, &mut rng));
+        check(&p, &mut rng);
+    }
+
+    #[test]
+    fn test_nth_simd_out_of_range() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = RandomPermutation::with_rng(360, &mut rng).unwrap();
+
+        assert_eq!(p.nth_simd(u64x4::from_array([0, 1, 359, 360])), None);
+        assert!(p.nth_simd(u64x4::from_array([0, 1, 358, 359])).is_some());
+    }
+}
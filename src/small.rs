@@ -0,0 +1,184 @@
+use rand::Rng;
+
+use crate::{rng, uniform, FromVecError, InvertiblePermutation, Mode, Permutation};
+
+/// A uniformly random permutation of `0..N`, stored inline as an explicit table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Vec<u64>", into = "Vec<u64>")
+)]
+pub struct SmallPermutation<const N: usize> {
+    mapping: [u64; N],
+}
+
+// There is no `Default`, since it would be ambiguous between `new`, which is random like the `new` of
+// every other permutation, and `identity`.
+#[allow(clippy::new_without_default)]
+impl<const N: usize> SmallPermutation<N> {
+    /// Returns a uniformly random permutation. Use `identity` for the identity permutation.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut mapping = Self::identity().mapping;
+        rng::shuffle(rng, &mut mapping);
+
+        Self { mapping }
+    }
+
+    #[must_use]
+    pub fn identity() -> Self {
+        let mut mapping = [0; N];
+        for (i, x) in mapping.iter_mut().enumerate() {
+            *x = i as u64;
+        }
+
+        Self { mapping }
+    }
+
+    #[must_use]
+    pub fn inverse(&self) -> Self {
+        let mut mapping = [0; N];
+        for (i, &x) in self.mapping.iter().enumerate() {
+            mapping[x as usize] = i as u64;
+        }
+
+        Self { mapping }
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> Mode {
+        Mode::Uniform
+    }
+
+    #[must_use]
+    pub fn as_array(&self) -> &[u64; N] {
+        &self.mapping
+    }
+}
+
+impl<const N: usize> From<SmallPermutation<N>> for Vec<u64> {
+    fn from(perm: SmallPermutation<N>) -> Self {
+        perm.mapping.to_vec()
+    }
+}
+
+/// `SmallPermutation` is `Copy`, so it can't be zeroized on drop.
+#[cfg(feature = "zeroize")]
+impl<const N: usize> zeroize::Zeroize for SmallPermutation<N> {
+    fn zeroize(&mut self) {
+        self.mapping.zeroize();
+    }
+}
+
+/// Checks that there are exactly `N` values, and that they are a bijection on `0..N`.
+impl<const N: usize> TryFrom<Vec<u64>> for SmallPermutation<N> {
+    type Error = FromVecError;
+
+    fn try_from(mapping: Vec<u64>) -> Result<Self, Self::Error> {
+        let found = mapping.len();
+        let mapping = <[u64; N]>::try_from(mapping)
+            .map_err(|_| FromVecError::WrongLength { expected: N, found })?;
+        uniform::validate(&mapping)?;
+
+        Ok(Self { mapping })
+    }
+}
+
+impl<const N: usize> Permutation for SmallPermutation<N> {
+    fn num_points(&self) -> u64 {
+        N as u64
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.mapping.get(usize::try_from(n).ok()?).copied()
+    }
+}
+
+impl<const N: usize> InvertiblePermutation for SmallPermutation<N> {
+    type Inverse<'a> = Self;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.mapping.iter().position(|&a| a == x).map(|i| i as u64)
+    }
+
+    fn inverse(&self) -> Self {
+        Self::inverse(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_with_rng() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p = SmallPermutation::<40>::with_rng(&mut rng);
+
+        let mut vec = p.iter().collect::<Vec<_>>();
+        vec.sort();
+
+        assert!(vec.into_iter().eq(0..40));
+        assert_eq!(p.nth(40), None);
+        assert_eq!(p.nth(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = SmallPermutation::<24>::with_rng(&mut rng);
+        let inv = p.inverse();
+
+        for i in 0..24 {
+            assert_eq!(inv.nth(p.nth(i).unwrap()), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let p = SmallPermutation::<0>::new();
+        assert_eq!(p.num_points(), 0);
+        assert_eq!(p.iter().next(), None);
+    }
+
+    #[test]
+    fn test_try_from() {
+        let p = SmallPermutation::<3>::try_from(vec![1, 2, 0]).unwrap();
+        assert_eq!(p.as_array(), &[1, 2, 0]);
+
+        assert_eq!(
+            SmallPermutation::<3>::try_from(vec![1, 0]),
+            Err(FromVecError::WrongLength {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            SmallPermutation::<3>::try_from(vec![1, 1, 0]),
+            Err(FromVecError::Duplicate {
+                value: 1,
+                first: 0,
+                second: 1
+            })
+        );
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let mut p = SmallPermutation::<10>::with_rng(&mut rng);
+        p.zeroize();
+        assert_eq!(p.as_array(), &[0; 10]);
+    }
+}
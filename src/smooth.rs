@@ -0,0 +1,226 @@
+use crate::PrimeSet;
+
+/// Returns the smallest integer `m >= max(n, 1)` whose prime factors are all in `primes`, or `None`
+/// if there is no such integer that fits in a `u64`.
+pub(crate) fn next_smooth(n: u64, primes: &PrimeSet) -> Option<u64> {
+    let primes = primes
+        .primes()
+        .iter()
+        .map(|&p| p as u64)
+        .collect::<Vec<_>>();
+
+    // With only a few primes, smooth integers are too far apart for sieving to be worth it.
+    let n = n.max(1);
+    let scanned = if primes.len() >= 18 {
+        match sieve(n, &primes) {
+            Ok(m) => return Some(m),
+            Err(scanned) => scanned,
+        }
+    } else {
+        0
+    };
+
+    search(n.checked_add(scanned)?, &primes)
+}
+
+fn is_smooth(mut n: u64, primes: &[u64]) -> bool {
+    for &p in primes {
+        while n.is_multiple_of(p) {
+            n /= p;
+        }
+    }
+
+    n == 1
+}
+
+const WINDOW: u64 = 1 << 16;
+const SIEVE_LIMIT: u64 = 1 << 26;
+
+// When smooth integers are dense, they are quickest to find by sieving the integers after `n` one
+// window at a time. For each integer, we add up the logarithms of the prime powers dividing it,
+// and only check the integers where this is close to their own logarithm. Returns the number of
+// integers scanned if none of them are smooth.
+fn sieve(n: u64, primes: &[u64]) -> Result<u64, u64> {
+    // Logarithms in units of 1/256 bits, rounded down. Summing at most 64 of them loses less than
+    // a quarter of a bit, but any integer that isn't smooth is at least a bit short.
+    let logs = primes
+        .iter()
+        .map(|&p| ((p as f64).log2() * 256.0) as u16)
+        .collect::<Vec<_>>();
+    let mut sums = vec![0u16; WINDOW as usize];
+
+    let mut lo = n;
+    loop {
+        let len = WINDOW.min(u64::MAX - lo + 1);
+        let sums = &mut sums[..len as usize];
+        sums.fill(0);
+
+        for (&p, &log) in primes.iter().zip(&logs) {
+            let mut q = p;
+            while q <= lo + (len - 1) {
+                let start = (q - lo % q) % q;
+                for j in (start..len).step_by(q as usize) {
+                    sums[j as usize] += log;
+                }
+
+                match q.checked_mul(p) {
+                    Some(x) => q = x,
+                    None => break,
+                }
+            }
+        }
+
+        let threshold = ((lo as f64).log2() * 256.0) as u16;
+        let found = (0..len)
+            .filter(|&j| sums[j as usize] + 64 >= threshold)
+            .map(|j| lo + j)
+            .find(|&m| is_smooth(m, primes));
+        if let Some(m) = found {
+            return Ok(m);
+        }
+
+        let scanned = (lo - n) + len;
+        if scanned >= SIEVE_LIMIT || lo.checked_add(len).is_none() {
+            return Err(scanned);
+        }
+        lo += len;
+    }
+}
+
+// When smooth integers are sparse, it's quicker to do a branch and bound search over products of
+// prime powers instead.
+fn search(n: u64, primes: &[u64]) -> Option<u64> {
+    let mut search = Search {
+        n,
+        primes,
+        best: u64::MAX as u128 + 1,
+    };
+    search.run(1, 0);
+
+    u64::try_from(search.best).ok()
+}
+
+// At each step, `c` is the product of the prime powers chosen so far, all from `primes[..i]`, and
+// we only look for multiples `c * r` with `n <= c * r < best`. Trying the largest powers first
+// finds a good bound quickly.
+struct Search<'a> {
+    n: u64,
+    primes: &'a [u64],
+    best: u128,
+}
+
+impl Search<'_> {
+    fn run(&mut self, c: u64, i: usize) {
+        let lo = self.n.div_ceil(c);
+        let hi = ((self.best - 1) / c as u128) as u64;
+        if lo > hi {
+            return;
+        }
+
+        if lo == 1 {
+            self.best = c as u128;
+            return;
+        }
+
+        if lo == hi {
+            if is_smooth(lo, self.primes) {
+                self.best = c as u128 * lo as u128;
+            }
+            return;
+        }
+
+        for j in (i..self.primes.len()).rev() {
+            let p = self.primes[j];
+            if p > hi {
+                continue;
+            }
+
+            let mut q = p;
+            while let Some(x) = q.checked_mul(p).filter(|&x| x <= hi) {
+                q = x;
+            }
+
+            loop {
+                self.run(c * q, j + 1);
+                if q == p {
+                    break;
+                }
+                q /= p;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn primes_up_to(bound: u64) -> Vec<u64> {
+        (2..=bound)
+            .filter(|&p| (2..p).all(|d| p % d != 0))
+            .collect()
+    }
+
+    fn next_smooth_naive(n: u64, bound: u64) -> u64 {
+        let primes = primes_up_to(bound);
+        (n.max(1)..).find(|&m| is_smooth(m, &primes)).unwrap()
+    }
+
+    #[test]
+    fn test_next_smooth_small() {
+        for bound in [2, 3, 5, 7, 13, 255] {
+            let primes = primes_up_to(bound);
+            for n in 0..2000 {
+                let expected = next_smooth_naive(n, bound);
+                assert_eq!(next_smooth(n, &PrimeSet::up_to(bound)), Some(expected));
+                assert_eq!(search(n.max(1), &primes), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_smooth_large() {
+        assert_eq!(next_smooth(257, &PrimeSet::up_to(255)), Some(258));
+        assert_eq!(next_smooth(0, &PrimeSet::up_to(1)), Some(1));
+        assert_eq!(next_smooth(2, &PrimeSet::up_to(1)), None);
+        assert_eq!(
+            next_smooth((1 << 40) + 1, &PrimeSet::up_to(2)),
+            Some(1 << 41)
+        );
+        assert_eq!(next_smooth(1 << 63, &PrimeSet::up_to(2)), Some(1 << 63));
+        assert_eq!(next_smooth((1 << 63) + 1, &PrimeSet::up_to(2)), None);
+        assert_eq!(next_smooth(u64::MAX, &PrimeSet::up_to(255)), None);
+        assert_eq!(next_smooth(u64::MAX, &PrimeSet::up_to(1000)), None);
+
+        for n in [10u64.pow(12) + 7, 3u64.pow(30) + 1] {
+            assert_eq!(
+                next_smooth(n, &PrimeSet::up_to(255)),
+                Some(next_smooth_naive(n, 255))
+            );
+        }
+        let primes = primes_up_to(31);
+        for n in [123457, 999983, 10u64.pow(8) + 7] {
+            let expected = next_smooth_naive(n, 31);
+            assert_eq!(next_smooth(n, &PrimeSet::up_to(31)), Some(expected));
+            assert_eq!(search(n, &primes), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_next_smooth_sparse() {
+        assert_eq!(
+            next_smooth((1 << 62) + 1, &PrimeSet::up_to(7)),
+            Some(4611840800000000000)
+        );
+        assert_eq!(
+            next_smooth(4611840800000000000, &PrimeSet::up_to(7)),
+            Some(4611840800000000000)
+        );
+
+        let primes = primes_up_to(13);
+        for n in [10u64.pow(15) + 1, u64::MAX / 5] {
+            let m = next_smooth(n, &PrimeSet::up_to(13)).unwrap();
+            assert!(m >= n && is_smooth(m, &primes));
+        }
+    }
+}
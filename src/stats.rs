@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+use crate::{assert_explicit, Permutation};
+
+/// The cycle type of a permutation: how many cycles it has of each length.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CycleStats {
+    // Cycle length to number of cycles, with no zero counts.
+    counts: BTreeMap<u64, u64>,
+}
+
+impl CycleStats {
+    /// The cycle type of the permutation that maps `i` to `table[i]`, which must be a
+    /// permutation of `0..table.len()`.
+    pub(crate) fn from_table(table: &[u64]) -> Self {
+        from_fn(table.len() as u64, |i| table[i as usize])
+    }
+
+    /// The cycle type of an `n`-cycle.
+    pub(crate) fn single_cycle(n: u64) -> Self {
+        Self {
+            counts: BTreeMap::from([(n, 1)]),
+        }
+    }
+
+    /// Returns the number of cycles, including fixed points.
+    #[must_use]
+    pub fn num_cycles(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Returns the length of the longest cycle, or 0 if there are no points.
+    #[must_use]
+    pub fn longest(&self) -> u64 {
+        self.counts.keys().next_back().copied().unwrap_or(0)
+    }
+
+    #[must_use]
+    pub fn fixed_points(&self) -> u64 {
+        self.count(1)
+    }
+
+    /// Returns the number of cycles of length `len`.
+    #[must_use]
+    pub fn count(&self, len: u64) -> u64 {
+        self.counts.get(&len).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of points, i.e. the sum of the cycle lengths.
+    #[must_use]
+    pub fn num_points(&self) -> u64 {
+        self.counts.iter().map(|(len, count)| len * count).sum()
+    }
+
+    /// Returns the number of cycles of each length, in increasing order of length.
+    #[must_use]
+    pub fn distribution(&self) -> &BTreeMap<u64, u64> {
+        &self.counts
+    }
+
+    /// Returns the cycle type of the permutation `(x, y) ↦ (σ(x), τ(y))` of pairs, where `σ` and
+    /// `τ` have cycle types `self` and `other`. A cycle of length `a` and a cycle of length `b`
+    /// combine into `gcd(a, b)` cycles of length `lcm(a, b)`.
+    #[must_use]
+    pub fn product(&self, other: &Self) -> Self {
+        let mut counts = BTreeMap::new();
+        for (&a, &ca) in &self.counts {
+            for (&b, &cb) in &other.counts {
+                let g = gcd(a, b);
+                *counts.entry(a / g * b).or_default() += ca * cb * g;
+            }
+        }
+        Self { counts }
+    }
+}
+
+/// Walks the cycles of `f`, a permutation of `0..n`, using n bits of memory.
+fn from_fn<F: Fn(u64) -> u64>(n: u64, f: F) -> CycleStats {
+    let mut seen = vec![0u64; n.div_ceil(64) as usize];
+    let mut counts = BTreeMap::new();
+
+    for i in 0..n {
+        if seen[(i / 64) as usize] >> (i % 64) & 1 == 1 {
+            continue;
+        }
+
+        let mut len = 0;
+        let mut j = i;
+        while seen[(j / 64) as usize] >> (j % 64) & 1 == 0 {
+            seen[(j / 64) as usize] |= 1 << (j % 64);
+            j = f(j);
+            len += 1;
+        }
+        *counts.entry(len).or_default() += 1;
+    }
+
+    CycleStats { counts }
+}
+
+pub fn cycle_stats<P: Permutation>(perm: &P) -> CycleStats {
+    assert_explicit(perm.num_points(), "cycle_stats");
+    from_fn(perm.num_points(), |i| perm.nth(i).unwrap())
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::{
+        CyclicPermutation, FnPermutation, OrderDividingPermutation, PhiloxPermutation,
+        RandomPermutation, SmallPermutation, MAX_EXPLICIT_POINTS,
+    };
+
+    #[test]
+    fn test_cycle_stats() {
+        // (0 1 2)(3 4)(5)(6)
+        let p = FnPermutation::new(7, |i| [1, 2, 0, 4, 3, 5, 6][i as usize]);
+        let stats = p.cycle_stats();
+        assert_eq!(stats.num_cycles(), 4);
+        assert_eq!(stats.longest(), 3);
+        assert_eq!(stats.fixed_points(), 2);
+        assert_eq!((stats.count(2), stats.count(4)), (1, 0));
+        assert_eq!(stats.num_points(), 7);
+        assert!(stats
+            .distribution()
+            .iter()
+            .eq([(&1, &2), (&2, &1), (&3, &1)]));
+
+        let stats = SmallPermutation::<0>::identity().cycle_stats();
+        assert_eq!((stats.num_cycles(), stats.longest()), (0, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cycle_stats_too_large() {
+        let p = PhiloxPermutation::with_key(MAX_EXPLICIT_POINTS + 1, 0).unwrap();
+        let _ = p.cycle_stats();
+    }
+
+    #[test]
+    fn test_product() {
+        let a = FnPermutation::new(5, |i| [1, 2, 0, 4, 3][i as usize]).cycle_stats();
+        let b = FnPermutation::new(6, |i| [1, 2, 3, 0, 4, 5][i as usize]).cycle_stats();
+        let p = FnPermutation::new(30, |i| {
+            let (x, y) = (i / 6, i % 6);
+            [1, 2, 0, 4, 3][x as usize] * 6 + [1, 2, 3, 0, 4, 5][y as usize]
+        });
+        assert_eq!(a.product(&b), p.cycle_stats());
+        assert_eq!(b.product(&a), p.cycle_stats());
+    }
+
+    #[test]
+    fn test_structured() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for n in [1, 10, 1009, 30030] {
+            let p = OrderDividingPermutation::with_rng(n, 12, &mut rng).unwrap();
+            assert_eq!(p.cycle_stats(), cycle_stats(&p));
+            assert!(p
+                .cycle_stats()
+                .distribution()
+                .keys()
+                .all(|len| 12 % len == 0));
+
+            let p = CyclicPermutation::with_rng(n, &mut rng);
+            if let Some(p) = p {
+                assert_eq!(p.cycle_stats(), cycle_stats(&p));
+            }
+        }
+
+        for n in [1, 12, 1000, 62208] {
+            let p = RandomPermutation::new_hybrid(n).unwrap();
+            assert_eq!(p.cycle_stats(), cycle_stats(&p));
+            assert_eq!(p.cycle_stats().num_points(), n);
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use rand::Rng;
+
+use crate::{Inversed, InvertiblePermutation, Permutation, PermutationExt, PhiloxPermutation};
+
+/// A permutation of the elements of `0..len` selected by a bitset, e.g. the active rows of a
+/// table, leaving the other elements in place.
+///
+/// As a `Permutation`, this permutes the ranks `0..count` of the selected elements with an inner
+/// permutation of `0..count`. Use `select` and `rank` to convert between ranks and elements, or
+/// `get` and `iter_elements` to work with the elements directly.
+///
+/// Element `i` is selected if bit `i % 64` of `words[i / 64]` is set. Ranks are computed with a
+/// table of prefix counts, one for each word, so `rank` takes O(1) time and `select` takes
+/// O(log(len)) time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubsetPermutation<P = PhiloxPermutation> {
+    perm: P,
+    len: u64,
+    words: Vec<u64>,
+    // `counts[i]` is the number of selected elements in `words[..i]`.
+    counts: Vec<u64>,
+}
+
+impl SubsetPermutation {
+    /// Permutes the elements selected by `words` with a `PhiloxPermutation`, which supports any
+    /// number of selected elements. Returns `None` for the same reasons as `from_bitset`.
+    pub fn with_rng<R: Rng + ?Sized>(words: Vec<u64>, len: u64, rng: &mut R) -> Option<Self> {
+        let count = words.iter().map(|w| w.count_ones() as u64).sum();
+        Self::from_bitset(words, len, PhiloxPermutation::with_rng(count, rng)?)
+    }
+
+    /// Like `with_rng`, but selects the elements of `0..len` for which `predicate` is true.
+    pub fn from_predicate_with_rng<F: FnMut(u64) -> bool, R: Rng + ?Sized>(
+        len: u64,
+        predicate: F,
+        rng: &mut R,
+    ) -> Option<Self> {
+        Self::with_rng(bitset(len, predicate)?, len, rng)
+    }
+}
+
+impl<P: Permutation> SubsetPermutation<P> {
+    /// Permutes the elements of `0..len` selected by `words` with `perm`. Returns `None` if
+    /// `words` doesn't have exactly `len.div_ceil(64)` words, if any bit at or past `len` is set,
+    /// or if `perm` doesn't have one point for each selected element.
+    #[must_use]
+    pub fn from_bitset(words: Vec<u64>, len: u64, perm: P) -> Option<Self> {
+        if !bitset_is_valid(&words, len) {
+            return None;
+        }
+
+        let counts = words
+            .iter()
+            .scan(0, |count, w| {
+                let prev = *count;
+                *count += w.count_ones() as u64;
+                Some(prev)
+            })
+            .collect::<Vec<_>>();
+
+        let count = counts
+            .last()
+            .map_or(0, |c| c + words.last().unwrap().count_ones() as u64);
+        if perm.num_points() != count {
+            return None;
+        }
+
+        Some(Self {
+            perm,
+            len,
+            words,
+            counts,
+        })
+    }
+
+    /// Like `from_bitset`, but selects the elements of `0..len` for which `predicate` is true.
+    #[must_use]
+    pub fn from_predicate<F: FnMut(u64) -> bool>(len: u64, predicate: F, perm: P) -> Option<Self> {
+        Self::from_bitset(bitset(len, predicate)?, len, perm)
+    }
+
+    /// The number of elements that the selected ones are chosen from.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn is_selected(&self, element: u64) -> bool {
+        element < self.len && self.words[(element / 64) as usize] >> (element % 64) & 1 == 1
+    }
+
+    /// Returns the rank of `element` among the selected elements, or `None` if it isn't selected.
+    #[must_use]
+    pub fn rank(&self, element: u64) -> Option<u64> {
+        if !self.is_selected(element) {
+            return None;
+        }
+
+        let (i, bit) = ((element / 64) as usize, element % 64);
+        let below = self.words[i] & ((1 << bit) - 1);
+        Some(self.counts[i] + below.count_ones() as u64)
+    }
+
+    /// Returns the selected element with the given rank, or `None` if `rank >= count`.
+    #[must_use]
+    pub fn select(&self, rank: u64) -> Option<u64> {
+        if rank >= self.num_points() {
+            return None;
+        }
+
+        // The last word whose prefix count is at most `rank` contains the element.
+        let i = self.counts.partition_point(|&count| count <= rank) - 1;
+        let mut word = self.words[i];
+        for _ in 0..rank - self.counts[i] {
+            word &= word - 1;
+        }
+        Some(i as u64 * 64 + word.trailing_zeros() as u64)
+    }
+
+    /// Returns the image of a selected element, or `None` if `element` isn't selected.
+    #[must_use]
+    pub fn get(&self, element: u64) -> Option<u64> {
+        self.select(self.perm.nth(self.rank(element)?)?)
+    }
+
+    /// Returns an iterator over the images of the selected elements, in increasing order of the
+    /// selected elements.
+    pub fn iter_elements(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.num_points()).map(|rank| self.select(self.perm.nth(rank).unwrap()).unwrap())
+    }
+
+    /// Applies the permutation to `slice`, moving the selected elements and leaving the others
+    /// in place, so that `slice[i]` becomes the old value of `slice[get(i)]` for each selected `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slice.len()` isn't `len`.
+    pub fn shuffle_selected<T: Clone>(&self, slice: &mut [T]) {
+        assert_eq!(
+            slice.len() as u64,
+            self.len,
+            "slice of length {} can't be shuffled by a subset of {} elements",
+            slice.len(),
+            self.len
+        );
+
+        let values = self
+            .iter_elements()
+            .map(|j| slice[j as usize].clone())
+            .collect::<Vec<_>>();
+        for (rank, value) in values.into_iter().enumerate() {
+            slice[self.select(rank as u64).unwrap() as usize] = value;
+        }
+    }
+
+    /// Returns the bitset of selected elements.
+    #[must_use]
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Returns the permutation of the ranks.
+    #[must_use]
+    pub fn permutation(&self) -> &P {
+        &self.perm
+    }
+}
+
+impl<P: Permutation> Permutation for SubsetPermutation<P> {
+    fn num_points(&self) -> u64 {
+        self.perm.num_points()
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.perm.nth(n)
+    }
+}
+
+impl<P: InvertiblePermutation> InvertiblePermutation for SubsetPermutation<P> {
+    type Inverse<'a>
+        = Inversed<&'a Self>
+    where
+        Self: 'a;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.perm.preimage(x)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+/// A random permutation of `0..len` that maps a subset onto itself, and so also maps its
+/// complement onto itself, e.g. to shuffle two groups of items without mixing them.
+///
+/// The subset and its complement are each permuted by a `SubsetPermutation` with an independent
+/// `PhiloxPermutation`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StabilizingPermutation {
+    len: u64,
+    // `None` if the subset or its complement is empty.
+    inside: Option<SubsetPermutation>,
+    outside: Option<SubsetPermutation>,
+}
+
+impl StabilizingPermutation {
+    /// Stabilizes the subset of `0..len` given by `words`, as in `SubsetPermutation::from_bitset`.
+    /// Returns `None` if `words` doesn't have exactly `len.div_ceil(64)` words, or if any bit at
+    /// or past `len` is set.
+    pub fn with_rng<R: Rng + ?Sized>(words: Vec<u64>, len: u64, rng: &mut R) -> Option<Self> {
+        if !bitset_is_valid(&words, len) {
+            return None;
+        }
+
+        let mut complement = words.iter().map(|w| !w).collect::<Vec<_>>();
+        if !len.is_multiple_of(64) {
+            *complement.last_mut().unwrap() &= (1 << (len % 64)) - 1;
+        }
+
+        let count = words.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+        let inside = if count == 0 {
+            None
+        } else {
+            Some(SubsetPermutation::with_rng(words, len, rng)?)
+        };
+        let outside = if count == len {
+            None
+        } else {
+            Some(SubsetPermutation::with_rng(complement, len, rng)?)
+        };
+
+        Some(Self {
+            len,
+            inside,
+            outside,
+        })
+    }
+
+    /// Like `with_rng`, but stabilizes the elements of `0..len` for which `predicate` is true.
+    pub fn from_predicate_with_rng<F: FnMut(u64) -> bool, R: Rng + ?Sized>(
+        len: u64,
+        predicate: F,
+        rng: &mut R,
+    ) -> Option<Self> {
+        Self::with_rng(bitset(len, predicate)?, len, rng)
+    }
+
+    /// Returns whether `element` is in the stabilized subset.
+    #[must_use]
+    pub fn contains(&self, element: u64) -> bool {
+        self.inside
+            .as_ref()
+            .is_some_and(|inside| inside.is_selected(element))
+    }
+
+    /// Returns the permutation of the subset, or `None` if the subset is empty.
+    #[must_use]
+    pub fn inside(&self) -> Option<&SubsetPermutation> {
+        self.inside.as_ref()
+    }
+
+    /// Returns the permutation of the complement, or `None` if the complement is empty.
+    #[must_use]
+    pub fn outside(&self) -> Option<&SubsetPermutation> {
+        self.outside.as_ref()
+    }
+
+    fn part(&self, element: u64) -> Option<&SubsetPermutation> {
+        if self.contains(element) {
+            self.inside.as_ref()
+        } else {
+            self.outside.as_ref()
+        }
+    }
+}
+
+impl Permutation for StabilizingPermutation {
+    fn num_points(&self) -> u64 {
+        self.len
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.part(n)?.get(n)
+    }
+}
+
+impl InvertiblePermutation for StabilizingPermutation {
+    type Inverse<'a> = Inversed<&'a Self>;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        let part = self.part(x)?;
+        part.select(part.preimage(part.rank(x)?)?)
+    }
+
+    fn inverse(&self) -> Inversed<&Self> {
+        self.inversed()
+    }
+}
+
+fn bitset_is_valid(words: &[u64], len: u64) -> bool {
+    words.len() as u64 == len.div_ceil(64)
+        && (len.is_multiple_of(64) || words.last().is_some_and(|w| w >> (len % 64) == 0))
+}
+
+/// The bitset of the elements of `0..len` for which `predicate` is true, or `None` if it would
+/// take more than `usize::MAX` words.
+fn bitset<F: FnMut(u64) -> bool>(len: u64, mut predicate: F) -> Option<Vec<u64>> {
+    let mut words = vec![0u64; usize::try_from(len.div_ceil(64)).ok()?];
+    for i in 0..len {
+        if predicate(i) {
+            words[(i / 64) as usize] |= 1 << (i % 64);
+        }
+    }
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+    use crate::RandomPermutation;
+
+    #[test]
+    fn test_rank_select() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let p =
+            SubsetPermutation::from_predicate_with_rng(1000, |i| i % 3 == 0 || i > 900, &mut rng)
+                .unwrap();
+        let selected = (0..1000)
+            .filter(|i| i % 3 == 0 || *i > 900)
+            .collect::<Vec<_>>();
+        assert_eq!(p.num_points(), selected.len() as u64);
+
+        for (rank, &i) in selected.iter().enumerate() {
+            assert_eq!(p.rank(i), Some(rank as u64));
+            assert_eq!(p.select(rank as u64), Some(i));
+        }
+        assert_eq!(p.rank(1), None);
+        assert_eq!(p.rank(1000), None);
+        assert_eq!(p.select(selected.len() as u64), None);
+    }
+
+    #[test]
+    fn test_elements() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = SubsetPermutation::from_predicate_with_rng(500, |i| i % 7 < 3, &mut rng).unwrap();
+
+        let mut images = p.iter_elements().collect::<Vec<_>>();
+        images.sort();
+        assert!(images.into_iter().eq((0..500).filter(|i| i % 7 < 3)));
+
+        for (rank, image) in p.iter_elements().enumerate() {
+            let i = p.select(rank as u64).unwrap();
+            assert_eq!(p.get(i), Some(image));
+            assert_eq!(p.rank(image), p.nth(rank as u64));
+            assert_eq!(p.preimage(p.rank(image).unwrap()), Some(rank as u64));
+        }
+        assert_eq!(p.get(3), None);
+    }
+
+    #[test]
+    fn test_shuffle_selected() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let p = SubsetPermutation::from_predicate_with_rng(100, |i| i % 2 == 0, &mut rng).unwrap();
+
+        let mut rows = (0..100).collect::<Vec<_>>();
+        p.shuffle_selected(&mut rows);
+        for i in 0..100 {
+            if i % 2 == 0 {
+                assert_eq!(Some(rows[i as usize]), p.get(i));
+            } else {
+                assert_eq!(rows[i as usize], i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_bitset() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let perm = RandomPermutation::with_rng(6, &mut rng).unwrap();
+        let all = RandomPermutation::with_rng(70, &mut rng).unwrap();
+        let p = SubsetPermutation::from_bitset(vec![u64::MAX, 0b111111], 70, all).unwrap();
+        assert_eq!(p.num_points(), 70);
+        assert_eq!(p.select(69), Some(69));
+
+        assert_eq!(
+            SubsetPermutation::from_bitset(vec![0b111, 0b111], 70, perm.clone()),
+            Some(SubsetPermutation {
+                perm: perm.clone(),
+                len: 70,
+                words: vec![0b111, 0b111],
+                counts: vec![0, 3],
+            })
+        );
+        assert_eq!(
+            SubsetPermutation::from_bitset(vec![0b111], 70, perm.clone()),
+            None
+        );
+        assert_eq!(
+            SubsetPermutation::from_bitset(vec![0b111, 0b111], 64, perm.clone()),
+            None
+        );
+        assert_eq!(
+            SubsetPermutation::from_bitset(vec![0b111, 1 << 6 | 0b11], 70, perm.clone()),
+            None
+        );
+        assert_eq!(
+            SubsetPermutation::from_bitset(vec![0b11111], 70, perm),
+            None
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        assert_eq!(SubsetPermutation::with_rng(vec![0], 10, &mut rng), None);
+    }
+
+    #[test]
+    fn test_stabilizing() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+        let premium = |i| i % 10 == 0 || (500..600).contains(&i);
+        let p = StabilizingPermutation::from_predicate_with_rng(1000, premium, &mut rng).unwrap();
+        assert_eq!(p.num_points(), 1000);
+
+        let mut values = p.iter().collect::<Vec<_>>();
+        for (i, &x) in values.iter().enumerate() {
+            assert_eq!(premium(x), premium(i as u64));
+            assert_eq!(p.contains(x), premium(x));
+            assert_eq!(p.preimage(x), Some(i as u64));
+        }
+        values.sort();
+        assert!(values.into_iter().eq(0..1000));
+
+        // The groups are actually shuffled.
+        assert!((0..1000).filter(|&i| p.nth(i) != Some(i)).count() > 900);
+        assert_eq!(p.nth(1000), None);
+        assert_eq!(p.preimage(1000), None);
+    }
+
+    #[test]
+    fn test_stabilizing_trivial() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(6);
+
+        for words in [vec![0, 0], vec![u64::MAX, 0b11]] {
+            let p = StabilizingPermutation::with_rng(words, 66, &mut rng).unwrap();
+            let mut values = p.iter().collect::<Vec<_>>();
+            values.sort();
+            assert!(values.into_iter().eq(0..66));
+            assert_eq!(p.inside().is_some(), p.contains(0));
+            assert_eq!(p.outside().is_some(), !p.contains(0));
+        }
+
+        assert_eq!(
+            StabilizingPermutation::with_rng(vec![0], 66, &mut rng),
+            None
+        );
+        assert_eq!(
+            StabilizingPermutation::with_rng(vec![0, 0b100], 66, &mut rng),
+            None
+        );
+        assert_eq!(
+            StabilizingPermutation::with_rng(vec![0, 0b111], 66, &mut rng),
+            None
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+
+use rand::Rng;
+
+use crate::{shuffled_table, InvertiblePermutation, Mode, Permutation};
+
+/// The reason a table of values isn't a permutation of `0..n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FromVecError {
+    /// The table has `found` entries, but `expected` were required.
+    WrongLength { expected: usize, found: usize },
+    /// The entry at `index` is `value`, which is not in `0..len`.
+    OutOfRange {
+        index: usize,
+        value: u64,
+        len: usize,
+    },
+    /// `value` appears at both `first` and `second`.
+    Duplicate {
+        value: u64,
+        first: usize,
+        second: usize,
+    },
+}
+
+impl fmt::Display for FromVecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongLength { expected, found } => {
+                write!(f, "expected {expected} values, found {found}")
+            }
+            Self::OutOfRange { index, value, len } => {
+                write!(f, "value {value} at index {index} is not less than {len}")
+            }
+            Self::Duplicate {
+                value,
+                first,
+                second,
+            } => write!(f, "value {value} appears at indices {first} and {second}"),
+        }
+    }
+}
+
+impl std::error::Error for FromVecError {}
+
+// Checks that `mapping` is a bijection on `0..mapping.len()`, reporting the first bad entry.
+pub fn validate(mapping: &[u64]) -> Result<(), FromVecError> {
+    let len = mapping.len();
+    let mut positions = vec![usize::MAX; len];
+    for (index, &value) in mapping.iter().enumerate() {
+        let slot = usize::try_from(value)
+            .ok()
+            .and_then(|v| positions.get_mut(v))
+            .ok_or(FromVecError::OutOfRange { index, value, len })?;
+
+        if *slot != usize::MAX {
+            return Err(FromVecError::Duplicate {
+                value,
+                first: *slot,
+                second: index,
+            });
+        }
+        *slot = index;
+    }
+
+    Ok(())
+}
+
+/// A uniformly random permutation of `0..n`, stored as an explicit table.
+///
+/// Unlike `RandomPermutation`, every permutation of `0..n` can be generated and `n` can be any
+/// integer, but this uses O(n) memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Vec<u64>", into = "Vec<u64>")
+)]
+pub struct UniformPermutation {
+    mapping: Vec<u64>,
+}
+
+impl UniformPermutation {
+    #[must_use]
+    pub fn new(n: u64) -> Self {
+        Self::with_rng(n, &mut rand::thread_rng())
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(n: u64, rng: &mut R) -> Self {
+        Self {
+            mapping: shuffled_table(n, rng),
+        }
+    }
+
+    #[must_use]
+    pub fn inverse(&self) -> Self {
+        let mut mapping = vec![0; self.mapping.len()];
+        for (i, &x) in self.mapping.iter().enumerate() {
+            mapping[x as usize] = i as u64;
+        }
+
+        Self { mapping }
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> Mode {
+        Mode::Uniform
+    }
+
+    #[must_use]
+    pub fn as_slice(&self) -> &[u64] {
+        &self.mapping
+    }
+
+    #[must_use]
+    pub fn into_vec(mut self) -> Vec<u64> {
+        std::mem::take(&mut self.mapping)
+    }
+}
+
+impl From<UniformPermutation> for Vec<u64> {
+    fn from(perm: UniformPermutation) -> Self {
+        perm.into_vec()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for UniformPermutation {
+    fn zeroize(&mut self) {
+        self.mapping.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for UniformPermutation {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for UniformPermutation {}
+
+/// Checks that the values are a bijection on `0..len`, so untrusted tables can be used safely.
+impl TryFrom<Vec<u64>> for UniformPermutation {
+    type Error = FromVecError;
+
+    fn try_from(mapping: Vec<u64>) -> Result<Self, Self::Error> {
+        validate(&mapping)?;
+        Ok(Self { mapping })
+    }
+}
+
+impl Permutation for UniformPermutation {
+    fn num_points(&self) -> u64 {
+        self.mapping.len() as u64
+    }
+
+    fn nth(&self, n: u64) -> Option<u64> {
+        self.mapping.get(usize::try_from(n).ok()?).copied()
+    }
+}
+
+/// Searches the table, so this takes O(n) time. Use `inverse` to evaluate many preimages.
+impl InvertiblePermutation for UniformPermutation {
+    type Inverse<'a> = Self;
+
+    fn preimage(&self, x: u64) -> Option<u64> {
+        self.mapping.iter().position(|&a| a == x).map(|i| i as u64)
+    }
+
+    /// Builds the inverse table, in O(n) time.
+    fn inverse(&self) -> Self {
+        Self::inverse(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    use super::*;
+
+    #[test]
+    fn test_with_rng() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // Not a product of small primes, so not supported by `RandomPermutation`.
+        let p = UniformPermutation::with_rng(1009, &mut rng);
+        let mut vec = p.iter().collect::<Vec<_>>();
+        vec.sort();
+
+        assert!(vec.into_iter().eq(0..1009));
+        assert_eq!(p.nth(1009), None);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let p = UniformPermutation::with_rng(500, &mut rng);
+        let inv = p.inverse();
+
+        for i in 0..500 {
+            assert_eq!(inv.nth(p.nth(i).unwrap()), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_preimage() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let p = UniformPermutation::with_rng(300, &mut rng);
+        let inv = p.inverse();
+
+        for x in 0..300 {
+            assert_eq!(p.preimage(x), inv.nth(x));
+        }
+        assert_eq!(p.preimage(300), None);
+    }
+
+    #[test]
+    fn test_uniform() {
+        // All 3! = 6 permutations of 3 points should be reachable.
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let mut seen = (0..1000)
+            .map(|_| UniformPermutation::with_rng(3, &mut rng).into_vec())
+            .collect::<Vec<_>>();
+        seen.sort();
+        seen.dedup();
+
+        assert_eq!(seen.len(), 6);
+    }
+
+    #[test]
+    fn test_try_from() {
+        let p = UniformPermutation::try_from(vec![2, 0, 3, 1]).unwrap();
+        assert_eq!(p.as_slice(), &[2, 0, 3, 1]);
+        assert!(UniformPermutation::try_from(vec![]).is_ok());
+
+        assert_eq!(
+            UniformPermutation::try_from(vec![2, 0, 4, 1]),
+            Err(FromVecError::OutOfRange {
+                index: 2,
+                value: 4,
+                len: 4
+            })
+        );
+        assert_eq!(
+            UniformPermutation::try_from(vec![0, u64::MAX]),
+            Err(FromVecError::OutOfRange {
+                index: 1,
+                value: u64::MAX,
+                len: 2
+            })
+        );
+        assert_eq!(
+            UniformPermutation::try_from(vec![1, 3, 0, 3]),
+            Err(FromVecError::Duplicate {
+                value: 3,
+                first: 1,
+                second: 3
+            })
+        );
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let mut p = UniformPermutation::with_rng(100, &mut rng);
+        p.zeroize();
+        assert!(p.as_slice().is_empty());
+
+        let p = UniformPermutation::with_rng(100, &mut rng);
+        assert_eq!(p.clone().into_vec(), p.as_slice());
+    }
+}
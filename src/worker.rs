@@ -0,0 +1,163 @@
+use crate::{Permutation, PermutationFamily, PhiloxPermutation};
+
+/// One worker's portion of an epoch's permuted order. See `PermutationFamily::iter_for_worker`.
+///
+/// Worker `rank` gets the values at positions `rank, rank + world_size, ...` of the epoch's
+/// permutation, so the workers together see every value exactly once per epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerIter {
+    perm: PhiloxPermutation,
+    world_size: u64,
+    rank: u64,
+    position: u64,
+}
+
+impl WorkerIter {
+    pub(crate) fn new(
+        family: &PermutationFamily,
+        n: u64,
+        world_size: u64,
+        rank: u64,
+        epoch: u64,
+    ) -> Option<Self> {
+        assert!(
+            rank < world_size,
+            "rank {rank} is out of range for a world size of {world_size}"
+        );
+
+        Some(Self {
+            perm: family.get(n, epoch)?,
+            world_size,
+            rank,
+            position: 0,
+        })
+    }
+
+    /// Returns the total number of values this worker gets in the epoch.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        match self.perm.num_points().checked_sub(self.rank) {
+            Some(0) | None => 0,
+            Some(k) => (k - 1) / self.world_size + 1,
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values this worker has been given so far. Pass this to `seek` to
+    /// resume the epoch later.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Moves to the `position`-th value of this worker's portion, clamped to `0..=len`.
+    pub fn seek(&mut self, position: u64) {
+        self.position = position.min(self.len());
+    }
+}
+
+impl Iterator for WorkerIter {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.len() {
+            return None;
+        }
+
+        let a = self.perm.nth(self.rank + self.position * self.world_size);
+        self.position += 1;
+        a
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len() - self.position;
+        (
+            usize::try_from(remaining).unwrap_or(usize::MAX),
+            usize::try_from(remaining).ok(),
+        )
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.seek(self.position.saturating_add(n as u64));
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Permutation, PermutationFamily};
+
+    #[test]
+    fn test_workers_cover_epoch() {
+        let family = PermutationFamily::with_key(7);
+
+        for (n, world_size) in [(1, 1), (1, 4), (1000, 1), (1000, 3), (1009, 8), (5, 8)] {
+            let mut values = (0..world_size)
+                .flat_map(|rank| family.iter_for_worker(n, world_size, rank, 0).unwrap())
+                .collect::<Vec<_>>();
+            values.sort();
+            assert!(values.into_iter().eq(0..n));
+
+            let lens = (0..world_size)
+                .map(|rank| {
+                    family
+                        .iter_for_worker(n, world_size, rank, 0)
+                        .unwrap()
+                        .len()
+                })
+                .sum::<u64>();
+            assert_eq!(lens, n);
+        }
+    }
+
+    #[test]
+    fn test_epochs() {
+        let family = PermutationFamily::with_key(7);
+        let epoch = |epoch| {
+            family
+                .iter_for_worker(1000, 4, 1, epoch)
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(epoch(0), epoch(0));
+        assert_ne!(epoch(0), epoch(1));
+
+        let p = family.get(1000, 1).unwrap();
+        assert!(epoch(1)
+            .into_iter()
+            .eq((1..1000).step_by(4).map(|i| p.nth(i).unwrap())));
+    }
+
+    #[test]
+    fn test_resume() {
+        let family = PermutationFamily::with_key(7);
+        let mut iter = family.iter_for_worker(1009, 8, 3, 2).unwrap();
+        let expected = iter.clone().collect::<Vec<_>>();
+        assert_eq!(iter.size_hint(), (expected.len(), Some(expected.len())));
+
+        iter.by_ref().take(50).for_each(drop);
+        let position = iter.position();
+        assert_eq!(position, 50);
+
+        let mut resumed = family.iter_for_worker(1009, 8, 3, 2).unwrap();
+        resumed.seek(position);
+        assert!(resumed.eq(expected[50..].iter().copied()));
+
+        let mut iter = family.iter_for_worker(1009, 8, 3, 2).unwrap();
+        assert_eq!(iter.nth(10), Some(expected[10]));
+        assert_eq!(iter.nth(1000), None);
+        assert_eq!(iter.position(), iter.len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_rank() {
+        let family = PermutationFamily::with_key(7);
+        let _ = family.iter_for_worker(1000, 4, 4, 0);
+    }
+}